@@ -0,0 +1,2095 @@
+//! `!bug` - A shared bug tracker for the bot.
+//!
+//! # Usage
+//!
+//! * `!bug [show] [NUMBER]` - List the open bugs, or show the details of a bug.
+//!   The list shows the highest priority bugs first, then the ones with the most
+//!   +1s.
+//! * `!bug report <NAME> <SUMMARY> <DETAILS>` - Report a new bug. The name and
+//!   summary need to be quoted if they contain spaces, e.g.
+//!   `!bug report "todo crash" "Crashes on add" Steps to reproduce...`.
+//!   Labels can be added at the end with `--labels`, e.g.
+//!   `!bug report typo "Typo in help" It says "teh" --labels docs,easy`.
+//! * `!bug (plus-one, +1) <NUMBER>` - Say that you've also run into a bug.
+//! * `!bug label add <NUMBER> <LABEL>` - Add a label to a bug.
+//! * `!bug label remove <NUMBER> <LABEL>` - Remove a label from a bug.
+//! * `!bug priority <NUMBER> <PRIORITY>` - Set a bug's priority, e.g. `!bug
+//!   priority 3 10`. Bugs start with priority 0.
+//! * `!bug search <TERM>` - Find bugs with the term in their name, summary, or
+//!   labels.
+//! * `!bug assign <NUMBER> <@USER>` - Assign someone to fix a bug.
+//! * `!bug unassign <NUMBER>` - Clear a bug's assignee.
+//! * `!bug mine` - List the open bugs you reported or are assigned to.
+//! * `!bug close <NUMBER>` - Close a bug. The reporter and everyone who +1'd
+//!   it get a DM saying it was closed.
+//! * `!bug reopen <NUMBER>` - Reopen a closed bug, e.g. if it wasn't really
+//!   fixed.
+//! * `!bug dup <NUMBER> <ORIGINAL>` - Close a bug as a duplicate of another one.
+//! * `!bug (remove, rm, delete) <NUMBER>` - Delete a bug, e.g. one reported by
+//!   mistake. Its number isn't given to any later bug.
+//! * `!bug watch <NUMBER>` - Get a DM whenever someone else changes a bug, e.g.
+//!   closes it, +1s it, or assigns it.
+//! * `!bug unwatch <NUMBER>` - Stop getting DMs about a bug.
+//! * `!bug help` - Show how to use the `!bug` commands. This is also shown when
+//!   a `!bug` command isn't recognized.
+//!
+//! Each server has its own list of bugs, numbered in the order they're
+//! reported, starting from 1. Bugs reported in DMs with the bot go in a
+//! personal list for the user instead.
+
+use crate::retry::{with_retry, BASE_DELAY};
+use crate::{serenity, Context, Data, Error};
+use anyhow::{bail, Context as _, Result};
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::options::ReplaceOptions;
+use poise::serenity_prelude::User;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use tracing::{error, info};
+
+/// The name of the collection where bug lists are stored.
+const COLLECTION_NAME: &str = "bug_lists";
+
+/// The name of the collection where the commands that changed each bug list are
+/// logged. See [`replay`].
+const LOG_COLLECTION_NAME: &str = "bug_commands";
+
+/// Show the open bugs, or a single bug by number.
+#[poise::command(
+    prefix_command,
+    slash_command,
+    subcommands(
+        "show", "report", "plus_one", "label", "priority", "search", "assign", "unassign", "mine",
+        "close", "reopen", "dup", "remove", "watch", "unwatch", "help"
+    ),
+    on_error = "bug_error"
+)]
+pub async fn bug(ctx: Context<'_>, number: Option<u32>) -> Result<(), Error> {
+    run_command(ctx, print_command(number)).await
+}
+
+/// Replies with the `!bug` usage when the arguments to `!bug` can't be parsed,
+/// e.g. because the subcommand was misspelled, instead of the generic argument
+/// error.
+async fn bug_error(error: poise::FrameworkError<'_, Data, Error>) {
+    let poise::FrameworkError::ArgumentParse { ctx, .. } = error else {
+        crate::on_error(error).await;
+        return;
+    };
+
+    if let Err(e) = ctx.say(unrecognized_message(ctx.command())).await {
+        error!("Error sending bug usage: {:?}", e);
+    }
+}
+
+/// Builds the response for a `!bug` command that couldn't be parsed, e.g.
+/// `!bug foo bar baz`, from the usage of the `!bug` command.
+fn unrecognized_message(command: &poise::Command<Data, Error>) -> String {
+    format!(
+        "I didn't recognize that `!bug` command.\n\n{}",
+        crate::command_help(command)
+    )
+}
+
+/// Show how to use the `!bug` commands.
+#[poise::command(prefix_command, slash_command)]
+pub async fn help(ctx: Context<'_>) -> Result<(), Error> {
+    // This is a subcommand, so the usage comes from the parent `!bug` command.
+    let command = ctx
+        .parent_commands()
+        .first()
+        .copied()
+        .unwrap_or_else(|| ctx.command());
+    ctx.say(crate::command_help(command)).await?;
+    Ok(())
+}
+
+/// List the open bugs, or show the details of a single bug.
+#[poise::command(prefix_command, slash_command)]
+pub async fn show(ctx: Context<'_>, number: Option<u32>) -> Result<(), Error> {
+    run_command(ctx, print_command(number)).await
+}
+
+/// Report a new bug.
+#[poise::command(prefix_command, slash_command, on_error = "report_error")]
+pub async fn report(
+    ctx: Context<'_>,
+    #[description = "A short name for the bug"] name: String,
+    #[description = "A one line description of the bug"] summary: String,
+    #[description = "Steps to reproduce the bug, etc. End with \"--labels a,b\" to add labels"]
+    #[rest]
+    details: String,
+) -> Result<(), Error> {
+    let (details, labels) = split_labels(&details);
+    let command = BugCommand::Report {
+        name,
+        summary,
+        details,
+        labels,
+    };
+    run_command(ctx, command).await
+}
+
+/// Replies with the expected format when a bug report can't be parsed, instead
+/// of the generic argument error.
+async fn report_error(error: poise::FrameworkError<'_, Data, Error>) {
+    let poise::FrameworkError::ArgumentParse { error, ctx, .. } = error else {
+        crate::on_error(error).await;
+        return;
+    };
+
+    if let Err(e) = ctx.say(report_error_message(error.as_ref())).await {
+        error!("Error sending bug report usage: {:?}", e);
+    }
+}
+
+/// Builds the response for a bug report that couldn't be parsed.
+fn report_error_message(error: &(dyn std::error::Error + Send + Sync + 'static)) -> String {
+    let problem = if error.is::<poise::TooFewArguments>() {
+        "A bug report needs a name, a summary, and details.".to_string()
+    } else {
+        format!("Couldn't read that bug report: {error}.")
+    };
+    format!(
+        "{problem} Quote the name and summary if they contain spaces, e.g. \
+        `!bug report \"todo crash\" \"Crashes on add\" Steps to reproduce...`"
+    )
+}
+
+/// Say that you've also run into a bug.
+#[poise::command(prefix_command, slash_command, rename = "plus-one", aliases("+1"))]
+pub async fn plus_one(ctx: Context<'_>, number: u32) -> Result<(), Error> {
+    run_command(ctx, BugCommand::PlusOne(number)).await
+}
+
+/// Add or remove a bug's labels.
+#[poise::command(
+    prefix_command,
+    slash_command,
+    subcommands("label_add", "label_remove")
+)]
+pub async fn label(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Use `!bug label add <number> <label>` or `!bug label remove <number> <label>`")
+        .await?;
+    Ok(())
+}
+
+/// Add a label to a bug.
+#[poise::command(prefix_command, slash_command, rename = "add")]
+pub async fn label_add(ctx: Context<'_>, number: u32, #[rest] label: String) -> Result<(), Error> {
+    run_command(ctx, BugCommand::AddLabel { number, label }).await
+}
+
+/// Remove a label from a bug.
+#[poise::command(prefix_command, slash_command, rename = "remove")]
+pub async fn label_remove(
+    ctx: Context<'_>,
+    number: u32,
+    #[rest] label: String,
+) -> Result<(), Error> {
+    run_command(ctx, BugCommand::RemoveLabel { number, label }).await
+}
+
+/// Set a bug's priority. Higher priority bugs are listed first.
+#[poise::command(prefix_command, slash_command)]
+pub async fn priority(ctx: Context<'_>, number: u32, priority: u32) -> Result<(), Error> {
+    run_command(ctx, BugCommand::SetPriority { number, priority }).await
+}
+
+/// Find bugs with the search term in their name, summary, or labels.
+#[poise::command(prefix_command, slash_command)]
+pub async fn search(ctx: Context<'_>, #[rest] term: String) -> Result<(), Error> {
+    run_command(ctx, BugCommand::Search(term)).await
+}
+
+/// Assign someone to fix a bug.
+#[poise::command(prefix_command, slash_command)]
+pub async fn assign(ctx: Context<'_>, number: u32, user: serenity::User) -> Result<(), Error> {
+    let command = BugCommand::Assign {
+        number,
+        assignee: Some(user.id),
+    };
+    run_command(ctx, command).await
+}
+
+/// Clear the assignee of a bug.
+#[poise::command(prefix_command, slash_command)]
+pub async fn unassign(ctx: Context<'_>, number: u32) -> Result<(), Error> {
+    let command = BugCommand::Assign {
+        number,
+        assignee: None,
+    };
+    run_command(ctx, command).await
+}
+
+/// List the open bugs you reported or are assigned to.
+#[poise::command(prefix_command, slash_command)]
+pub async fn mine(ctx: Context<'_>) -> Result<(), Error> {
+    run_command(ctx, BugCommand::Mine).await
+}
+
+/// Close a bug, e.g. once it's been fixed.
+#[poise::command(prefix_command, slash_command)]
+pub async fn close(ctx: Context<'_>, number: u32) -> Result<(), Error> {
+    run_command(ctx, BugCommand::Close(number)).await
+}
+
+/// Reopen a closed bug, e.g. if it turns out it wasn't fixed.
+#[poise::command(prefix_command, slash_command)]
+pub async fn reopen(ctx: Context<'_>, number: u32) -> Result<(), Error> {
+    run_command(ctx, BugCommand::Reopen(number)).await
+}
+
+/// Close a bug as a duplicate of an earlier report.
+#[poise::command(prefix_command, slash_command)]
+pub async fn dup(
+    ctx: Context<'_>,
+    #[description = "The duplicate bug"] number: u32,
+    #[description = "The bug it duplicates"] original: u32,
+) -> Result<(), Error> {
+    run_command(ctx, BugCommand::Duplicate { number, original }).await
+}
+
+/// Delete a bug, e.g. one that was reported by mistake.
+#[poise::command(prefix_command, slash_command, aliases("rm", "delete"))]
+pub async fn remove(ctx: Context<'_>, number: u32) -> Result<(), Error> {
+    run_command(ctx, BugCommand::Remove(number)).await
+}
+
+/// Get a DM whenever someone else changes a bug.
+#[poise::command(prefix_command, slash_command)]
+pub async fn watch(ctx: Context<'_>, number: u32) -> Result<(), Error> {
+    run_command(ctx, BugCommand::Watch(number)).await
+}
+
+/// Stop getting DMs about changes to a bug.
+#[poise::command(prefix_command, slash_command)]
+pub async fn unwatch(ctx: Context<'_>, number: u32) -> Result<(), Error> {
+    run_command(ctx, BugCommand::Unwatch(number)).await
+}
+
+/// Splits a trailing `--labels a,b` flag off the end of a bug's details.
+///
+/// Labels are trimmed and empty ones are dropped. If there's no flag the details
+/// are returned unchanged, with no labels.
+fn split_labels(details: &str) -> (String, Vec<String>) {
+    let Some((before, labels)) = details.rsplit_once("--labels") else {
+        return (details.into(), Vec::new());
+    };
+
+    // Only treat `--labels` as a flag when it's a separate word, so that details
+    // that happen to contain it aren't cut short.
+    let is_flag = (before.is_empty() || before.ends_with(char::is_whitespace))
+        && (labels.is_empty() || labels.starts_with(char::is_whitespace));
+    if !is_flag {
+        return (details.into(), Vec::new());
+    }
+
+    let labels = labels
+        .split(',')
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(String::from)
+        .collect();
+    (before.trim_end().into(), labels)
+}
+
+/// Picks the command for showing either one bug or all of the open bugs.
+fn print_command(number: Option<u32>) -> BugCommand {
+    match number {
+        Some(number) => BugCommand::Print(number),
+        None => BugCommand::PrintAll,
+    }
+}
+
+/// Loads the bug list for the current guild (or the user's own list in DMs)
+/// from the database, processes the command, and saves the list if it changed.
+async fn run_command(ctx: Context<'_>, command: BugCommand) -> Result<()> {
+    let store = DbBugStore::new(ctx.data());
+    let guild_id = ctx.guild_id();
+    let user_id = ctx.author().id;
+    let outcome = apply_command(&store, guild_id, ctx.author(), &command).await?;
+
+    // Append the command to the list's command log so that the list can be
+    // reconstructed later with `replay`. The list has already been saved, so a
+    // failure here is only logged.
+    if outcome.changed {
+        let entry = BugLogEntry {
+            guild_id,
+            user_id,
+            command,
+        };
+        let result = ctx
+            .data()
+            .db
+            .collection(LOG_COLLECTION_NAME)
+            .insert_one(entry, None)
+            .await;
+        if let Err(e) = result {
+            let query = list_query(guild_id, user_id);
+            error!("Failed to log bug command for {query}: {:?}", e);
+        }
+    }
+
+    ctx.say(outcome.response).await?;
+
+    if let Some((before, after)) = outcome.bug_change {
+        notify_changes(ctx, &before, &after).await;
+    }
+
+    Ok(())
+}
+
+/// How many times a command is tried against a freshly loaded bug list when
+/// someone else keeps changing the list at the same time.
+const MAX_SAVE_ATTEMPTS: u32 = 5;
+
+/// The result of running a command against a stored bug list.
+struct Outcome {
+    response: String,
+
+    /// Whether the command changed the list, in which case it was saved.
+    changed: bool,
+
+    /// The bug the command changes, as it was before and after the command.
+    bug_change: Option<(BugItem, BugItem)>,
+}
+
+/// Runs a command against the bug list for `guild_id` (or the author's own list
+/// in DMs), and saves the list if the command changed it.
+///
+/// Lists are shared by everyone in a guild, so someone else may save the list
+/// between it being loaded and saved here. In that case the command is run
+/// again against the newly saved list, so that neither change is lost and two
+/// bugs are never given the same number.
+async fn apply_command(
+    store: &impl BugStore,
+    guild_id: Option<serenity::GuildId>,
+    author: &User,
+    command: &BugCommand,
+) -> Result<Outcome> {
+    let query = list_query(guild_id, author.id);
+    for _ in 0..MAX_SAVE_ATTEMPTS {
+        let mut bug_list = store
+            .find(&query)
+            .await?
+            .unwrap_or_else(|| BugList::new(guild_id, author.id));
+
+        // Remember the bug as it was before the command, so that people can be told
+        // what changed once it's done.
+        let before = command
+            .number()
+            .and_then(|number| bug_list.get(number))
+            .cloned();
+
+        // Only save the list if the command changed it, so that just viewing the
+        // bugs doesn't write to the database. Read-only commands can't change it,
+        // so there's no need to copy the list for them.
+        let list_before = (!command.is_read_only()).then(|| bug_list.clone());
+        let response = handle_command(command.clone(), &mut bug_list, author);
+        let changed = list_before.is_some_and(|list_before| list_before != bug_list);
+
+        if changed {
+            let version = bug_list.version;
+            bug_list.version += 1;
+            if !store.save(&query, &bug_list, version).await? {
+                info!("Bug list for {query} was changed by someone else, trying again");
+                continue;
+            }
+        }
+
+        let bug_change = before.and_then(|before| {
+            let after = bug_list.get(before.number)?.clone();
+            Some((before, after))
+        });
+        return Ok(Outcome {
+            response,
+            changed,
+            bug_change,
+        });
+    }
+
+    bail!("Bug list for {query} kept changing, gave up after {MAX_SAVE_ATTEMPTS} attempts")
+}
+
+/// Where bug lists are stored, i.e. [`DbBugStore`] outside of tests.
+trait BugStore {
+    async fn find(&self, query: &Document) -> Result<Option<BugList>>;
+
+    /// Saves `bug_list` over the stored list, but only if the stored list is
+    /// still at `version`, i.e. nobody else saved it since it was loaded. Returns
+    /// whether the list was saved.
+    async fn save(&self, query: &Document, bug_list: &BugList, version: u32) -> Result<bool>;
+}
+
+/// The database collection of bug lists.
+struct DbBugStore {
+    collection: mongodb::Collection<BugList>,
+    retries: u32,
+}
+
+impl DbBugStore {
+    fn new(data: &Data) -> Self {
+        Self {
+            collection: data.db.collection(COLLECTION_NAME),
+            retries: data.db_retries,
+        }
+    }
+}
+
+impl BugStore for DbBugStore {
+    async fn find(&self, query: &Document) -> Result<Option<BugList>> {
+        with_retry(self.retries, BASE_DELAY, || {
+            self.collection.find_one(query.clone(), None)
+        })
+        .await
+        .with_context(|| format!("Failed to get bug list for {query}"))
+    }
+
+    async fn save(&self, query: &Document, bug_list: &BugList, version: u32) -> Result<bool> {
+        // Lists that haven't been saved yet are inserted. Two brand new lists saved
+        // at the same moment can both be inserted, since nothing makes the query
+        // unique, but after that every save is checked against the version.
+        let filter = versioned_query(query, version);
+        let options = ReplaceOptions::builder().upsert(version == 0).build();
+        let result = with_retry(self.retries, BASE_DELAY, || {
+            self.collection
+                .replace_one(filter.clone(), bug_list, options.clone())
+        })
+        .await
+        .with_context(|| format!("Failed to update bug list for {query}"))?;
+        if result.matched_count > 0 || result.upserted_id.is_some() {
+            return Ok(true);
+        }
+
+        // A retried save may have reached the database before failing, in which
+        // case the stored list is already this one.
+        Ok(self.find(query).await?.as_ref() == Some(bug_list))
+    }
+}
+
+/// Adds a check that the stored list is still at `version` to the list's query.
+/// Lists saved before versions were stored don't have one, and count as
+/// version 0.
+fn versioned_query(query: &Document, version: u32) -> Document {
+    let mut query = query.clone();
+    if version == 0 {
+        query.insert("version", doc! { "$in": [0, Bson::Null] });
+    } else {
+        query.insert("version", version);
+    }
+    query
+}
+
+/// DMs the people following a bug about a change to it.
+///
+/// When a bug is closed its reporter and everyone who +1'd it are told. Watchers
+/// are told about every change, unless they made it or were already told that the
+/// bug was closed.
+async fn notify_changes(ctx: Context<'_>, before: &BugItem, after: &BugItem) {
+    let mut notified = Vec::new();
+    if before.status == BugStatus::Open && after.status == BugStatus::Closed {
+        let message = format!("Bug #{} {:?} has been closed.", after.number, after.name);
+        for user_id in close_recipients(after) {
+            send_dm(ctx, user_id, &message).await;
+            notified.push(user_id);
+        }
+    }
+
+    let Some(message) = change_message(before, after) else {
+        return;
+    };
+    for user_id in watch_recipients(after, ctx.author().id) {
+        if !notified.contains(&user_id) {
+            send_dm(ctx, user_id, &message).await;
+        }
+    }
+}
+
+/// Sends a DM about a bug. Failures are only logged, since users can block DMs
+/// from the bot.
+async fn send_dm(ctx: Context<'_>, user_id: serenity::UserId, message: &str) {
+    let result = match user_id.create_dm_channel(ctx).await {
+        Ok(channel) => channel.say(ctx, message).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        info!("Couldn't send bug update to user {user_id}: {e}");
+    }
+}
+
+/// Describes what changed about a bug for its watchers, or returns `None` if
+/// nothing they're told about changed.
+fn change_message(before: &BugItem, after: &BugItem) -> Option<String> {
+    let mut changes = Vec::new();
+
+    if before.status != after.status {
+        changes.push(match (after.status, after.duplicate_of) {
+            (BugStatus::Closed, Some(original)) => {
+                format!("it was closed as a duplicate of #{original}")
+            }
+            (BugStatus::Closed, None) => "it was closed".into(),
+            (BugStatus::Open, _) => "it was reopened".into(),
+        });
+    }
+
+    if before.plus_one_count() != after.plus_one_count() {
+        changes.push(format!(
+            "{} people have hit it now",
+            after.plus_one_count() + 1
+        ));
+    }
+
+    if before.assignee != after.assignee {
+        changes.push(match after.assignee {
+            Some(assignee) => format!("it was assigned to <@{assignee}>"),
+            None => "it was unassigned".into(),
+        });
+    }
+
+    if changes.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "Bug #{} {:?} was updated: {}",
+        after.number,
+        after.name,
+        changes.join(", ")
+    ))
+}
+
+/// Gets the watchers to notify about a change to a bug, leaving out the user who
+/// made the change.
+fn watch_recipients(bug: &BugItem, author: serenity::UserId) -> Vec<serenity::UserId> {
+    let mut recipients = Vec::new();
+    for &user_id in &bug.watchers {
+        if user_id != author && !recipients.contains(&user_id) {
+            recipients.push(user_id);
+        }
+    }
+    recipients
+}
+
+/// Gets the users to notify when a bug is closed: the reporter, then everyone
+/// who +1'd it, with each user only appearing once.
+fn close_recipients(bug: &BugItem) -> Vec<serenity::UserId> {
+    let mut recipients = vec![bug.reporter];
+    for &user_id in &bug.plus_ones {
+        if !recipients.contains(&user_id) {
+            recipients.push(user_id);
+        }
+    }
+    recipients
+}
+
+/// Builds the query for finding the bug list that commands sent in `guild_id`
+/// apply to. Commands sent outside of a guild use the user's own list.
+fn list_query(guild_id: Option<serenity::GuildId>, user_id: serenity::UserId) -> Document {
+    match guild_id {
+        Some(guild_id) => doc! { "guild_id": guild_id.to_string() },
+        None => doc! { "user_id": user_id.to_string() },
+    }
+}
+
+/// The list of bugs reported in a guild, or by a user in DMs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BugList {
+    /// The guild the list belongs to, if it was created in a guild.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    guild_id: Option<serenity::GuildId>,
+
+    /// The user the list belongs to, if it was created in DMs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user_id: Option<serenity::UserId>,
+
+    /// How many times the list has been saved, used to detect when someone else
+    /// saved it in the meantime. See [`BugStore::save`].
+    #[serde(default)]
+    version: u32,
+
+    /// The number of the last bug that was reported. Numbers are never reused,
+    /// even once the bug has been removed. See [`BugList::next_number`].
+    #[serde(default)]
+    last_number: u32,
+
+    /// The reported bugs, in the order they were reported.
+    items: Vec<BugItem>,
+}
+
+impl BugList {
+    /// Creates an empty list for the guild, or for the user if there's no guild.
+    fn new(guild_id: Option<serenity::GuildId>, user_id: serenity::UserId) -> Self {
+        BugList {
+            guild_id,
+            user_id: guild_id.is_none().then_some(user_id),
+            ..Default::default()
+        }
+    }
+
+    /// Picks the number for the next reported bug.
+    ///
+    /// Lists saved before `last_number` was stored don't have it, so it's never
+    /// allowed to fall behind the numbers that are already in use.
+    fn next_number(&self) -> u32 {
+        let highest = self.items.iter().map(|bug| bug.number).max();
+        self.last_number.max(highest.unwrap_or_default()) + 1
+    }
+
+    fn get(&self, number: u32) -> Option<&BugItem> {
+        self.items.iter().find(|bug| bug.number == number)
+    }
+
+    fn get_mut(&mut self, number: u32) -> Option<&mut BugItem> {
+        self.items.iter_mut().find(|bug| bug.number == number)
+    }
+}
+
+/// A single reported bug.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BugItem {
+    pub number: u32,
+    pub name: String,
+    pub summary: String,
+    pub details: String,
+    pub reporter: serenity::UserId,
+    pub status: BugStatus,
+
+    /// The users other than the reporter that have also run into the bug.
+    pub plus_ones: Vec<serenity::UserId>,
+
+    /// Labels for grouping related bugs, e.g. `docs` or `crash`.
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    /// How important the bug is, higher is more important.
+    #[serde(default)]
+    pub priority: u32,
+
+    /// The user responsible for fixing the bug, if anyone.
+    #[serde(default)]
+    pub assignee: Option<serenity::UserId>,
+
+    /// The number of the bug this one was closed as a duplicate of.
+    #[serde(default)]
+    pub duplicate_of: Option<u32>,
+
+    /// The users that get a DM whenever the bug changes.
+    #[serde(default)]
+    pub watchers: Vec<serenity::UserId>,
+}
+
+impl BugItem {
+    /// Counts the users other than the reporter that have +1'd the bug.
+    ///
+    /// `!bug +1` never adds anyone twice, but each user is still only counted once
+    /// in case a saved list has repeats.
+    fn plus_one_count(&self) -> usize {
+        let mut users = self
+            .plus_ones
+            .iter()
+            .filter(|&&user_id| user_id != self.reporter)
+            .collect::<Vec<_>>();
+        users.sort();
+        users.dedup();
+        users.len()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BugStatus {
+    #[default]
+    Open,
+    Closed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BugCommand {
+    PrintAll,
+    Print(u32),
+
+    Report {
+        name: String,
+        summary: String,
+        details: String,
+        labels: Vec<String>,
+    },
+
+    PlusOne(u32),
+
+    /// Adds a label to a bug, unless it already has it.
+    AddLabel {
+        number: u32,
+        label: String,
+    },
+
+    /// Removes a label from a bug.
+    RemoveLabel {
+        number: u32,
+        label: String,
+    },
+
+    /// Sets a bug's priority. Higher priority bugs are listed first.
+    SetPriority {
+        number: u32,
+        priority: u32,
+    },
+
+    /// Finds the bugs whose name, summary, or labels contain the search term,
+    /// ignoring case.
+    Search(String),
+
+    /// Lists the open bugs the author reported or is assigned to.
+    Mine,
+
+    /// Sets or clears the user assigned to a bug.
+    Assign {
+        number: u32,
+        assignee: Option<serenity::UserId>,
+    },
+
+    Close(u32),
+
+    /// Opens a closed bug again, including one closed as a duplicate.
+    Reopen(u32),
+
+    /// Deletes a bug from the list. Its number isn't reused.
+    Remove(u32),
+
+    /// Closes bug `number` as a duplicate of bug `original`.
+    Duplicate {
+        number: u32,
+        original: u32,
+    },
+
+    /// Adds the author to a bug's watchers.
+    Watch(u32),
+
+    /// Removes the author from a bug's watchers.
+    Unwatch(u32),
+}
+
+impl BugCommand {
+    /// Checks if the command only reads the list, i.e. it never changes it.
+    fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            BugCommand::PrintAll | BugCommand::Print(_) | BugCommand::Search(_) | BugCommand::Mine
+        )
+    }
+
+    /// Gets the number of the bug the command changes, if it changes one.
+    fn number(&self) -> Option<u32> {
+        match *self {
+            BugCommand::PlusOne(number)
+            | BugCommand::AddLabel { number, .. }
+            | BugCommand::RemoveLabel { number, .. }
+            | BugCommand::SetPriority { number, .. }
+            | BugCommand::Assign { number, .. }
+            | BugCommand::Close(number)
+            | BugCommand::Reopen(number)
+            | BugCommand::Duplicate { number, .. }
+            | BugCommand::Watch(number)
+            | BugCommand::Unwatch(number) => Some(number),
+            _ => None,
+        }
+    }
+}
+
+/// Builds a compact code block listing the given bugs, one per line.
+fn bug_table(bugs: &[&BugItem]) -> String {
+    let mut table = "```\n".to_string();
+    for bug in bugs {
+        write!(&mut table, "#{} {}\t{}", bug.number, bug.name, bug.summary).unwrap();
+        let plus_ones = bug.plus_one_count();
+        if plus_ones > 0 {
+            write!(&mut table, " (+{plus_ones})").unwrap();
+        }
+        table.push('\n');
+    }
+    table.push_str("```\n");
+    table
+}
+
+/// Builds the response to changing a bug's labels, listing its labels now.
+fn label_message(bug: &BugItem) -> String {
+    if bug.labels.is_empty() {
+        return format!("Bug #{} has no labels now", bug.number);
+    }
+
+    format!("Bug #{} is labeled {}", bug.number, bug.labels.join(", "))
+}
+
+/// Gets the open bugs with the highest priority first, then the ones with the
+/// most +1s, and in the order they were reported otherwise.
+fn sorted_open_bugs(bug_list: &BugList) -> Vec<&BugItem> {
+    let mut bugs = bug_list
+        .items
+        .iter()
+        .filter(|bug| bug.status == BugStatus::Open)
+        .collect::<Vec<_>>();
+    bugs.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then(b.plus_one_count().cmp(&a.plus_one_count()))
+            .then(a.number.cmp(&b.number))
+    });
+    bugs
+}
+
+/// A single entry in a bug list's append-only command log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BugLogEntry {
+    /// The guild of the list the command changed, or `None` for a user's own list.
+    pub guild_id: Option<serenity::GuildId>,
+
+    /// The user that ran the command.
+    pub user_id: serenity::UserId,
+
+    pub command: BugCommand,
+}
+
+/// Reconstructs a bug list by replaying its logged commands, in order, onto a
+/// fresh list. `user_id` is the owner of the list for lists made in DMs.
+pub fn replay(
+    guild_id: Option<serenity::GuildId>,
+    user_id: serenity::UserId,
+    entries: impl IntoIterator<Item = BugLogEntry>,
+) -> BugList {
+    let mut bug_list = BugList::new(guild_id, user_id);
+    for entry in entries {
+        let mut author = User::default();
+        author.id = entry.user_id;
+        handle_command(entry.command, &mut bug_list, &author);
+    }
+
+    bug_list
+}
+
+fn handle_command(command: BugCommand, bug_list: &mut BugList, author: &User) -> String {
+    let user_id = author.id;
+
+    match command {
+        BugCommand::Report {
+            name,
+            summary,
+            details,
+            labels,
+        } => {
+            let number = bug_list.next_number();
+            bug_list.last_number = number;
+            bug_list.items.push(BugItem {
+                number,
+                name: name.clone(),
+                summary,
+                details,
+                reporter: user_id,
+                status: BugStatus::Open,
+                plus_ones: Vec::new(),
+                labels,
+                priority: 0,
+                assignee: None,
+                duplicate_of: None,
+                watchers: Vec::new(),
+            });
+
+            info!("User {user_id} reported bug #{number} {name:?}");
+
+            format!("Reported bug #{number} {name:?}")
+        }
+
+        BugCommand::PlusOne(number) => {
+            let Some(bug) = bug_list.get_mut(number) else {
+                return format!("I couldn't find bug #{number}");
+            };
+
+            if bug.reporter == user_id || bug.plus_ones.contains(&user_id) {
+                return format!("You've already +1'd bug #{number}");
+            }
+
+            bug.plus_ones.push(user_id);
+
+            info!("User {user_id} +1'd bug #{number}");
+
+            format!(
+                "+1'd bug #{number}, {} people have hit it",
+                bug.plus_one_count() + 1
+            )
+        }
+
+        BugCommand::AddLabel { number, label } => {
+            let Some(bug) = bug_list.get_mut(number) else {
+                return format!("I couldn't find bug #{number}");
+            };
+
+            let label = label.trim();
+            if label.is_empty() {
+                return "Labels can't be empty".into();
+            }
+            if bug.labels.iter().any(|existing| existing == label) {
+                return format!("Bug #{number} already has the label {label:?}");
+            }
+
+            bug.labels.push(label.into());
+
+            info!("User {user_id} added label {label:?} to bug #{number}");
+
+            label_message(bug)
+        }
+
+        BugCommand::RemoveLabel { number, label } => {
+            let Some(bug) = bug_list.get_mut(number) else {
+                return format!("I couldn't find bug #{number}");
+            };
+
+            let label = label.trim();
+            if !bug.labels.iter().any(|existing| existing == label) {
+                return format!("Bug #{number} doesn't have the label {label:?}");
+            }
+
+            bug.labels.retain(|existing| existing != label);
+
+            info!("User {user_id} removed label {label:?} from bug #{number}");
+
+            label_message(bug)
+        }
+
+        BugCommand::SetPriority { number, priority } => {
+            let Some(bug) = bug_list.get_mut(number) else {
+                return format!("I couldn't find bug #{number}");
+            };
+
+            bug.priority = priority;
+
+            info!("User {user_id} set the priority of bug #{number} to {priority}");
+
+            format!("Set the priority of bug #{number} to {priority}")
+        }
+
+        BugCommand::Assign { number, assignee } => {
+            let Some(bug) = bug_list.get_mut(number) else {
+                return format!("I couldn't find bug #{number}");
+            };
+
+            let previous = std::mem::replace(&mut bug.assignee, assignee);
+
+            info!("User {user_id} assigned bug #{number} to {assignee:?}");
+
+            match (previous, assignee) {
+                (None, None) => format!("Bug #{number} isn't assigned to anyone"),
+                (Some(_), None) => format!("Unassigned bug #{number}"),
+                (Some(previous), Some(assignee)) if previous != assignee => {
+                    format!("Reassigned bug #{number} from <@{previous}> to <@{assignee}>")
+                }
+                (_, Some(assignee)) => format!("Assigned bug #{number} to <@{assignee}>"),
+            }
+        }
+
+        BugCommand::Close(number) => {
+            let Some(bug) = bug_list.get_mut(number) else {
+                return format!("I couldn't find bug #{number}");
+            };
+
+            if bug.status == BugStatus::Closed {
+                return format!("Bug #{number} is already closed");
+            }
+
+            bug.status = BugStatus::Closed;
+
+            info!("User {user_id} closed bug #{number}");
+
+            format!("Closed bug #{number}")
+        }
+
+        BugCommand::Reopen(number) => {
+            let Some(bug) = bug_list.get_mut(number) else {
+                return format!("I couldn't find bug #{number}");
+            };
+
+            if bug.status == BugStatus::Open {
+                return format!("Bug #{number} isn't closed");
+            }
+
+            bug.status = BugStatus::Open;
+            bug.duplicate_of = None;
+
+            info!("User {user_id} reopened bug #{number}");
+
+            format!("Reopened bug #{number}")
+        }
+
+        BugCommand::Remove(number) => {
+            let Some(index) = bug_list.items.iter().position(|bug| bug.number == number) else {
+                return format!("I couldn't find bug #{number}");
+            };
+
+            let bug = bug_list.items.remove(index);
+
+            info!("User {user_id} removed bug #{number}");
+
+            format!("Removed bug #{number} {:?}", bug.name)
+        }
+
+        BugCommand::Duplicate { number, original } => {
+            if number == original {
+                return "A bug can't be a duplicate of itself".into();
+            }
+
+            if bug_list.get(original).is_none() {
+                return format!("I couldn't find bug #{original}");
+            }
+
+            let Some(bug) = bug_list.get_mut(number) else {
+                return format!("I couldn't find bug #{number}");
+            };
+
+            bug.status = BugStatus::Closed;
+            bug.duplicate_of = Some(original);
+
+            info!("User {user_id} marked bug #{number} as a duplicate of #{original}");
+
+            format!("Closed bug #{number} as a duplicate of #{original}")
+        }
+
+        BugCommand::Watch(number) => {
+            let Some(bug) = bug_list.get_mut(number) else {
+                return format!("I couldn't find bug #{number}");
+            };
+
+            if bug.watchers.contains(&user_id) {
+                return format!("You're already watching bug #{number}");
+            }
+
+            bug.watchers.push(user_id);
+
+            info!("User {user_id} is watching bug #{number}");
+
+            format!("You're now watching bug #{number}, I'll DM you when it changes")
+        }
+
+        BugCommand::Unwatch(number) => {
+            let Some(bug) = bug_list.get_mut(number) else {
+                return format!("I couldn't find bug #{number}");
+            };
+
+            if !bug.watchers.contains(&user_id) {
+                return format!("You weren't watching bug #{number}");
+            }
+
+            bug.watchers.retain(|&watcher| watcher != user_id);
+
+            info!("User {user_id} stopped watching bug #{number}");
+
+            format!("You're no longer watching bug #{number}")
+        }
+
+        BugCommand::Print(number) => {
+            let Some(bug) = bug_list.get(number) else {
+                return format!("I couldn't find bug #{number}");
+            };
+
+            let status = match bug.status {
+                BugStatus::Open => "Open",
+                BugStatus::Closed => "Closed",
+            };
+
+            let assignee = match bug.assignee {
+                Some(assignee) => format!("<@{assignee}>"),
+                None => "unassigned".into(),
+            };
+
+            let duplicate = match bug.duplicate_of {
+                Some(original) => format!("Duplicate of #{original}\n"),
+                None => String::new(),
+            };
+
+            let labels = if bug.labels.is_empty() {
+                String::new()
+            } else {
+                format!("Labels: {}\n", bug.labels.join(", "))
+            };
+
+            format!(
+                "**#{number} {}**\n\
+                Status: {status}\n\
+                {duplicate}\
+                Reported by: <@{}>\n\
+                Assignee: {assignee}\n\
+                Priority: {}\n\
+                {labels}\
+                +1s: {}\n\
+                \n\
+                {}\n\
+                \n\
+                {}",
+                bug.name,
+                bug.reporter,
+                bug.priority,
+                bug.plus_one_count(),
+                bug.summary,
+                bug.details,
+            )
+        }
+
+        BugCommand::PrintAll => {
+            let open_bugs = sorted_open_bugs(bug_list);
+
+            if open_bugs.is_empty() {
+                return "No open bugs 🎉".into();
+            }
+
+            format!("Open bugs:\n{}", bug_table(&open_bugs))
+        }
+
+        BugCommand::Mine => {
+            let bugs = bug_list
+                .items
+                .iter()
+                .filter(|bug| bug.status == BugStatus::Open)
+                .filter(|bug| bug.reporter == user_id || bug.assignee == Some(user_id))
+                .collect::<Vec<_>>();
+
+            if bugs.is_empty() {
+                return "You have no bugs".into();
+            }
+
+            format!("Your open bugs:\n{}", bug_table(&bugs))
+        }
+
+        BugCommand::Search(term) => {
+            let query = term.to_lowercase();
+            let matches = bug_list
+                .items
+                .iter()
+                .filter(|bug| {
+                    bug.name.to_lowercase().contains(&query)
+                        || bug.summary.to_lowercase().contains(&query)
+                        || bug
+                            .labels
+                            .iter()
+                            .any(|label| label.to_lowercase().contains(&query))
+                })
+                .collect::<Vec<_>>();
+
+            if matches.is_empty() {
+                return format!("No bugs matched {term:?}");
+            }
+
+            format!("Bugs matching {term:?}:\n{}", bug_table(&matches))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bug::{self, BugCommand, BugItem, BugList, BugLogEntry, BugStatus, BugStore};
+    use anyhow::Result;
+    use mongodb::bson::{self, doc, Document};
+    use poise::serenity_prelude::model::user::User;
+    use poise::serenity_prelude::{GuildId, UserId};
+    use pretty_assertions::assert_eq;
+    use std::cell::{Cell, RefCell};
+
+    fn user(id: u64) -> User {
+        let mut user = User::default();
+        user.id = UserId(id);
+        user.name = format!("user{id}");
+        user
+    }
+
+    /// Stores a single bug list, and can have someone else report a bug just before
+    /// the next save so that the save conflicts.
+    #[derive(Default)]
+    struct FakeBugStore {
+        list: RefCell<Option<BugList>>,
+        report_before_save: Cell<bool>,
+        saves: Cell<usize>,
+    }
+
+    impl BugStore for FakeBugStore {
+        async fn find(&self, _query: &Document) -> Result<Option<BugList>> {
+            Ok(self.list.borrow().clone())
+        }
+
+        async fn save(&self, _query: &Document, bug_list: &BugList, version: u32) -> Result<bool> {
+            self.saves.set(self.saves.get() + 1);
+            let mut list = self.list.borrow_mut();
+            if self.report_before_save.take() {
+                let stored = list.get_or_insert_with(|| BugList::new(Some(GuildId(1)), UserId(2)));
+                report(stored, "Other", &user(2));
+                stored.version += 1;
+            }
+
+            let stored_version = list.as_ref().map_or(0, |list| list.version);
+            if stored_version != version {
+                return Ok(false);
+            }
+            *list = Some(bug_list.clone());
+            Ok(true)
+        }
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn report(state: &mut BugList, name: &str, reporter: &User) -> String {
+        let command = BugCommand::Report {
+            name: name.into(),
+            summary: format!("{name} summary"),
+            details: format!("{name} details"),
+            labels: Vec::new(),
+        };
+        bug::handle_command(command, state, reporter)
+    }
+
+    /// Verifies that bugs can be reported and are numbered in order.
+    #[test]
+    fn report_and_print() {
+        let mut state = BugList::default();
+        let alice = user(1);
+
+        let response = bug::handle_command(BugCommand::PrintAll, &mut state, &alice);
+        assert_eq!("No open bugs 🎉", response);
+
+        let response = report(&mut state, "crash", &alice);
+        assert_eq!(r#"Reported bug #1 "crash""#, response);
+        let response = report(&mut state, "typo", &alice);
+        assert_eq!(r#"Reported bug #2 "typo""#, response);
+
+        let response = bug::handle_command(BugCommand::PrintAll, &mut state, &alice);
+        assert_eq!(
+            "Open bugs:\n\
+            ```\n\
+            #1 crash\tcrash summary\n\
+            #2 typo\ttypo summary\n\
+            ```\n",
+            response,
+        );
+
+        let response = bug::handle_command(BugCommand::Print(2), &mut state, &alice);
+        assert_eq!(
+            "**#2 typo**\n\
+            Status: Open\n\
+            Reported by: <@1>\n\
+            Assignee: unassigned\n\
+            Priority: 0\n\
+            +1s: 0\n\
+            \n\
+            typo summary\n\
+            \n\
+            typo details",
+            response,
+        );
+
+        let response = bug::handle_command(BugCommand::Print(3), &mut state, &alice);
+        assert_eq!("I couldn't find bug #3", response);
+
+        // Closed bugs aren't listed.
+        state.items[0].status = BugStatus::Closed;
+        let response = bug::handle_command(BugCommand::PrintAll, &mut state, &alice);
+        assert_eq!(
+            "Open bugs:\n\
+            ```\n\
+            #2 typo\ttypo summary\n\
+            ```\n",
+            response,
+        );
+    }
+
+    /// Verifies that the open bug list puts each bug on its own line inside a code
+    /// block, and says when there's nothing open.
+    #[test]
+    fn print_all_lines() {
+        let mut state = BugList::default();
+        let alice = user(1);
+
+        let response = bug::handle_command(BugCommand::PrintAll, &mut state, &alice);
+        assert_eq!("No open bugs 🎉", response);
+
+        report(&mut state, "crash", &alice);
+        report(&mut state, "typo", &alice);
+        let response = bug::handle_command(BugCommand::PrintAll, &mut state, &alice);
+        let lines = response.lines().collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "Open bugs:",
+                "```",
+                "#1 crash\tcrash summary",
+                "#2 typo\ttypo summary",
+                "```",
+            ],
+            lines,
+        );
+    }
+
+    /// Verifies that each user can only +1 a bug once.
+    #[test]
+    fn plus_one() {
+        let mut state = BugList::default();
+        let (alice, bob) = (user(1), user(2));
+        report(&mut state, "crash", &alice);
+
+        let response = bug::handle_command(BugCommand::PlusOne(1), &mut state, &bob);
+        assert_eq!("+1'd bug #1, 2 people have hit it", response);
+
+        let response = bug::handle_command(BugCommand::PlusOne(1), &mut state, &bob);
+        assert_eq!("You've already +1'd bug #1", response);
+        let response = bug::handle_command(BugCommand::PlusOne(1), &mut state, &alice);
+        assert_eq!("You've already +1'd bug #1", response);
+        assert_eq!(vec![bob.id], state.items[0].plus_ones);
+
+        let response = bug::handle_command(BugCommand::PlusOne(2), &mut state, &bob);
+        assert_eq!("I couldn't find bug #2", response);
+
+        let response = bug::handle_command(BugCommand::PrintAll, &mut state, &alice);
+        assert_eq!(
+            "Open bugs:\n\
+            ```\n\
+            #1 crash\tcrash summary (+1)\n\
+            ```\n",
+            response,
+        );
+
+        // Repeats in a saved list only count once, and the reporter doesn't count.
+        state.items[0].plus_ones = vec![bob.id, alice.id, bob.id];
+        assert_eq!(1, state.items[0].plus_one_count());
+        let response = bug::handle_command(BugCommand::Print(1), &mut state, &alice);
+        assert!(response.contains("\n+1s: 1\n"));
+        let response = bug::handle_command(BugCommand::PrintAll, &mut state, &alice);
+        assert!(response.contains("#1 crash\tcrash summary (+1)\n"));
+    }
+
+    /// Verifies that each guild gets its own bug list, and that DMs use a list
+    /// for the user.
+    #[test]
+    fn list_keys() {
+        let (guild, user) = (GuildId(10), UserId(1));
+
+        assert_eq!(
+            doc! { "guild_id": "10" },
+            bug::list_query(Some(guild), user)
+        );
+        assert_eq!(doc! { "user_id": "1" }, bug::list_query(None, user));
+
+        // New lists store the same key they're looked up by, so that they can be
+        // found again after they're inserted.
+        let list = bson::to_document(&BugList::new(Some(guild), user)).unwrap();
+        assert_eq!(Some("10"), list.get_str("guild_id").ok());
+        assert!(!list.contains_key("user_id"));
+
+        let list = bson::to_document(&BugList::new(None, user)).unwrap();
+        assert_eq!(Some("1"), list.get_str("user_id").ok());
+        assert!(!list.contains_key("guild_id"));
+    }
+
+    /// Verifies that bugs can be searched by name, summary, and labels, ignoring
+    /// case.
+    #[test]
+    fn search() {
+        let mut state = BugList::default();
+        let alice = user(1);
+        report(&mut state, "Crash on add", &alice);
+        report(&mut state, "typo", &alice);
+        bug::handle_command(
+            BugCommand::Report {
+                name: "slow".into(),
+                summary: "Adding items is slow".into(),
+                details: "".into(),
+                labels: Vec::new(),
+            },
+            &mut state,
+            &alice,
+        );
+
+        let response = bug::handle_command(BugCommand::Search("crash".into()), &mut state, &alice);
+        assert_eq!(
+            "Bugs matching \"crash\":\n\
+            ```\n\
+            #1 Crash on add\tCrash on add summary\n\
+            ```\n",
+            response,
+        );
+
+        let response = bug::handle_command(BugCommand::Search("ADD".into()), &mut state, &alice);
+        assert_eq!(
+            "Bugs matching \"ADD\":\n\
+            ```\n\
+            #1 Crash on add\tCrash on add summary\n\
+            #3 slow\tAdding items is slow\n\
+            ```\n",
+            response,
+        );
+
+        let response = bug::handle_command(BugCommand::Search("panic".into()), &mut state, &alice);
+        assert_eq!(r#"No bugs matched "panic""#, response);
+
+        let label = BugCommand::AddLabel {
+            number: 2,
+            label: "Docs".into(),
+        };
+        bug::handle_command(label, &mut state, &alice);
+        let response = bug::handle_command(BugCommand::Search("doc".into()), &mut state, &alice);
+        assert_eq!(
+            "Bugs matching \"doc\":\n\
+            ```\n\
+            #2 typo\ttypo summary\n\
+            ```\n",
+            response,
+        );
+    }
+
+    /// Verifies that bugs can be assigned, reassigned, and unassigned.
+    #[test]
+    fn assign() {
+        let mut state = BugList::default();
+        let (alice, bob) = (user(1), user(2));
+        report(&mut state, "crash", &alice);
+
+        let assign = |number, assignee: Option<&User>| BugCommand::Assign {
+            number,
+            assignee: assignee.map(|user| user.id),
+        };
+
+        let response = bug::handle_command(assign(1, Some(&bob)), &mut state, &alice);
+        assert_eq!("Assigned bug #1 to <@2>", response);
+        assert_eq!(Some(bob.id), state.items[0].assignee);
+
+        let response = bug::handle_command(BugCommand::Print(1), &mut state, &alice);
+        assert!(response.contains("\nAssignee: <@2>\n"));
+
+        let response = bug::handle_command(assign(1, Some(&alice)), &mut state, &alice);
+        assert_eq!("Reassigned bug #1 from <@2> to <@1>", response);
+        assert_eq!(Some(alice.id), state.items[0].assignee);
+
+        let response = bug::handle_command(assign(1, None), &mut state, &alice);
+        assert_eq!("Unassigned bug #1", response);
+        assert_eq!(None, state.items[0].assignee);
+
+        let response = bug::handle_command(assign(1, None), &mut state, &alice);
+        assert_eq!("Bug #1 isn't assigned to anyone", response);
+
+        let response = bug::handle_command(assign(2, Some(&bob)), &mut state, &alice);
+        assert_eq!("I couldn't find bug #2", response);
+    }
+
+    /// Verifies that the assignee is saved, and that bugs saved before bugs could
+    /// be assigned still load.
+    #[test]
+    fn assignee_serialization() {
+        let mut state = BugList::default();
+        report(&mut state, "crash", &user(1));
+        state.items[0].assignee = Some(UserId(2));
+
+        let doc = bson::to_document(&state.items[0]).unwrap();
+        assert_eq!(Some("2"), doc.get_str("assignee").ok());
+        assert_eq!(state.items[0], bson::from_document::<BugItem>(doc).unwrap());
+
+        let mut doc = bson::to_document(&state.items[0]).unwrap();
+        doc.remove("assignee");
+        assert_eq!(None, bson::from_document::<BugItem>(doc).unwrap().assignee);
+    }
+
+    /// Verifies that `mine` only lists the author's open bugs.
+    #[test]
+    fn mine() {
+        let mut state = BugList::default();
+        let (alice, bob) = (user(1), user(2));
+
+        let response = bug::handle_command(BugCommand::Mine, &mut state, &alice);
+        assert_eq!("You have no bugs", response);
+
+        report(&mut state, "crash", &alice);
+        report(&mut state, "typo", &bob);
+        report(&mut state, "slow", &bob);
+        report(&mut state, "fixed", &alice);
+        state.items[3].status = BugStatus::Closed;
+
+        // Bugs the author is assigned to count as theirs too.
+        let command = BugCommand::Assign {
+            number: 3,
+            assignee: Some(alice.id),
+        };
+        bug::handle_command(command, &mut state, &bob);
+
+        let response = bug::handle_command(BugCommand::Mine, &mut state, &alice);
+        assert_eq!(
+            "Your open bugs:\n\
+            ```\n\
+            #1 crash\tcrash summary\n\
+            #3 slow\tslow summary\n\
+            ```\n",
+            response,
+        );
+
+        let response = bug::handle_command(BugCommand::Mine, &mut state, &bob);
+        assert_eq!(
+            "Your open bugs:\n\
+            ```\n\
+            #2 typo\ttypo summary\n\
+            #3 slow\tslow summary\n\
+            ```\n",
+            response,
+        );
+    }
+
+    /// Verifies that labels can be given at the end of a report's details.
+    #[test]
+    fn report_labels() {
+        let split = |details| bug::split_labels(details);
+        let labels = |labels: &[&str]| labels.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            ("It crashes".to_string(), labels(&["crash", "todo"])),
+            split("It crashes --labels crash,todo"),
+        );
+        assert_eq!(
+            ("It crashes".to_string(), labels(&["crash", "todo list"])),
+            split("It crashes --labels  crash , ,todo list,"),
+        );
+        assert_eq!((String::new(), labels(&["docs"])), split("--labels docs"));
+        assert_eq!(
+            ("It crashes".to_string(), labels(&[])),
+            split("It crashes --labels")
+        );
+
+        // Without the flag the details are left alone.
+        assert_eq!(("It crashes".to_string(), labels(&[])), split("It crashes"));
+        assert_eq!(
+            ("Try --labelsfoo".to_string(), labels(&[])),
+            split("Try --labelsfoo"),
+        );
+
+        let mut state = BugList::default();
+        let alice = user(1);
+        let (details, labels) = split("It crashes --labels crash,todo");
+        let command = BugCommand::Report {
+            name: "crash".into(),
+            summary: "Crashes on add".into(),
+            details,
+            labels,
+        };
+        bug::handle_command(command, &mut state, &alice);
+        assert_eq!(vec!["crash", "todo"], state.items[0].labels);
+
+        let response = bug::handle_command(BugCommand::Print(1), &mut state, &alice);
+        assert!(response.contains("\nLabels: crash, todo\n"));
+        assert!(response.ends_with("\nIt crashes"));
+    }
+
+    /// Verifies that labels can be added to and removed from a bug, without
+    /// duplicates.
+    #[test]
+    fn labels() {
+        let mut state = BugList::default();
+        let alice = user(1);
+        report(&mut state, "crash", &alice);
+
+        let add = |number, label: &str| BugCommand::AddLabel {
+            number,
+            label: label.into(),
+        };
+        let remove = |number, label: &str| BugCommand::RemoveLabel {
+            number,
+            label: label.into(),
+        };
+
+        let response = bug::handle_command(add(1, "crash"), &mut state, &alice);
+        assert_eq!("Bug #1 is labeled crash", response);
+        let response = bug::handle_command(add(1, " todo list "), &mut state, &alice);
+        assert_eq!("Bug #1 is labeled crash, todo list", response);
+
+        let response = bug::handle_command(add(1, "crash"), &mut state, &alice);
+        assert_eq!(r#"Bug #1 already has the label "crash""#, response);
+        let response = bug::handle_command(add(1, " "), &mut state, &alice);
+        assert_eq!("Labels can't be empty", response);
+        assert_eq!(vec!["crash", "todo list"], state.items[0].labels);
+
+        let response = bug::handle_command(remove(1, "crash"), &mut state, &alice);
+        assert_eq!("Bug #1 is labeled todo list", response);
+        let response = bug::handle_command(remove(1, "crash"), &mut state, &alice);
+        assert_eq!(r#"Bug #1 doesn't have the label "crash""#, response);
+        let response = bug::handle_command(remove(1, "todo list"), &mut state, &alice);
+        assert_eq!("Bug #1 has no labels now", response);
+        assert!(state.items[0].labels.is_empty());
+
+        let response = bug::handle_command(add(2, "crash"), &mut state, &alice);
+        assert_eq!("I couldn't find bug #2", response);
+        let response = bug::handle_command(remove(2, "crash"), &mut state, &alice);
+        assert_eq!("I couldn't find bug #2", response);
+    }
+
+    /// Verifies that malformed bug reports get a message explaining the format.
+    #[test]
+    fn report_error_message() {
+        // Both `!bug report "only one arg"` and `!bug report "name" "summary"`
+        // fail with too few arguments.
+        let message = bug::report_error_message(&poise::TooFewArguments);
+        assert!(message.starts_with("A bug report needs a name, a summary, and details."));
+        assert!(message.contains("`!bug report \"todo crash\" \"Crashes on add\" Steps"));
+
+        let message = bug::report_error_message(&poise::TooManyArguments);
+        assert!(
+            message.starts_with("Couldn't read that bug report: Too many arguments were passed.")
+        );
+        assert!(message.contains("Quote the name and summary"));
+    }
+
+    /// Verifies that the bug list shows the most +1'd bugs first, then the
+    /// oldest.
+    #[test]
+    fn sorted_open_bugs() {
+        let mut state = BugList::default();
+        let (alice, bob, carol) = (user(1), user(2), user(3));
+        for name in ["a", "b", "c", "d", "e"] {
+            report(&mut state, name, &alice);
+        }
+        bug::handle_command(BugCommand::PlusOne(4), &mut state, &bob);
+        bug::handle_command(BugCommand::PlusOne(2), &mut state, &bob);
+        bug::handle_command(BugCommand::PlusOne(2), &mut state, &carol);
+        bug::handle_command(BugCommand::PlusOne(5), &mut state, &carol);
+        state.get_mut(3).unwrap().status = BugStatus::Closed;
+
+        let numbers = bug::sorted_open_bugs(&state)
+            .iter()
+            .map(|bug| bug.number)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![2, 4, 5, 1], numbers);
+
+        let response = bug::handle_command(BugCommand::PrintAll, &mut state, &alice);
+        assert_eq!(
+            "Open bugs:\n\
+            ```\n\
+            #2 b\tb summary (+2)\n\
+            #4 d\td summary (+1)\n\
+            #5 e\te summary (+1)\n\
+            #1 a\ta summary\n\
+            ```\n",
+            response,
+        );
+    }
+
+    /// Verifies that bugs are listed by priority first, and that setting the
+    /// priority is shown in the bug's details.
+    #[test]
+    fn priority() {
+        let mut state = BugList::default();
+        let (alice, bob) = (user(1), user(2));
+        for name in ["a", "b", "c", "d"] {
+            report(&mut state, name, &alice);
+        }
+        bug::handle_command(BugCommand::PlusOne(4), &mut state, &bob);
+
+        let set = |number, priority| BugCommand::SetPriority { number, priority };
+        let response = bug::handle_command(set(1, 5), &mut state, &alice);
+        assert_eq!("Set the priority of bug #1 to 5", response);
+        bug::handle_command(set(2, 10), &mut state, &alice);
+        bug::handle_command(set(3, 5), &mut state, &alice);
+        let response = bug::handle_command(set(5, 1), &mut state, &alice);
+        assert_eq!("I couldn't find bug #5", response);
+
+        let response = bug::handle_command(BugCommand::PrintAll, &mut state, &alice);
+        assert_eq!(
+            "Open bugs:\n\
+            ```\n\
+            #2 b\tb summary\n\
+            #1 a\ta summary\n\
+            #3 c\tc summary\n\
+            #4 d\td summary (+1)\n\
+            ```\n",
+            response,
+        );
+
+        let response = bug::handle_command(BugCommand::Print(2), &mut state, &alice);
+        assert!(response.contains("\nPriority: 10\n"));
+
+        // Bugs saved before priorities existed still load.
+        let mut document = bson::to_document(state.get(2).unwrap()).unwrap();
+        document.remove("priority");
+        let bug: BugItem = bson::from_document(document).unwrap();
+        assert_eq!(0, bug.priority);
+    }
+
+    /// Verifies that closing a bug hides it from the open list, and that the
+    /// reporter and +1-ers are each told about it once.
+    #[test]
+    fn close() {
+        let mut state = BugList::default();
+        let (alice, bob, carol) = (user(1), user(2), user(3));
+        report(&mut state, "crash", &alice);
+        report(&mut state, "typo", &alice);
+        bug::handle_command(BugCommand::PlusOne(1), &mut state, &bob);
+        bug::handle_command(BugCommand::PlusOne(1), &mut state, &carol);
+
+        let response = bug::handle_command(BugCommand::Close(1), &mut state, &bob);
+        assert_eq!("Closed bug #1", response);
+        assert_eq!(BugStatus::Closed, state.get(1).unwrap().status);
+
+        let response = bug::handle_command(BugCommand::Close(1), &mut state, &bob);
+        assert_eq!("Bug #1 is already closed", response);
+        let response = bug::handle_command(BugCommand::Close(3), &mut state, &bob);
+        assert_eq!("I couldn't find bug #3", response);
+
+        let response = bug::handle_command(BugCommand::PrintAll, &mut state, &alice);
+        assert!(!response.contains("#1 crash"));
+
+        assert_eq!(
+            vec![UserId(1), UserId(2), UserId(3)],
+            bug::close_recipients(state.get(1).unwrap()),
+        );
+        assert_eq!(
+            vec![UserId(1)],
+            bug::close_recipients(state.get(2).unwrap())
+        );
+
+        // Reporters aren't notified twice if they also show up as a +1, e.g. in
+        // lists saved before that was prevented.
+        let bug = state.get_mut(1).unwrap();
+        bug.plus_ones = vec![UserId(2), UserId(1), UserId(2)];
+        assert_eq!(vec![UserId(1), UserId(2)], bug::close_recipients(bug));
+    }
+
+    /// Verifies that closed bugs can be reopened, which puts them back in the open
+    /// list and tells their watchers.
+    #[test]
+    fn reopen() {
+        let mut state = BugList::default();
+        let (alice, bob) = (user(1), user(2));
+        report(&mut state, "crash", &alice);
+        report(&mut state, "typo", &alice);
+
+        let response = bug::handle_command(BugCommand::Reopen(1), &mut state, &bob);
+        assert_eq!("Bug #1 isn't closed", response);
+        let response = bug::handle_command(BugCommand::Reopen(3), &mut state, &bob);
+        assert_eq!("I couldn't find bug #3", response);
+
+        bug::handle_command(BugCommand::Close(1), &mut state, &bob);
+        let response = bug::handle_command(BugCommand::PrintAll, &mut state, &alice);
+        assert!(!response.contains("#1 crash"));
+
+        let before = state.get(1).unwrap().clone();
+        let response = bug::handle_command(BugCommand::Reopen(1), &mut state, &bob);
+        assert_eq!("Reopened bug #1", response);
+        assert_eq!(BugStatus::Open, state.get(1).unwrap().status);
+        assert_eq!(
+            Some("Bug #1 \"crash\" was updated: it was reopened".into()),
+            bug::change_message(&before, state.get(1).unwrap()),
+        );
+
+        let response = bug::handle_command(BugCommand::PrintAll, &mut state, &alice);
+        assert!(response.contains("#1 crash"));
+
+        // Reopening a duplicate means it's no longer considered one.
+        let dup = BugCommand::Duplicate {
+            number: 2,
+            original: 1,
+        };
+        bug::handle_command(dup, &mut state, &bob);
+        bug::handle_command(BugCommand::Reopen(2), &mut state, &bob);
+        assert_eq!(None, state.get(2).unwrap().duplicate_of);
+    }
+
+    /// Verifies that removing a bug never lets its number be given to another bug.
+    #[test]
+    fn remove() {
+        let mut state = BugList::default();
+        let alice = user(1);
+        report(&mut state, "crash", &alice);
+        report(&mut state, "typo", &alice);
+        report(&mut state, "slow", &alice);
+
+        let response = bug::handle_command(BugCommand::Remove(2), &mut state, &alice);
+        assert_eq!(r#"Removed bug #2 "typo""#, response);
+        assert!(state.get(2).is_none());
+        let response = bug::handle_command(BugCommand::Remove(2), &mut state, &alice);
+        assert_eq!("I couldn't find bug #2", response);
+
+        let response = report(&mut state, "leak", &alice);
+        assert_eq!(r#"Reported bug #4 "leak""#, response);
+
+        // Removing the newest bug doesn't free up its number either.
+        bug::handle_command(BugCommand::Remove(4), &mut state, &alice);
+        let response = report(&mut state, "hang", &alice);
+        assert_eq!(r#"Reported bug #5 "hang""#, response);
+
+        let numbers = state.items.iter().map(|bug| bug.number).collect::<Vec<_>>();
+        assert_eq!(vec![1, 3, 5], numbers);
+
+        // Lists saved without the last number carry on after their highest bug.
+        let mut document = bson::to_document(&state).unwrap();
+        document.remove("last_number");
+        let mut state: BugList = bson::from_document(document).unwrap();
+        let response = report(&mut state, "freeze", &alice);
+        assert_eq!(r#"Reported bug #6 "freeze""#, response);
+    }
+
+    /// Verifies that bugs can be closed as duplicates of other bugs.
+    #[test]
+    fn duplicate() {
+        let mut state = BugList::default();
+        let alice = user(1);
+        report(&mut state, "crash", &alice);
+        report(&mut state, "crash again", &alice);
+
+        let dup = |number, original| BugCommand::Duplicate { number, original };
+        assert_eq!(
+            "A bug can't be a duplicate of itself",
+            bug::handle_command(dup(2, 2), &mut state, &alice),
+        );
+        assert_eq!(
+            "I couldn't find bug #3",
+            bug::handle_command(dup(2, 3), &mut state, &alice),
+        );
+        assert_eq!(
+            "I couldn't find bug #3",
+            bug::handle_command(dup(3, 1), &mut state, &alice),
+        );
+        assert_eq!(BugStatus::Open, state.get(2).unwrap().status);
+
+        assert_eq!(
+            "Closed bug #2 as a duplicate of #1",
+            bug::handle_command(dup(2, 1), &mut state, &alice),
+        );
+        let bug = state.get(2).unwrap();
+        assert_eq!(BugStatus::Closed, bug.status);
+        assert_eq!(Some(1), bug.duplicate_of);
+
+        let response = bug::handle_command(BugCommand::Print(2), &mut state, &alice);
+        assert!(response.contains("\nStatus: Closed\nDuplicate of #1\n"));
+        let response = bug::handle_command(BugCommand::Print(1), &mut state, &alice);
+        assert!(!response.contains("Duplicate of"));
+
+        // Bugs saved before duplicates existed still load.
+        let mut document = bson::to_document(state.get(1).unwrap()).unwrap();
+        document.remove("duplicate_of");
+        let bug: BugItem = bson::from_document(document).unwrap();
+        assert_eq!(None, bug.duplicate_of);
+    }
+
+    /// Verifies that watching and unwatching a bug can be repeated safely.
+    #[test]
+    fn watch() {
+        let mut state = BugList::default();
+        let (alice, bob) = (user(1), user(2));
+        report(&mut state, "crash", &alice);
+
+        let response = bug::handle_command(BugCommand::Watch(1), &mut state, &bob);
+        assert_eq!(
+            "You're now watching bug #1, I'll DM you when it changes",
+            response
+        );
+        let response = bug::handle_command(BugCommand::Watch(1), &mut state, &bob);
+        assert_eq!("You're already watching bug #1", response);
+        bug::handle_command(BugCommand::Watch(1), &mut state, &alice);
+        assert_eq!(vec![UserId(2), UserId(1)], state.get(1).unwrap().watchers);
+
+        let response = bug::handle_command(BugCommand::Unwatch(1), &mut state, &bob);
+        assert_eq!("You're no longer watching bug #1", response);
+        let response = bug::handle_command(BugCommand::Unwatch(1), &mut state, &bob);
+        assert_eq!("You weren't watching bug #1", response);
+        assert_eq!(vec![UserId(1)], state.get(1).unwrap().watchers);
+
+        let response = bug::handle_command(BugCommand::Watch(2), &mut state, &bob);
+        assert_eq!("I couldn't find bug #2", response);
+
+        // Bugs saved before watchers existed still load.
+        let mut document = bson::to_document(state.get(1).unwrap()).unwrap();
+        document.remove("watchers");
+        let bug: BugItem = bson::from_document(document).unwrap();
+        assert!(bug.watchers.is_empty());
+    }
+
+    /// Verifies that watchers are told what changed, except about their own
+    /// changes.
+    #[test]
+    fn watch_notifications() {
+        let mut state = BugList::default();
+        let (alice, bob, carol) = (user(1), user(2), user(3));
+        report(&mut state, "crash", &alice);
+        report(&mut state, "typo", &alice);
+        bug::handle_command(BugCommand::Watch(1), &mut state, &alice);
+        bug::handle_command(BugCommand::Watch(1), &mut state, &bob);
+
+        let bug = state.get(1).unwrap();
+        assert_eq!(vec![UserId(2)], bug::watch_recipients(bug, UserId(1)));
+        assert_eq!(
+            vec![UserId(1), UserId(2)],
+            bug::watch_recipients(bug, UserId(3))
+        );
+
+        let before = state.get(1).unwrap().clone();
+        bug::handle_command(BugCommand::PlusOne(1), &mut state, &carol);
+        let assign = BugCommand::Assign {
+            number: 1,
+            assignee: Some(UserId(3)),
+        };
+        bug::handle_command(assign, &mut state, &carol);
+        assert_eq!(
+            Some(
+                "Bug #1 \"crash\" was updated: 2 people have hit it now, \
+                it was assigned to <@3>"
+                    .into()
+            ),
+            bug::change_message(&before, state.get(1).unwrap()),
+        );
+
+        let before = state.get(1).unwrap().clone();
+        let dup = BugCommand::Duplicate {
+            number: 1,
+            original: 2,
+        };
+        bug::handle_command(dup, &mut state, &carol);
+        assert_eq!(
+            Some("Bug #1 \"crash\" was updated: it was closed as a duplicate of #2".into()),
+            bug::change_message(&before, state.get(1).unwrap()),
+        );
+
+        // Watching doesn't count as a change.
+        let before = state.get(1).unwrap().clone();
+        bug::handle_command(BugCommand::Watch(1), &mut state, &carol);
+        assert_eq!(None, bug::change_message(&before, state.get(1).unwrap()));
+    }
+
+    /// Verifies that replaying the commands that changed a bug list reproduces the
+    /// list, including who made each change.
+    #[test]
+    fn replay_command_log() {
+        let guild_id = Some(GuildId(1));
+        let alice = user(1);
+        let bob = user(2);
+        let mut state = BugList::new(guild_id, alice.id);
+
+        let report = BugCommand::Report {
+            name: "crash".into(),
+            summary: "Crashes on add".into(),
+            details: "Steps to reproduce".into(),
+            labels: vec!["crash".into()],
+        };
+        let commands = [
+            (&alice, report),
+            (&bob, BugCommand::PlusOne(1)),
+            (&bob, BugCommand::Watch(1)),
+            (&alice, BugCommand::PrintAll),
+            (
+                &alice,
+                BugCommand::Assign {
+                    number: 1,
+                    assignee: Some(bob.id),
+                },
+            ),
+            (&bob, BugCommand::Close(1)),
+        ];
+
+        // Log the commands that change the list the same way `run_command` does.
+        let mut log = Vec::new();
+        for (author, command) in commands {
+            let before = state.clone();
+            bug::handle_command(command.clone(), &mut state, author);
+            if state != before {
+                let entry = BugLogEntry {
+                    guild_id,
+                    user_id: author.id,
+                    command,
+                };
+                log.push(bson::to_document(&entry).unwrap());
+            }
+        }
+        assert_eq!(5, log.len());
+
+        let replayed = bug::replay(
+            guild_id,
+            alice.id,
+            log.into_iter()
+                .map(|doc| bson::from_document::<BugLogEntry>(doc).unwrap()),
+        );
+        assert_eq!(state, replayed);
+        assert_eq!(BugStatus::Closed, replayed.items[0].status);
+        assert_eq!(vec![bob.id], replayed.items[0].plus_ones);
+    }
+
+    /// Verifies that the `!bug` usage covers every subcommand, since it's what
+    /// people see when a `!bug` command isn't recognized.
+    #[test]
+    fn usage() {
+        let mut commands = vec![bug::bug()];
+        poise::set_qualified_names(&mut commands);
+        let usage = crate::command_help(&commands[0]);
+
+        assert!(
+            usage.starts_with("`!bug [number]`\nShow the open bugs, or a single bug by number.\n")
+        );
+        for subcommand in &commands[0].subcommands {
+            assert!(usage.contains(&format!("`!bug {}", subcommand.name)));
+        }
+        assert!(usage.contains("\n`!bug report <name> <summary> <details>` - Report a new bug.\n"));
+        assert!(usage.contains("\n`!bug help` - Show how to use the `!bug` commands.\n"));
+
+        let message = bug::unrecognized_message(&commands[0]);
+        assert_eq!(
+            format!("I didn't recognize that `!bug` command.\n\n{usage}"),
+            message
+        );
+    }
+
+    #[test]
+    fn read_only_commands() {
+        assert!(BugCommand::PrintAll.is_read_only());
+        assert!(BugCommand::Print(1).is_read_only());
+        assert!(BugCommand::Search("crash".into()).is_read_only());
+        assert!(BugCommand::Mine.is_read_only());
+
+        assert!(!BugCommand::PlusOne(1).is_read_only());
+        assert!(!BugCommand::Close(1).is_read_only());
+        assert!(!BugCommand::Remove(1).is_read_only());
+        assert!(!BugCommand::Watch(1).is_read_only());
+    }
+
+    /// Verifies that a bug reported while someone else is saving the list gets
+    /// its own number instead of overwriting the other report.
+    #[test]
+    fn concurrent_reports() {
+        let store = FakeBugStore::default();
+        let author = user(1);
+        let command = BugCommand::Report {
+            name: "Mine".into(),
+            summary: "Mine summary".into(),
+            details: "Mine details".into(),
+            labels: Vec::new(),
+        };
+
+        store.report_before_save.set(true);
+        let outcome = block_on(bug::apply_command(
+            &store,
+            Some(GuildId(1)),
+            &author,
+            &command,
+        ))
+        .unwrap();
+        assert_eq!(r#"Reported bug #2 "Mine""#, outcome.response);
+        assert!(outcome.changed);
+        assert_eq!(2, store.saves.get());
+
+        let list = store.list.borrow().clone().unwrap();
+        assert_eq!(2, list.version);
+        let names = list
+            .items
+            .iter()
+            .map(|bug| (bug.number, bug.name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(1, "Other"), (2, "Mine")], names);
+
+        // Commands that don't change the list don't save it.
+        let outcome = block_on(bug::apply_command(
+            &store,
+            Some(GuildId(1)),
+            &author,
+            &BugCommand::Mine,
+        ))
+        .unwrap();
+        assert!(!outcome.changed);
+        assert_eq!(2, store.saves.get());
+    }
+}
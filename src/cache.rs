@@ -0,0 +1,113 @@
+//! A simple in-memory cache for data loaded from the database.
+//!
+//! Entries expire a fixed time after they were stored, so changes made outside of
+//! the cache are eventually picked up. Expired entries are dropped whenever a new
+//! entry is stored, so the cache only holds recently used data.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A map whose entries expire `ttl` after they were last stored.
+pub struct TtlCache<K, V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<K, (V, Instant)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Default::default(),
+        }
+    }
+
+    /// Gets a copy of the value for `key`, or `None` if there isn't one or it has
+    /// expired.
+    pub fn get(&self, key: &K, now: Instant) -> Option<V> {
+        let entries = self.entries.lock().unwrap();
+        let (value, stored_at) = entries.get(key)?;
+        self.is_fresh(*stored_at, now).then(|| value.clone())
+    }
+
+    /// Stores the value for `key`, replacing any previous value and restarting its
+    /// expiry time.
+    pub fn insert(&self, key: K, value: V, now: Instant) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (_, stored_at)| self.is_fresh(*stored_at, now));
+        entries.insert(key, (value, now));
+    }
+
+    /// Changes the value for `key` in place, e.g. to match a change made directly
+    /// in the database. Does nothing if there's no value for `key`, and doesn't
+    /// restart its expiry time.
+    pub fn update(&self, key: &K, change: impl FnOnce(&mut V)) {
+        if let Some((value, _)) = self.entries.lock().unwrap().get_mut(key) {
+            change(value);
+        }
+    }
+
+    /// Drops the value for `key`, e.g. because it was changed in the database.
+    pub fn remove(&self, key: &K) {
+        self.entries.lock().unwrap().remove(key);
+    }
+
+    fn is_fresh(&self, stored_at: Instant, now: Instant) -> bool {
+        now.saturating_duration_since(stored_at) < self.ttl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::TtlCache;
+    use pretty_assertions::assert_eq;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn ttl_cache() {
+        let ttl = Duration::from_secs(60);
+        let cache = TtlCache::new(ttl);
+        let start = Instant::now();
+        assert_eq!(None, cache.get(&1, start));
+
+        cache.insert(1, "foo", start);
+        assert_eq!(Some("foo"), cache.get(&1, start));
+        assert_eq!(
+            Some("foo"),
+            cache.get(&1, start + ttl - Duration::from_secs(1))
+        );
+        assert_eq!(None, cache.get(&2, start));
+
+        // Entries expire after the TTL, unless they're stored again.
+        assert_eq!(None, cache.get(&1, start + ttl));
+        cache.insert(1, "bar", start + ttl);
+        assert_eq!(Some("bar"), cache.get(&1, start + ttl));
+
+        // Updating a value keeps its expiry time.
+        cache.update(&1, |value| *value = "baz");
+        assert_eq!(Some("baz"), cache.get(&1, start + ttl));
+        assert_eq!(None, cache.get(&1, start + ttl * 2));
+        cache.update(&2, |value| *value = "baz");
+        assert_eq!(None, cache.get(&2, start + ttl));
+
+        cache.remove(&1);
+        assert_eq!(None, cache.get(&1, start + ttl));
+    }
+
+    /// Verifies that expired entries are dropped when new ones are stored.
+    #[test]
+    fn drop_expired() {
+        let ttl = Duration::from_secs(60);
+        let cache = TtlCache::new(ttl);
+        let start = Instant::now();
+        cache.insert(1, "foo", start);
+        cache.insert(2, "bar", start + ttl / 2);
+        cache.insert(3, "baz", start + ttl);
+
+        let entries = cache.entries.lock().unwrap();
+        let mut keys = entries.keys().copied().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(vec![2, 3], keys);
+    }
+}
@@ -0,0 +1,269 @@
+//! `!config` - Per-guild bot configuration.
+//!
+//! # Usage
+//!
+//! * `!config disable <COMMAND>` - Disable a command in this server.
+//! * `!config enable <COMMAND>` - Re-enable a disabled command.
+//!
+//! Disabling a command also disables all of its subcommands, e.g. disabling
+//! `todo` disables `todo add`, `todo show`, etc. A single subcommand can be
+//! disabled by quoting its full name, e.g. `!config disable "todo bulk-edit"`.
+//! Commands can be given by any of their aliases, e.g. `!config disable "todo rm"`
+//! disables `todo remove`. The `config` command itself can't be disabled.
+//!
+//! All `!config` commands require the Manage Server permission.
+
+use crate::retry::{with_retry, BASE_DELAY};
+use crate::{serenity, Context, Data, Error};
+use anyhow::{Context as _, Result};
+use mongodb::bson::{doc, Document};
+use mongodb::options::UpdateOptions;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// The name of the collection where guild settings are stored.
+const COLLECTION_NAME: &str = "guild_settings";
+
+/// The name of the `!config` command, which can never be disabled.
+const CONFIG_COMMAND: &str = "config";
+
+/// How long a guild's settings are kept in memory after they were loaded, so that
+/// changes saved by another instance of the bot are picked up eventually.
+pub const SETTINGS_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Enable or disable commands in this server.
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("enable", "disable")
+)]
+pub async fn config(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Use `!config enable <command>` or `!config disable <command>`")
+        .await?;
+    Ok(())
+}
+
+/// Re-enable a disabled command in this server.
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn enable(ctx: Context<'_>, command: String) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .context("`!config` used outside of a guild")?;
+    // Commands that no longer exist can still be enabled by the name they were
+    // disabled under.
+    let commands = &ctx.framework().options().commands;
+    let command = canonical_name(commands, &command).unwrap_or_else(|| command.to_lowercase());
+
+    ctx.data()
+        .db
+        .collection::<GuildSettings>(COLLECTION_NAME)
+        .update_one(
+            settings_query(guild_id),
+            doc! { "$pull": { "disabled_commands": &command } },
+            None,
+        )
+        .await
+        .with_context(|| format!("Failed to update settings for guild {guild_id}"))?;
+
+    invalidate_settings(ctx.data(), guild_id);
+    info!("Enabled command {command:?} in guild {guild_id}");
+
+    ctx.say(format!("`{command}` is now enabled here")).await?;
+    Ok(())
+}
+
+/// Disable a command in this server.
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn disable(ctx: Context<'_>, command: String) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .context("`!config` used outside of a guild")?;
+
+    // Only allow disabling commands that actually exist, to catch typos. Aliases
+    // are stored under the command's full name, since that's what's checked.
+    let Some(command) = canonical_name(&ctx.framework().options().commands, &command) else {
+        ctx.say(format!("There's no command called `{command}`"))
+            .await?;
+        return Ok(());
+    };
+
+    if command.split(' ').next() == Some(CONFIG_COMMAND) {
+        ctx.say("`config` can't be disabled").await?;
+        return Ok(());
+    }
+
+    ctx.data()
+        .db
+        .collection::<GuildSettings>(COLLECTION_NAME)
+        .update_one(
+            settings_query(guild_id),
+            doc! { "$addToSet": { "disabled_commands": &command } },
+            UpdateOptions::builder().upsert(true).build(),
+        )
+        .await
+        .with_context(|| format!("Failed to update settings for guild {guild_id}"))?;
+
+    invalidate_settings(ctx.data(), guild_id);
+    info!("Disabled command {command:?} in guild {guild_id}");
+
+    ctx.say(format!("`{command}` is now disabled here")).await?;
+    Ok(())
+}
+
+/// Command check that stops commands that have been disabled in the current guild.
+///
+/// Used as the framework's `command_check`. Commands sent in DMs are always
+/// allowed.
+///
+/// Every command runs this check, so each guild's disabled commands are cached in
+/// [`Data::guild_disabled_commands`] for [`SETTINGS_TTL`] after they're loaded.
+pub async fn check_enabled(ctx: Context<'_>) -> Result<bool, Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(true);
+    };
+
+    let cached = ctx
+        .data()
+        .guild_disabled_commands
+        .get(&guild_id, Instant::now());
+    let disabled_commands = match cached {
+        Some(disabled_commands) => disabled_commands,
+        None => {
+            let collection = ctx.data().db.collection::<GuildSettings>(COLLECTION_NAME);
+            let query = settings_query(guild_id);
+            let disabled_commands = with_retry(ctx.data().db_retries, BASE_DELAY, || {
+                collection.find_one(query.clone(), None)
+            })
+            .await
+            .with_context(|| format!("Failed to get settings for guild {guild_id}"))?
+            .map(|settings| settings.disabled_commands)
+            .unwrap_or_default();
+
+            ctx.data().guild_disabled_commands.insert(
+                guild_id,
+                disabled_commands.clone(),
+                Instant::now(),
+            );
+            disabled_commands
+        }
+    };
+
+    let command_name = &ctx.command().qualified_name;
+    if is_command_disabled(&disabled_commands, command_name) {
+        ctx.say("This command is disabled here").await?;
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// Drops a guild's cached settings after they were changed, so that they're
+/// loaded again by the next command.
+fn invalidate_settings(data: &Data, guild_id: serenity::GuildId) {
+    data.guild_disabled_commands.remove(&guild_id);
+}
+
+/// Gets the full name of the command called `name`, e.g. `todo remove` for
+/// `TODO rm`, or `None` if there's no such command.
+fn canonical_name(commands: &[poise::Command<Data, Error>], name: &str) -> Option<String> {
+    crate::find_command(commands, name).map(|command| command.qualified_name.clone())
+}
+
+/// Checks if the command with the given qualified name (e.g. `todo add`) is
+/// disabled, either directly or because its parent command is disabled.
+fn is_command_disabled(disabled_commands: &[String], qualified_name: &str) -> bool {
+    let root = qualified_name.split(' ').next().unwrap_or_default();
+    if root == CONFIG_COMMAND {
+        return false;
+    }
+
+    disabled_commands.iter().any(|disabled| {
+        qualified_name == disabled
+            || matches!(
+                qualified_name.strip_prefix(disabled.as_str()),
+                Some(rest) if rest.starts_with(' ')
+            )
+    })
+}
+
+/// Builds the query for finding a guild's settings document.
+fn settings_query(guild_id: serenity::GuildId) -> Document {
+    doc! { "guild_id": guild_id.to_string() }
+}
+
+/// The bot settings for a single guild.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GuildSettings {
+    guild_id: String,
+
+    /// The names of commands that have been disabled in the guild.
+    #[serde(default)]
+    disabled_commands: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+    use pretty_assertions::assert_eq;
+
+    /// Verifies that commands are disabled and enabled under their full name,
+    /// whichever alias they're given by.
+    #[test]
+    fn canonical_name() {
+        let mut commands = vec![crate::todo::todo(), crate::bug::bug()];
+        poise::set_qualified_names(&mut commands);
+
+        assert_eq!(
+            Some("todo".into()),
+            config::canonical_name(&commands, "TODO")
+        );
+        assert_eq!(
+            Some("todo undone".into()),
+            config::canonical_name(&commands, "todo reopen")
+        );
+        assert_eq!(
+            Some("bug remove".into()),
+            config::canonical_name(&commands, "!bug RM")
+        );
+        assert_eq!(None, config::canonical_name(&commands, "todo nope"));
+        assert_eq!(None, config::canonical_name(&commands, "nope"));
+    }
+
+    #[test]
+    fn disabled_commands() {
+        let disabled = vec!["todo".to_string(), "age".to_string()];
+
+        // Disabled commands and their subcommands are blocked.
+        assert!(config::is_command_disabled(&disabled, "todo"));
+        assert!(config::is_command_disabled(&disabled, "todo add"));
+        assert!(config::is_command_disabled(&disabled, "age"));
+
+        // Other commands, including ones that share a prefix, are allowed.
+        assert!(!config::is_command_disabled(&disabled, "ping"));
+        assert!(!config::is_command_disabled(&disabled, "ages"));
+        assert!(!config::is_command_disabled(&[], "todo"));
+
+        // Disabling a subcommand only blocks that subcommand.
+        let disabled = vec!["todo bulk-edit".to_string()];
+        assert!(config::is_command_disabled(&disabled, "todo bulk-edit"));
+        assert!(!config::is_command_disabled(&disabled, "todo add"));
+
+        // `config` can never be disabled, so admins can always undo a disable.
+        let disabled = vec!["config".to_string()];
+        assert!(!config::is_command_disabled(&disabled, "config"));
+        assert!(!config::is_command_disabled(&disabled, "config enable"));
+    }
+}
@@ -0,0 +1,190 @@
+//! Parsing for human-written durations like `10m`, `2h30m`, or `1d`.
+//!
+//! A duration is one or more `<NUMBER><UNIT>` pairs, optionally separated by
+//! whitespace. The supported units are:
+//!
+//! * `s` - seconds
+//! * `m` - minutes
+//! * `h` - hours
+//! * `d` - days
+//! * `w` - weeks
+//!
+//! Units can be combined in any order, e.g. `1d 12h` or `30m2h`, and the parts
+//! are summed together.
+//!
+//! Durations can be formatted back into the same form with [`format`].
+
+use anyhow::{anyhow, bail, Result};
+use std::time::Duration;
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// Parses a human-written duration string, e.g. `2h30m`.
+pub fn parse(input: &str) -> Result<Duration> {
+    let input = input.trim();
+    if input.is_empty() {
+        bail!("Expected a duration like `10m`, `2h30m`, or `1d`");
+    }
+
+    let mut total: u64 = 0;
+    let mut chars = input.chars().peekable();
+    while chars.peek().is_some() {
+        // Skip whitespace between parts, e.g. `2h 30m`.
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        let mut number = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+            number.push(c);
+        }
+
+        let unit = chars.next();
+        if number.is_empty() {
+            match unit {
+                Some(unit) => bail!("Expected a number before {unit:?} in {input:?}"),
+                None => bail!("Unexpected end of duration {input:?}"),
+            }
+        }
+
+        let multiplier = match unit {
+            Some('s') => 1,
+            Some('m') => MINUTE,
+            Some('h') => HOUR,
+            Some('d') => DAY,
+            Some('w') => WEEK,
+            Some(unit) => {
+                bail!("Unknown unit {unit:?} in {input:?}, expected one of s, m, h, d, w")
+            }
+            None => {
+                bail!("Missing unit after {number:?} in {input:?}, expected one of s, m, h, d, w")
+            }
+        };
+
+        let overflow = || anyhow!("Duration {input:?} is too large");
+        let value: u64 = number.parse().map_err(|_| overflow())?;
+        total = value
+            .checked_mul(multiplier)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(overflow)?;
+    }
+
+    Ok(Duration::from_secs(total))
+}
+
+/// Formats a duration in the form accepted by [`parse`], e.g. `1d 2h 30m`.
+///
+/// Zero-valued units are left out, and anything shorter than a second is
+/// dropped. Weeks are displayed as days.
+pub fn format(duration: Duration) -> String {
+    let mut secs = duration.as_secs();
+    let mut parts = Vec::new();
+    for (unit, unit_secs) in [('d', DAY), ('h', HOUR), ('m', MINUTE), ('s', 1)] {
+        let value = secs / unit_secs;
+        secs %= unit_secs;
+        if value > 0 {
+            parts.push(format!("{value}{unit}"));
+        }
+    }
+
+    if parts.is_empty() {
+        "0m".into()
+    } else {
+        parts.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::duration::{self, DAY, HOUR, MINUTE, WEEK};
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn single_units() {
+        assert_eq!(secs(45), duration::parse("45s").unwrap());
+        assert_eq!(secs(10 * MINUTE), duration::parse("10m").unwrap());
+        assert_eq!(secs(2 * HOUR), duration::parse("2h").unwrap());
+        assert_eq!(secs(DAY), duration::parse("1d").unwrap());
+        assert_eq!(secs(3 * WEEK), duration::parse("3w").unwrap());
+    }
+
+    #[test]
+    fn compound_units() {
+        assert_eq!(
+            secs(2 * HOUR + 30 * MINUTE),
+            duration::parse("2h30m").unwrap()
+        );
+        assert_eq!(
+            secs(WEEK + DAY + HOUR + MINUTE + 1),
+            duration::parse("1w1d1h1m1s").unwrap()
+        );
+
+        // Units don't have to be in descending order, and repeated units are summed.
+        assert_eq!(
+            secs(2 * HOUR + 30 * MINUTE),
+            duration::parse("30m2h").unwrap()
+        );
+        assert_eq!(secs(20 * MINUTE), duration::parse("10m10m").unwrap());
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(secs(10 * MINUTE), duration::parse("  10m  ").unwrap());
+        assert_eq!(secs(DAY + 12 * HOUR), duration::parse("1d 12h").unwrap());
+        assert_eq!(secs(DAY + 12 * HOUR), duration::parse("1d\t 12h").unwrap());
+    }
+
+    #[test]
+    fn zero() {
+        assert_eq!(secs(0), duration::parse("0m").unwrap());
+    }
+
+    #[test]
+    fn invalid() {
+        // Empty input.
+        assert!(duration::parse("").is_err());
+        assert!(duration::parse("   ").is_err());
+
+        // Missing unit.
+        assert!(duration::parse("10").is_err());
+        assert!(duration::parse("1h30").is_err());
+
+        // Missing number.
+        assert!(duration::parse("m").is_err());
+        assert!(duration::parse("1hm").is_err());
+
+        // Unknown units and stray characters.
+        assert!(duration::parse("10y").is_err());
+        assert!(duration::parse("10 m").is_err());
+        assert!(duration::parse("-10m").is_err());
+        assert!(duration::parse("1.5h").is_err());
+
+        // Overflow.
+        assert!(duration::parse("99999999999999999999s").is_err());
+        assert!(duration::parse("9999999999999999w").is_err());
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!("0m", duration::format(secs(0)));
+        assert_eq!("0m", duration::format(Duration::from_millis(999)));
+        assert_eq!("45s", duration::format(secs(45)));
+        assert_eq!("2h 30m", duration::format(secs(2 * HOUR + 30 * MINUTE)));
+        assert_eq!("1d 1s", duration::format(secs(DAY + 1)));
+        assert_eq!(
+            "8d 1h 1m",
+            duration::format(secs(WEEK + DAY + HOUR + MINUTE))
+        );
+
+        // Formatted durations can be parsed back into the same duration.
+        for text in ["45s", "2h 30m", "1d 1s", "8d 1h 1m"] {
+            assert_eq!(text, duration::format(duration::parse(text).unwrap()));
+        }
+    }
+}
@@ -1,22 +1,155 @@
 use anyhow::Error;
 use mongodb::Database;
 use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::Mentionable;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::error;
 
+pub mod bug;
+pub mod cache;
+pub mod config;
+pub mod duration;
+pub mod prefix;
+pub mod retry;
 pub mod todo;
 
 type Context<'a> = poise::Context<'a, Data, Error>;
 
 pub struct Data {
     pub db: Database,
+
+    /// How many times to retry database operations that fail.
+    pub db_retries: u32,
+
+    /// When the bot started, used by `uptime`.
+    pub start_time: Instant,
+
+    /// The message sent to a user the first time they use `!todo`.
+    pub todo_welcome_message: String,
+
+    /// The maximum number of items a user can have in their TODO list.
+    pub todo_max_items: usize,
+
+    /// Whether `!todo show` displays the list as an embed instead of as text.
+    pub todo_use_embeds: bool,
+
+    /// Users' recently used TODO lists, shared with the reminder task.
+    pub todo_cache: Arc<todo::ListCache>,
+
+    /// The last item each user added with `!todo add`, used to detect accidental
+    /// repeated adds.
+    pub recent_todo_adds: Mutex<HashMap<serenity::UserId, todo::RecentAdd>>,
+
+    /// The minimum time between a user's `!todo` commands that change their list.
+    pub todo_cooldown: Duration,
+
+    /// When each user last ran a `!todo` command that changed their list, used to
+    /// enforce `todo_cooldown`.
+    pub todo_cooldowns: Mutex<HashMap<serenity::UserId, Instant>>,
+
+    /// The prefixes of the guilds that have been looked up so far, or `None` for
+    /// guilds that use the default prefix.
+    pub guild_prefixes: Mutex<HashMap<serenity::GuildId, Option<String>>>,
+
+    /// The commands that have been disabled in each guild that was recently looked
+    /// up.
+    pub guild_disabled_commands: cache::TtlCache<serenity::GuildId, Vec<String>>,
 }
 
-/// Basic ping command, useful for testing if the bot is running.
+/// Handles errors from commands and the framework, used as the framework's
+/// `on_error`.
+///
+/// Errors returned by commands are logged in full, and the user gets a generic
+/// message rather than the details of what went wrong. Everything else, e.g.
+/// invalid arguments, is handled by poise's default handler.
+pub async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
+    match error {
+        poise::FrameworkError::Command { error, ctx } => {
+            error!(
+                "Error running command `{}` for user {}: {:?}",
+                ctx.command().qualified_name,
+                ctx.author().id,
+                error,
+            );
+
+            let result = ctx
+                .send(|m| {
+                    m.content("Sorry, something went wrong running that command. Please try again.")
+                        .ephemeral(true)
+                })
+                .await;
+            if let Err(e) = result {
+                error!("Error sending error message: {:?}", e);
+            }
+        }
+
+        error => {
+            if let Err(e) = poise::builtins::on_error(error).await {
+                error!("Error handling framework error: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Checks that the bot is running and reports its latency.
 #[poise::command(slash_command, prefix_command)]
 pub async fn ping(ctx: Context<'_>) -> Result<(), Error> {
-    ctx.say("Pong!").await?;
+    // Time from receiving the command until the reply has been sent and edited,
+    // so that the REST latency covers a full round trip of each.
+    let start = Instant::now();
+    let reply = ctx.say("Pong!").await?;
+    reply.edit(ctx, |m| m.content("Pong! Measuring...")).await?;
+    let rest_latency = start.elapsed();
+
+    // The gateway latency is only known once the shard has received a heartbeat
+    // acknowledgement.
+    let shard_id = serenity::ShardId(ctx.serenity_context().shard_id);
+    let gateway_latency = ctx
+        .framework()
+        .shard_manager()
+        .lock()
+        .await
+        .runners
+        .lock()
+        .await
+        .get(&shard_id)
+        .and_then(|runner| runner.latency);
+
+    let message = ping_message(rest_latency, gateway_latency);
+    reply.edit(ctx, |m| m.content(message)).await?;
+    Ok(())
+}
+
+/// Builds the response to `ping`, e.g. "Pong! REST: 142ms, Gateway: 48ms".
+fn ping_message(rest_latency: Duration, gateway_latency: Option<Duration>) -> String {
+    let gateway_latency = match gateway_latency {
+        Some(latency) => format!("{}ms", latency.as_millis()),
+        None => "N/A".into(),
+    };
+    format!(
+        "Pong! REST: {}ms, Gateway: {gateway_latency}",
+        rest_latency.as_millis()
+    )
+}
+
+/// Shows how long the bot has been running.
+#[poise::command(slash_command, prefix_command)]
+pub async fn uptime(ctx: Context<'_>) -> Result<(), Error> {
+    let message = uptime_message(ctx.data().start_time.elapsed());
+    ctx.say(message).await?;
     Ok(())
 }
 
+/// Builds the response to `uptime`, e.g. "Up for 3d 4h 12m".
+fn uptime_message(uptime: Duration) -> String {
+    // Seconds are just noise when the bot has been up for days.
+    let minutes = Duration::from_secs(uptime.as_secs() / 60 * 60);
+    format!("Up for {}", duration::format(minutes))
+}
+
 /// Displays your or another user's account creation date
 #[poise::command(slash_command, prefix_command)]
 pub async fn age(
@@ -28,3 +161,387 @@ pub async fn age(
     ctx.say(response).await?;
     Ok(())
 }
+
+/// Displays account details for you or another user, plus server details when used in a server
+#[poise::command(slash_command, prefix_command, aliases("whois"))]
+pub async fn userinfo(
+    ctx: Context<'_>,
+    #[description = "Selected user"] user: Option<serenity::User>,
+) -> Result<(), Error> {
+    let user = user.as_ref().unwrap_or_else(|| ctx.author());
+
+    // Server-specific details come from the member object, which isn't available in
+    // DMs or if the user isn't in the server. In that case just show the account
+    // details.
+    let member = match ctx.guild_id() {
+        Some(guild_id) => guild_id.member(ctx, user.id).await.ok(),
+        None => None,
+    };
+
+    let embed = userinfo_embed(user, member.as_ref());
+    ctx.send(|m| {
+        m.embed(|e| {
+            *e = embed;
+            e
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// The maximum length of an embed field value.
+const MAX_EMBED_FIELD_LENGTH: usize = 1024;
+
+/// Builds the embed for `userinfo`.
+fn userinfo_embed(
+    user: &serenity::User,
+    member: Option<&serenity::Member>,
+) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::default();
+    embed
+        .title(user.tag())
+        .thumbnail(user.face())
+        .field("ID", user.id, true)
+        .field("Bot", if user.bot { "Yes" } else { "No" }, true)
+        .field("Created", discord_date(user.created_at()), true);
+
+    if let Some(member) = member {
+        if let Some(joined_at) = member.joined_at {
+            embed.field("Joined", discord_date(joined_at), true);
+        }
+
+        // Leave out any roles that don't fit rather than failing to send the embed.
+        let mut roles = String::new();
+        for role in &member.roles {
+            let mention = role.mention().to_string();
+            if roles.len() + mention.len() + 1 > MAX_EMBED_FIELD_LENGTH {
+                break;
+            }
+            if !roles.is_empty() {
+                roles.push(' ');
+            }
+            roles.push_str(&mention);
+        }
+        if roles.is_empty() {
+            roles.push_str("None");
+        }
+        embed.field("Roles", roles, false);
+    }
+
+    embed
+}
+
+/// Lists the available commands, or shows the full usage of a single command
+#[poise::command(slash_command, prefix_command)]
+pub async fn help(
+    ctx: Context<'_>,
+    #[description = "A command to show the usage of, e.g. \"todo add\""]
+    #[rest]
+    command: Option<String>,
+) -> Result<(), Error> {
+    let commands = &ctx.framework().options().commands;
+    let message = match command {
+        Some(name) => match find_command(commands, &name) {
+            Some(command) => command_help(command),
+            None => format!("There's no command called `{}`", name.trim()),
+        },
+        None => help_message(commands),
+    };
+
+    for chunk in split_message(&message) {
+        ctx.say(chunk).await?;
+    }
+    Ok(())
+}
+
+/// The maximum length of a Discord message.
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// Builds the response to `help` with no arguments.
+///
+/// Commands with subcommands, e.g. `todo`, are listed in their own group along
+/// with their subcommands. Everything else is listed under "General".
+fn help_message(commands: &[poise::Command<Data, Error>]) -> String {
+    let commands = commands
+        .iter()
+        .filter(|command| !command.hide_in_help)
+        .collect::<Vec<_>>();
+
+    let mut message = "**General**\n".to_string();
+    for command in commands.iter().filter(|c| c.subcommands.is_empty()) {
+        writeln!(message, "{}", command_summary(command)).unwrap();
+    }
+
+    for command in commands.iter().filter(|c| !c.subcommands.is_empty()) {
+        let mut group = command.name.clone();
+        if let Some(first) = group.get_mut(..1) {
+            first.make_ascii_uppercase();
+        }
+        writeln!(message, "\n**{group}**").unwrap();
+        writeln!(message, "{}", command_summary(command)).unwrap();
+        for subcommand in command.subcommands.iter().filter(|c| !c.hide_in_help) {
+            writeln!(message, "{}", command_summary(subcommand)).unwrap();
+        }
+    }
+
+    message.push_str("\nUse `!help <command>` to see the full usage of a command.");
+    message
+}
+
+/// Builds the response to `help <command>`, showing the command's arguments and
+/// subcommands.
+fn command_help(command: &poise::Command<Data, Error>) -> String {
+    let mut message = format!("`{}`\n", command_usage(command));
+    if let Some(description) = &command.description {
+        writeln!(message, "{description}").unwrap();
+    }
+    if let Some(help_text) = command.help_text {
+        writeln!(message, "\n{}", help_text()).unwrap();
+    }
+
+    let described = command
+        .parameters
+        .iter()
+        .filter_map(|param| Some((&param.name, param.description.as_ref()?)))
+        .collect::<Vec<_>>();
+    if !described.is_empty() {
+        message.push_str("\nArguments:\n");
+        for (name, description) in described {
+            writeln!(message, "  `{name}` - {description}").unwrap();
+        }
+    }
+
+    if !command.aliases.is_empty() {
+        writeln!(message, "\nAliases: {}", command.aliases.join(", ")).unwrap();
+    }
+
+    let subcommands = command
+        .subcommands
+        .iter()
+        .filter(|c| !c.hide_in_help)
+        .collect::<Vec<_>>();
+    if !subcommands.is_empty() {
+        message.push_str("\nSubcommands:\n");
+        for subcommand in subcommands {
+            writeln!(message, "{}", command_summary(subcommand)).unwrap();
+        }
+    }
+
+    message
+}
+
+/// Formats a command's usage and description on one line, e.g.
+/// "`!todo done <key>` - Mark an item as done."
+fn command_summary(command: &poise::Command<Data, Error>) -> String {
+    let usage = command_usage(command);
+    match &command.description {
+        Some(description) => format!("`{usage}` - {description}"),
+        None => format!("`{usage}`"),
+    }
+}
+
+/// Formats a command's usage, e.g. `!todo add <key> [priority] [category]`.
+fn command_usage(command: &poise::Command<Data, Error>) -> String {
+    let mut usage = format!("!{}", command.qualified_name);
+    for param in &command.parameters {
+        if param.required {
+            write!(usage, " <{}>", param.name).unwrap();
+        } else {
+            write!(usage, " [{}]", param.name).unwrap();
+        }
+    }
+    usage
+}
+
+/// Finds a command by its full name, e.g. `todo add`. Aliases are accepted for
+/// each part of the name, and a leading `!` is ignored.
+fn find_command<'a>(
+    commands: &'a [poise::Command<Data, Error>],
+    name: &str,
+) -> Option<&'a poise::Command<Data, Error>> {
+    let find = |commands: &'a [poise::Command<Data, Error>], name: &str| {
+        commands.iter().find(|command| {
+            command.name.eq_ignore_ascii_case(name)
+                || command
+                    .aliases
+                    .iter()
+                    .any(|alias| alias.eq_ignore_ascii_case(name))
+        })
+    };
+
+    let mut words = name.trim().trim_start_matches('!').split_whitespace();
+    let mut command = find(commands, words.next()?)?;
+    for word in words {
+        command = find(&command.subcommands, word)?;
+    }
+    Some(command)
+}
+
+/// Splits a message into chunks that each fit in a single Discord message,
+/// breaking between lines.
+fn split_message(message: &str) -> Vec<String> {
+    let mut chunks = vec![String::new()];
+    for line in message.lines() {
+        let mut chunk = chunks.last_mut().unwrap();
+        if !chunk.is_empty() && chunk.len() + line.len() + 1 > MAX_MESSAGE_LENGTH {
+            chunks.push(String::new());
+            chunk = chunks.last_mut().unwrap();
+        }
+        chunk.push_str(line);
+        chunk.push('\n');
+    }
+    chunks
+}
+
+/// Formats a timestamp as a Discord date, which is displayed in each viewer's
+/// local format.
+fn discord_date(timestamp: serenity::Timestamp) -> String {
+    format!("<t:{}:D>", timestamp.unix_timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use poise::serenity_prelude::json::json;
+    use poise::serenity_prelude::{Member, User, UserId};
+    use pretty_assertions::assert_eq;
+    use serde::Deserialize;
+    use std::time::Duration;
+
+    #[test]
+    fn ping_message() {
+        assert_eq!(
+            "Pong! REST: 142ms, Gateway: 48ms",
+            crate::ping_message(Duration::from_millis(142), Some(Duration::from_millis(48))),
+        );
+        assert_eq!(
+            "Pong! REST: 142ms, Gateway: N/A",
+            crate::ping_message(Duration::from_millis(142), None),
+        );
+    }
+
+    #[test]
+    fn uptime_message() {
+        const MINUTE: u64 = 60;
+        const HOUR: u64 = 60 * MINUTE;
+        const DAY: u64 = 24 * HOUR;
+
+        let message = |secs| crate::uptime_message(Duration::from_secs(secs));
+        assert_eq!("Up for 0m", message(59));
+        assert_eq!("Up for 1m", message(MINUTE + 59));
+        assert_eq!("Up for 1h", message(HOUR));
+        assert_eq!("Up for 1h 1m", message(HOUR + MINUTE));
+        assert_eq!("Up for 1d", message(DAY));
+        assert_eq!(
+            "Up for 3d 4h 12m",
+            message(3 * DAY + 4 * HOUR + 12 * MINUTE + 30)
+        );
+    }
+
+    #[test]
+    fn userinfo_embed() {
+        let mut user = User::default();
+        user.id = UserId(80351110224678912);
+        user.name = "randomPoison".into();
+        user.discriminator = 1234;
+        user.bot = false;
+
+        // Outside of a server only the account details are shown.
+        let embed = crate::userinfo_embed(&user, None);
+        assert_eq!(
+            json!({
+                "type": "rich",
+                "title": "randomPoison#1234",
+                "thumbnail": { "url": user.face() },
+                "fields": [
+                    { "name": "ID", "value": "80351110224678912", "inline": true },
+                    { "name": "Bot", "value": "No", "inline": true },
+                    { "name": "Created", "value": "<t:1439227597:D>", "inline": true },
+                ],
+            }),
+            json!(embed.0),
+        );
+
+        // In a server the join date and roles are added.
+        let member = Member::deserialize(json!({
+            "guild_id": "1",
+            "user": user,
+            "roles": ["2", "3"],
+            "joined_at": "2020-01-01T00:00:00Z",
+            "deaf": false,
+            "mute": false,
+            "nick": null,
+            "premium_since": null,
+            "permissions": null,
+            "avatar": null,
+            "communication_disabled_until": null,
+        }))
+        .unwrap();
+        let embed = crate::userinfo_embed(&user, Some(&member));
+        let fields = &json!(embed.0)["fields"];
+        assert_eq!(
+            json!({ "name": "Joined", "value": "<t:1577836800:D>", "inline": true }),
+            fields[3],
+        );
+        assert_eq!(
+            json!({ "name": "Roles", "value": "<@&2> <@&3>", "inline": false }),
+            fields[4],
+        );
+    }
+
+    #[test]
+    fn help_message() {
+        let mut commands = vec![crate::ping(), crate::help(), crate::config::config()];
+        poise::set_qualified_names(&mut commands);
+        assert_eq!(
+            "**General**\n\
+            `!ping` - Checks that the bot is running and reports its latency.\n\
+            `!help [command]` - Lists the available commands, or shows the full usage of a single command\n\
+            \n\
+            **Config**\n\
+            `!config` - Enable or disable commands in this server.\n\
+            `!config enable <command>` - Re-enable a disabled command in this server.\n\
+            `!config disable <command>` - Disable a command in this server.\n\
+            \n\
+            Use `!help <command>` to see the full usage of a command.",
+            crate::help_message(&commands),
+        );
+    }
+
+    #[test]
+    fn command_help() {
+        let mut commands = vec![crate::todo::todo()];
+        poise::set_qualified_names(&mut commands);
+
+        // Subcommands and aliases can be looked up by name.
+        let undone = crate::find_command(&commands, "!todo REOPEN").unwrap();
+        assert_eq!(
+            "`!todo undone <key>`\n\
+            Mark a completed item as not done.\n\
+            \n\
+            Aliases: reopen\n",
+            crate::command_help(undone),
+        );
+        assert!(crate::find_command(&commands, "todo nope").is_none());
+        assert!(crate::find_command(&commands, "").is_none());
+
+        // Argument descriptions and subcommands are listed.
+        let show = crate::find_command(&commands, "todo show").unwrap();
+        assert!(crate::command_help(show)
+            .contains("\nArguments:\n  `sort` - Sort by priority (the default) or due date\n"));
+        let todo = crate::find_command(&commands, "todo").unwrap();
+        assert!(crate::command_help(todo).contains("\nSubcommands:\n`!todo show "));
+    }
+
+    #[test]
+    fn split_message() {
+        assert_eq!(vec!["a\nb\n"], crate::split_message("a\nb"));
+
+        // Two of these lines fit in a message, but not three.
+        let line = "x".repeat(900);
+        let message = [line.as_str(); 3].join("\n");
+        let chunks = crate::split_message(&message);
+        assert_eq!(2, chunks.len());
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 2000));
+        assert_eq!(format!("{line}\n"), chunks[1]);
+    }
+}
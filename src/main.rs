@@ -1,9 +1,23 @@
 use anyhow::{anyhow, Context, Error};
-use eval_bot::{age, ping, todo::todo, Data};
+use eval_bot::{
+    age,
+    bug::bug,
+    cache::TtlCache,
+    config::{self, config},
+    duration, help, on_error, ping,
+    prefix::{self, setprefix},
+    retry,
+    todo::{
+        self, send_reminders, todo, DEFAULT_COOLDOWN, DEFAULT_MAX_ITEMS, DEFAULT_WELCOME_MESSAGE,
+    },
+    uptime, userinfo, Data,
+};
 use mongodb::Database;
 use poise::serenity_prelude::GatewayIntents;
 use shuttle_poise::ShuttlePoise;
 use shuttle_secrets::SecretStore;
+use std::sync::Arc;
+use std::time::Instant;
 
 #[shuttle_runtime::main]
 async fn serenity(
@@ -17,12 +31,58 @@ async fn serenity(
         return Err(anyhow!("'DISCORD_TOKEN' was not found").into());
     };
 
+    // The welcome message for new `!todo` users can optionally be overridden in
+    // `Secrets.toml`.
+    let todo_welcome_message = secret_store
+        .get("TODO_WELCOME_MESSAGE")
+        .unwrap_or_else(|| DEFAULT_WELCOME_MESSAGE.into());
+
+    // The TODO item limit can optionally be overridden with `TODO_MAX_ITEMS`.
+    let todo_max_items = match secret_store.get("TODO_MAX_ITEMS") {
+        Some(max_items) => max_items
+            .parse()
+            .context("'TODO_MAX_ITEMS' must be a whole number")?,
+        None => DEFAULT_MAX_ITEMS,
+    };
+
+    // The cooldown between commands that change a TODO list can optionally be
+    // overridden with `TODO_COOLDOWN`, e.g. `5s`. Set it to `0s` to turn it off.
+    let todo_cooldown = match secret_store.get("TODO_COOLDOWN") {
+        Some(cooldown) => duration::parse(&cooldown).context("'TODO_COOLDOWN' is invalid")?,
+        None => DEFAULT_COOLDOWN,
+    };
+
+    // The number of times to retry failed database operations can optionally be
+    // overridden with `DB_RETRIES`.
+    let db_retries = match secret_store.get("DB_RETRIES") {
+        Some(retries) => retries
+            .parse()
+            .context("'DB_RETRIES' must be a whole number")?,
+        None => retry::DEFAULT_RETRIES,
+    };
+
+    // TODO lists are displayed as embeds if `TODO_USE_EMBEDS` is set to `true`.
+    let todo_use_embeds = secret_store.get("TODO_USE_EMBEDS").as_deref() == Some("true");
+
     let framework = poise::Framework::<Data, _>::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![ping(), age(), todo()],
+            commands: vec![
+                ping(),
+                uptime(),
+                age(),
+                userinfo(),
+                help(),
+                todo(),
+                bug(),
+                config(),
+                setprefix(),
+            ],
+            command_check: Some(|ctx| Box::pin(config::check_enabled(ctx))),
+            on_error: |error| Box::pin(on_error(error)),
             prefix_options: poise::PrefixFrameworkOptions {
+                // `.` always works, and `!` can be changed per guild with `!setprefix`.
                 prefix: Some(".".into()),
-                additional_prefixes: vec![poise::Prefix::Literal("!")],
+                dynamic_prefix: Some(|ctx| Box::pin(prefix::dynamic_prefix(ctx))),
                 mention_as_prefix: true,
                 case_insensitive_commands: true,
                 ..Default::default()
@@ -34,7 +94,30 @@ async fn serenity(
         .setup(move |ctx, _ready, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Data { db })
+
+                // Send `!todo reminder` DMs in the background for as long as the bot runs.
+                let todo_cache = Arc::new(TtlCache::new(todo::CACHE_TTL));
+                tokio::spawn(send_reminders(
+                    ctx.http.clone(),
+                    db.clone(),
+                    todo_cache.clone(),
+                    db_retries,
+                ));
+
+                Ok(Data {
+                    db,
+                    db_retries,
+                    start_time: Instant::now(),
+                    todo_welcome_message,
+                    todo_max_items,
+                    todo_use_embeds,
+                    todo_cache,
+                    recent_todo_adds: Default::default(),
+                    todo_cooldown,
+                    todo_cooldowns: Default::default(),
+                    guild_prefixes: Default::default(),
+                    guild_disabled_commands: TtlCache::new(config::SETTINGS_TTL),
+                })
             })
         })
         .build()
@@ -0,0 +1,176 @@
+//! `!setprefix` - Per-guild command prefixes.
+//!
+//! # Usage
+//!
+//! * `!setprefix <PREFIX>` - Change the prefix for commands in this server, e.g.
+//!   `!setprefix ?` to use `?todo`. Use `!setprefix !` to go back to the default.
+//!
+//! Commands can always be used with `.` or by mentioning the bot as well, so a
+//! forgotten prefix can still be changed back. Prefixes can't be changed in DMs.
+//!
+//! `!setprefix` requires the Manage Server permission.
+
+use crate::{serenity, Context, Data, Error};
+use anyhow::{Context as _, Result};
+use mongodb::bson::{doc, Document};
+use mongodb::options::UpdateOptions;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::info;
+
+/// The name of the collection where guild prefixes are stored.
+const COLLECTION_NAME: &str = "guild_prefixes";
+
+/// The prefix used in DMs and in guilds that haven't set their own.
+pub const DEFAULT_PREFIX: &str = "!";
+
+/// The maximum length of a custom prefix, in characters.
+const MAX_PREFIX_LENGTH: usize = 5;
+
+/// Change the prefix for commands in this server.
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn setprefix(ctx: Context<'_>, prefix: String) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .context("`!setprefix` used outside of a guild")?;
+
+    if let Err(message) = validate_prefix(&prefix) {
+        ctx.say(message).await?;
+        return Ok(());
+    }
+
+    ctx.data()
+        .db
+        .collection::<GuildPrefix>(COLLECTION_NAME)
+        .update_one(
+            prefix_query(guild_id),
+            doc! { "$set": { "prefix": &prefix } },
+            UpdateOptions::builder().upsert(true).build(),
+        )
+        .await
+        .with_context(|| format!("Failed to update prefix for guild {guild_id}"))?;
+
+    ctx.data()
+        .guild_prefixes
+        .lock()
+        .unwrap()
+        .insert(guild_id, Some(prefix.clone()));
+
+    info!("Set prefix {prefix:?} in guild {guild_id}");
+
+    ctx.say(format!(
+        "Commands here now start with `{prefix}`, e.g. `{prefix}todo`"
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Gets the prefix for the guild a message was sent in, falling back to the
+/// default. Used as the framework's `dynamic_prefix`.
+///
+/// Prefixes are cached in [`Data::guild_prefixes`] after they're first loaded, so
+/// the database is only checked once per guild.
+pub async fn dynamic_prefix(
+    ctx: poise::PartialContext<'_, Data, Error>,
+) -> Result<Option<String>, Error> {
+    let Some(guild_id) = ctx.guild_id else {
+        return Ok(Some(DEFAULT_PREFIX.into()));
+    };
+
+    let cached = cached_prefix(&ctx.data.guild_prefixes.lock().unwrap(), guild_id);
+    if let Some(prefix) = cached {
+        return Ok(Some(prefix));
+    }
+
+    let configured = ctx
+        .data
+        .db
+        .collection::<GuildPrefix>(COLLECTION_NAME)
+        .find_one(prefix_query(guild_id), None)
+        .await
+        .with_context(|| format!("Failed to get prefix for guild {guild_id}"))?
+        .map(|settings| settings.prefix);
+
+    let mut cache = ctx.data.guild_prefixes.lock().unwrap();
+    cache.insert(guild_id, configured);
+    Ok(cached_prefix(&cache, guild_id))
+}
+
+/// Looks up a guild's prefix in the cache, using the default if the guild hasn't
+/// set one. Returns `None` if the guild hasn't been loaded into the cache yet.
+fn cached_prefix(
+    cache: &HashMap<serenity::GuildId, Option<String>>,
+    guild_id: serenity::GuildId,
+) -> Option<String> {
+    let configured = cache.get(&guild_id)?;
+    Some(configured.as_deref().unwrap_or(DEFAULT_PREFIX).into())
+}
+
+/// Checks that a prefix can be used, returning the message for the user if it
+/// can't.
+fn validate_prefix(prefix: &str) -> Result<(), String> {
+    if prefix.is_empty() || prefix.contains(char::is_whitespace) {
+        return Err("The prefix can't be empty or contain spaces".into());
+    }
+
+    if prefix.chars().count() > MAX_PREFIX_LENGTH {
+        return Err(format!(
+            "The prefix can be at most {MAX_PREFIX_LENGTH} characters long"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Builds the query for finding a guild's prefix document.
+fn prefix_query(guild_id: serenity::GuildId) -> Document {
+    doc! { "guild_id": guild_id.to_string() }
+}
+
+/// The custom prefix for a single guild.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuildPrefix {
+    guild_id: String,
+    prefix: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prefix::{self, DEFAULT_PREFIX};
+    use poise::serenity_prelude::GuildId;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    #[test]
+    fn cached_prefix() {
+        let mut cache = HashMap::new();
+        cache.insert(GuildId(1), Some("?".to_string()));
+        cache.insert(GuildId(2), None);
+
+        // Guilds with a custom prefix use it, and the rest use the default.
+        assert_eq!(Some("?".into()), prefix::cached_prefix(&cache, GuildId(1)));
+        assert_eq!(
+            Some(DEFAULT_PREFIX.into()),
+            prefix::cached_prefix(&cache, GuildId(2))
+        );
+
+        // Guilds that haven't been loaded yet need to be looked up.
+        assert_eq!(None, prefix::cached_prefix(&cache, GuildId(3)));
+    }
+
+    #[test]
+    fn validate_prefix() {
+        assert!(prefix::validate_prefix("?").is_ok());
+        assert!(prefix::validate_prefix("bot!").is_ok());
+        assert!(prefix::validate_prefix("🤖").is_ok());
+
+        assert!(prefix::validate_prefix("").is_err());
+        assert!(prefix::validate_prefix("a b").is_err());
+        assert!(prefix::validate_prefix("toolong").is_err());
+    }
+}
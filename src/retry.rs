@@ -0,0 +1,112 @@
+//! Retrying database operations that fail because of transient errors, such as a
+//! dropped connection.
+//!
+//! Operations are retried with exponential backoff, waiting [`BASE_DELAY`] before
+//! the first retry and doubling the wait for each retry after that. Only
+//! operations that are safe to repeat should be retried, e.g. reads, `$set`
+//! updates, and replacements, since a failed attempt may still have been applied.
+
+use std::fmt::Debug;
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+/// How long to wait before the first retry.
+pub const BASE_DELAY: Duration = Duration::from_millis(100);
+
+/// The default number of times to retry a failed database operation.
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// Runs `operation`, retrying it up to `retries` times if it fails.
+///
+/// The error from the last attempt is returned if every attempt fails.
+pub async fn with_retry<F, Fut, T, E>(
+    retries: u32,
+    base_delay: Duration,
+    mut operation: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Debug,
+{
+    let mut retry = 0;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(error) if retry < retries => {
+                let delay = backoff_delay(base_delay, retry);
+                warn!("Database operation failed, retrying in {delay:?}: {error:?}");
+                tokio::time::sleep(delay).await;
+                retry += 1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Calculates how long to wait before the given retry, counting from 0.
+fn backoff_delay(base_delay: Duration, retry: u32) -> Duration {
+    2u32.checked_pow(retry)
+        .and_then(|factor| base_delay.checked_mul(factor))
+        .unwrap_or(Duration::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::retry::{self, BASE_DELAY};
+    use pretty_assertions::assert_eq;
+    use std::cell::Cell;
+    use std::future::Future;
+    use std::time::Duration;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    /// Runs an operation that fails `failures` times before succeeding, returning
+    /// the result and the number of attempts.
+    fn run_flaky(retries: u32, failures: u32) -> (Result<&'static str, u32>, u32) {
+        let attempts = Cell::new(0);
+        let result = block_on(retry::with_retry(retries, Duration::from_millis(1), || {
+            let attempt = attempts.get() + 1;
+            attempts.set(attempt);
+            async move {
+                if attempt <= failures {
+                    Err(attempt)
+                } else {
+                    Ok("done")
+                }
+            }
+        }));
+        (result, attempts.get())
+    }
+
+    #[test]
+    fn with_retry() {
+        // Successes aren't retried.
+        assert_eq!((Ok("done"), 1), run_flaky(3, 0));
+
+        // Failures are retried until the operation succeeds.
+        assert_eq!((Ok("done"), 3), run_flaky(3, 2));
+        assert_eq!((Ok("done"), 4), run_flaky(3, 3));
+
+        // The last error is returned once the retries run out.
+        assert_eq!((Err(4), 4), run_flaky(3, 10));
+        assert_eq!((Err(1), 1), run_flaky(0, 10));
+    }
+
+    #[test]
+    fn backoff_delay() {
+        assert_eq!(BASE_DELAY, retry::backoff_delay(BASE_DELAY, 0));
+        assert_eq!(BASE_DELAY * 2, retry::backoff_delay(BASE_DELAY, 1));
+        assert_eq!(BASE_DELAY * 8, retry::backoff_delay(BASE_DELAY, 3));
+
+        // Huge retry counts don't overflow.
+        assert_eq!(Duration::MAX, retry::backoff_delay(BASE_DELAY, 100));
+    }
+}
@@ -2,489 +2,6760 @@
 //!
 //! # Usage
 //!
-//! * `!todo [show, print, display]` - Print your TODO list.
-//! * `!todo [add] <ITEM_KEY>` - Add an item to the list.
+//! * `!todo [show, print, display] [SORT] [CATEGORY]` - Print your TODO list.
+//!   `SORT` is either `priority` (the default) or `due` to show the items due
+//!   soonest first. Snoozed items are hidden unless you use `!todo show --all`.
+//! * `!todo show <ITEM_KEY>` - Show all the details of a single item.
+//! * `!todo search <TERM>` - Show the items with the term in their key,
+//!   category, or note.
+//! * `!todo [add] <ITEM_KEY> [PRIORITY] [CATEGORY]` - Add an item to the list.
+//!   Keys with spaces need quotes, e.g. `!todo add "fix build" big project`.
+//!   Everything after the key and priority is the category, with or without
+//!   quotes.
 //! * `!todo (remove, rm, delete) <ITEM_KEY>` - Remove an item from the list.
+//!   Removed items are kept until they're purged, so they can be restored.
+//! * `!todo restore <ITEM_KEY>` - Bring back an item that was removed.
+//! * `!todo purge` - Permanently delete every removed item.
 //! * `!todo (done, finish, finished, x, X) <ITEM_KEY>` - Mark an item done.
+//! * `!todo (undone, reopen) <ITEM_KEY>` - Mark a done item as not done.
+//! * `!todo clear` - Remove every item that's marked done.
+//! * `!todo move <ITEM_KEY> [CATEGORY]` - Change an item's category, keeping its
+//!   priority. Leave out the category to remove the item from its category.
+//! * `!todo edit <ITEM_KEY> <NEW_KEY>` - Rename an item, keeping its priority.
+//! * `!todo reassign-priorities` - Renumber priorities densely, keeping their order.
+//! * `!todo where` - Confirm which list you're using.
+//! * `!todo inbox` - Show your uncategorized items, with menus to categorize them.
+//! * `!todo reorder` - Move an item to a new position in the list using menus.
+//! * `!todo clone-item <ITEM_KEY> [NEW_KEY]` - Copy an item under a new key.
+//! * `!todo estimate <ITEM_KEY> [DURATION]` - Set how long an item will take,
+//!   e.g. `2h30m`, up to 52 weeks. Leave out the duration to clear the estimate.
+//! * `!todo due <ITEM_KEY> [DATE]` - Set when an item is due, e.g. `2024-05-01`,
+//!   `tomorrow`, or `3d`. Leave out the date to clear the due date.
+//! * `!todo note <ITEM_KEY> [TEXT]` - Attach a note to an item. Leave out the
+//!   text to remove the note. Items with notes are marked with `*` in the list.
+//! * `!todo recur <ITEM_KEY> [daily, weekly, monthly]` - Make an item repeat.
+//!   Marking a repeating item done sets its next due date instead of finishing
+//!   it. Leave out the interval to stop the item repeating.
+//! * `!todo snooze <ITEM_KEY> [DURATION]` - Hide an item from your list for a
+//!   while, e.g. `2h` or `3d`. Leave out the duration to bring it back early.
+//! * `!todo workload [CATEGORY]` - Show the total estimated time of pending items.
+//! * `!todo stats` - Show a summary of your list.
+//! * `!todo count [CATEGORY]` - Show how many pending and done items you have.
+//! * `!todo by-tag-cloud` - Show how many items are in each category.
+//! * `!todo priority-style (numeric, emoji) [HIGH] [MEDIUM]` - Choose how
+//!   priorities are displayed.
+//! * `!todo done-category [CATEGORY]` - Move items to `CATEGORY` when they're
+//!   marked done. Leave out the category to turn this off.
+//! * `!todo key-matching (exact, ignore-case)` - Choose whether `add`, `remove`,
+//!   `done`, and `undone` match existing items ignoring case and surrounding
+//!   whitespace, e.g. so that `fix build` bumps `Fix Build`.
+//! * `!todo priority-decay (on, off)` - Choose whether items lose priority as they
+//!   go stale. With decay on, an item's priority drops by 1 for each week since it
+//!   was last added, down to a minimum of 1.
+//! * `!todo reminder [TIME]` - Get a DM with your top items every day at `TIME`,
+//!   e.g. `09:00`. Times are in UTC. Leave out the time to stop the reminders.
+//! * `!todo export` - Download your list as JSON, e.g. to back it up.
+//! * `!todo import [--replace] <JSON>` - Add the items from `!todo export` to your
+//!   list. The JSON can be pasted or attached as a file. When an item is already
+//!   in your list, whichever copy has the higher priority is kept. With
+//!   `--replace` your list is replaced instead, which can be undone with
+//!   `!todo bulk-undo`.
+//! * `!todo bulk-edit <FILE>` - Replace your list with the contents of a text file.
+//! * `!todo bulk-undo` - Restore your list from before the last bulk edit.
+//!
+//! When using `/todo show` your list is only shown to you. Set `ephemeral` to
+//! false to show it to everyone in the channel.
+//!
+//! Lists shown publicly get numbered reactions for the first 10 items. Clicking
+//! one marks that item done, and removing it again marks the item not done.
+//!
+//! # Bulk Editing
+//!
+//! `!todo bulk-edit` takes an uploaded plain-text file with one item per line, in
+//! the form `priority<TAB>done<TAB>category<TAB>key`. `done` must be `true` or
+//! `false`, and `category` may be left empty. Blank lines are ignored.
+//!
+//! Each user has a single TODO list that is shared across every server the bot
+//! is in, as well as in DMs with the bot.
 //!
 //! # Item Prioritization
 //!
 //! Each item is given a priority value in order to bubble higher priority items
 //! to the top of your list. Each time you add an item to your list it increases
 //! the priority by 1. By default the list is printed in priority order.
+//!
+//! To move an item straight to the top of your list you can instead give an
+//! explicit priority when adding it, e.g. `!todo add "fix build" 50`, which sets
+//! the item's priority to exactly that value.
 
-use crate::{serenity, Context, Error};
-use anyhow::{Context as _, Result};
-use mongodb::bson::doc;
+use crate::cache::TtlCache;
+use crate::retry::{with_retry, BASE_DELAY};
+use crate::{duration, serenity, Context, Data, Error};
+use anyhow::{anyhow, Context as _, Result};
+use chrono::{DateTime, Months, NaiveDate, NaiveTime, TimeZone, Utc};
+use futures::{StreamExt, TryStreamExt};
+use mongodb::bson::{doc, Document};
+use mongodb::Database;
 use poise::serenity_prelude::{CacheHttp, User};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info};
 
+/// The width of the bar for the most common category in `!todo by-tag-cloud`.
+const TAG_CLOUD_BAR_WIDTH: usize = 20;
+
+/// The default message sent to a user the first time they use `!todo`.
+pub const DEFAULT_WELCOME_MESSAGE: &str =
+    "Welcome! I've created your TODO list. Try `!todo add \"buy milk\"`.";
+
+/// The name of the collection where the audit trail of changes to TODO lists is
+/// stored.
+const AUDIT_COLLECTION_NAME: &str = "todo_audit";
+
+/// The maximum length of a Discord message.
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// The name of the file sent by `!todo export` when the list is too long to send
+/// as a message.
+const EXPORT_FILENAME: &str = "todo.json";
+
+/// How long a user's TODO list is kept in memory after it was last loaded or
+/// saved.
+pub const CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// The longest estimate an item can have. This keeps the total workload well
+/// within what can be stored and added up.
+const MAX_ESTIMATE: Duration = Duration::from_secs(52 * 7 * 24 * 60 * 60);
+
+/// The default minimum time between a user's commands that change their list.
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(2);
+
+/// How much an item's priority drops for each week since it was last added, when
+/// the user has turned on priority decay.
+const PRIORITY_DECAY_PER_WEEK: u32 = 1;
+
+/// The default maximum number of items a user can have in their list.
+pub const DEFAULT_MAX_ITEMS: usize = 200;
+
+/// The maximum number of options Discord allows in a single select menu.
+const MAX_SELECT_OPTIONS: usize = 25;
+
+/// The maximum length Discord allows for the label or value of a select menu
+/// option.
+const MAX_SELECT_OPTION_LENGTH: usize = 100;
+
+/// The maximum number of action rows Discord allows in a single message.
+const MAX_ACTION_ROWS: usize = 5;
+
+/// The prefix for the custom IDs of the menus sent by `!todo inbox`. The rest of
+/// the ID is the index of the item in the inbox.
+const INBOX_MENU_PREFIX: &str = "todo.inbox.";
+
+/// The maximum number of fields Discord allows in a single embed.
+const MAX_EMBED_FIELDS: usize = 25;
+
+/// The maximum length Discord allows for the value of an embed field.
+const MAX_EMBED_FIELD_LENGTH: usize = 1024;
+
+/// The lowest top priority that gives an embedded list a red sidebar. See
+/// [`priority_color`].
+const HIGH_PRIORITY_COLOR_THRESHOLD: u32 = 10;
+
+/// The lowest top priority that gives an embedded list a yellow sidebar. See
+/// [`priority_color`].
+const MEDIUM_PRIORITY_COLOR_THRESHOLD: u32 = 5;
+
+/// The maximum number of autocomplete suggestions Discord allows.
+const MAX_AUTOCOMPLETE_CHOICES: usize = 25;
+
+/// The maximum length Discord allows for an autocomplete suggestion.
+const MAX_AUTOCOMPLETE_LENGTH: usize = 100;
+
+/// How often the background task checks for reminders that need to be sent.
+const REMINDER_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The number of pending items included in a daily reminder.
+const REMINDER_ITEMS: usize = 5;
+
+/// How long interactive commands like `!todo reorder` wait for the user to respond.
+const INTERACTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The reactions added to a printed list for marking items done, in the order of
+/// the items they refer to.
+const DONE_REACTIONS: [&str; 10] = ["1️⃣", "2️⃣", "3️⃣", "4️⃣", "5️⃣", "6️⃣", "7️⃣", "8️⃣", "9️⃣", "🔟"];
+
+/// Show your TODO list, or add an item to it.
 #[poise::command(
     prefix_command,
     slash_command,
-    subcommands("show", "add", "remove", "done")
+    subcommands(
+        "show",
+        "search",
+        "add",
+        "remove",
+        "restore",
+        "purge",
+        "done",
+        "undone",
+        "clear",
+        "move_",
+        "edit",
+        "reassign_priorities",
+        "where_",
+        "inbox",
+        "reorder",
+        "clone_item",
+        "estimate",
+        "due",
+        "note",
+        "recur",
+        "snooze",
+        "workload",
+        "stats",
+        "count",
+        "tag_cloud",
+        "priority_style",
+        "done_category",
+        "key_matching",
+        "priority_decay",
+        "reminder",
+        "export",
+        "import",
+        "bulk_edit",
+        "bulk_undo"
+    )
 )]
 pub async fn todo(
     ctx: Context<'_>,
     key: Option<String>,
-    category: Option<String>,
+    priority: Option<u32>,
+    #[rest] category: Option<String>,
 ) -> Result<(), Error> {
     match key {
-        Some(key) => run_command(ctx, TodoCommand::Add { key, category }).await,
-        None => run_command(ctx, TodoCommand::Print { category }).await,
+        Some(key) => {
+            let (key, category) = key_and_category(ctx, key, priority, category);
+            add_item(ctx, key, priority, category).await
+        }
+        None => {
+            let category = category.as_deref().and_then(parse_category);
+            let sort = SortMode::Priority;
+            let command = TodoCommand::Print {
+                category,
+                sort,
+                all: false,
+            };
+            run_command(ctx, command).await
+        }
+    }
+}
+
+/// Show your TODO list, a category from it, or a single item in detail.
+#[poise::command(prefix_command, slash_command)]
+pub async fn show(
+    ctx: Context<'_>,
+    #[description = "Sort by priority (the default) or due date"] sort: Option<SortMode>,
+    #[description = "A category to show, or an item to show in detail"] category: Option<String>,
+    #[description = "Only show the list to you (defaults to true)"] ephemeral: Option<bool>,
+    #[description = "Include snoozed items"] all: Option<bool>,
+) -> Result<(), Error> {
+    // Only slash commands can be replied to ephemerally, so prefix commands always
+    // reply publicly.
+    let ephemeral = matches!(ctx, poise::Context::Application(_)) && ephemeral.unwrap_or(true);
+
+    // Prefix commands can use `!todo show --all` rather than passing `all` after
+    // the other arguments.
+    let (category, all) = match category.as_deref() {
+        Some("--all") => (None, true),
+        _ => (category, all.unwrap_or(false)),
+    };
+
+    // Items and categories share the same argument, so check whether it's the key of
+    // one of the user's items before treating it as a category.
+    if let Some(key) = &category {
+        if load_list(ctx).await?.get(key).is_some() {
+            let command = TodoCommand::PrintOne(key.clone());
+            return run_command_with_visibility(ctx, command, ephemeral).await;
+        }
     }
+
+    let sort = sort.unwrap_or_default();
+    let command = TodoCommand::Print {
+        category,
+        sort,
+        all,
+    };
+    run_command_with_visibility(ctx, command, ephemeral).await
 }
 
+/// Find the items in your list with the search term in their key, category, or note.
 #[poise::command(prefix_command, slash_command)]
-pub async fn show(ctx: Context<'_>, category: Option<String>) -> Result<(), Error> {
-    run_command(ctx, TodoCommand::Print { category }).await
+pub async fn search(ctx: Context<'_>, #[rest] term: String) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::Search(term)).await
 }
 
+/// Add an item to your list, or bump its priority if it's already there.
 #[poise::command(prefix_command, slash_command)]
-pub async fn add(ctx: Context<'_>, key: String, category: Option<String>) -> Result<(), Error> {
-    run_command(ctx, TodoCommand::Add { key, category }).await
+pub async fn add(
+    ctx: Context<'_>,
+    key: String,
+    #[description = "Set the priority to this value instead of increasing it by 1"]
+    priority: Option<u32>,
+    #[rest] category: Option<String>,
+) -> Result<(), Error> {
+    let (key, category) = key_and_category(ctx, key, priority, category);
+    add_item(ctx, key, priority, category).await
+}
+
+/// Picks out the key and category of an item being added.
+///
+/// Slash commands get the category as its own argument. Prefix commands without
+/// a priority are split with [`parse_key_and_category`], so that quoted keys and
+/// categories follow the same rules whichever one is quoted.
+fn key_and_category(
+    ctx: Context<'_>,
+    key: String,
+    priority: Option<u32>,
+    category: Option<String>,
+) -> (String, Option<String>) {
+    match ctx {
+        poise::Context::Prefix(prefix) if priority.is_none() => parse_key_and_category(prefix.args),
+        _ => (key, category.as_deref().and_then(parse_category)),
+    }
+}
+
+/// Splits the arguments of a prefix command into an item key and a category, e.g.
+/// `!todo add "fix build" big project` into `fix build` and `big project`.
+///
+/// Keys with spaces need quotes, but the category is everything after the key,
+/// with or without quotes. See [`parse_category`].
+fn parse_key_and_category(input: &str) -> (String, Option<String>) {
+    let input = input.trim_start();
+    let (key, rest) = match input
+        .strip_prefix('"')
+        .and_then(|input| input.split_once('"'))
+    {
+        Some((key, rest)) => (key, rest),
+        None => input.split_once(char::is_whitespace).unwrap_or((input, "")),
+    };
+    (key.into(), parse_category(rest))
+}
+
+/// Parses the category at the end of a prefix command, e.g. `big project` in
+/// `!todo add "fix build" big project`.
+///
+/// The category may be wrapped in quotes, which are removed. Returns `None` if
+/// the category is empty.
+fn parse_category(input: &str) -> Option<String> {
+    let input = input.trim();
+    let category = input
+        .strip_prefix('"')
+        .and_then(|input| input.strip_suffix('"'))
+        .filter(|inner| !inner.contains('"'))
+        .unwrap_or(input)
+        .trim();
+
+    (!category.is_empty()).then(|| category.into())
+}
+
+/// Adds an item to the user's list.
+///
+/// If the user adds the same key several times in quick succession it's likely
+/// they didn't mean to bump the priority each time, so we offer to undo the add.
+async fn add_item(
+    ctx: Context<'_>,
+    key: String,
+    priority: Option<u32>,
+    category: Option<String>,
+) -> Result<()> {
+    let user_id = ctx.author().id;
+
+    // Setting an explicit priority is never accidental, so only repeated bumps are
+    // checked.
+    if priority.is_some() {
+        let command = TodoCommand::Add {
+            key,
+            category,
+            priority,
+        };
+        return run_command(ctx, command).await;
+    }
+
+    // Check the cooldown before remembering the add, since an add that's rejected
+    // doesn't count towards a rapid repeat.
+    if on_cooldown(ctx, true).await? {
+        return Ok(());
+    }
+
+    let now = Instant::now();
+    let rapid_repeat = {
+        let recent_adds = ctx.data().recent_todo_adds.lock().unwrap();
+        is_rapid_repeat(recent_adds.get(&user_id), &key, now)
+    };
+
+    let command = TodoCommand::Add {
+        key: key.clone(),
+        category,
+        priority: None,
+    };
+    let changed = apply_command(ctx, command, false).await?;
+
+    // Only remember adds that went through, e.g. not ones rejected because the
+    // list is full, and there's nothing to undo for those either.
+    if !changed {
+        return Ok(());
+    }
+    ctx.data().recent_todo_adds.lock().unwrap().insert(
+        user_id,
+        RecentAdd {
+            key: key.clone(),
+            at: now,
+        },
+    );
+
+    if !rapid_repeat {
+        return Ok(());
+    }
+
+    let reply = ctx
+        .send(|m| {
+            m.content(format!(
+                "You've added {key:?} several times in a row, did you mean to bump its priority?"
+            ))
+            .components(|c| {
+                c.create_action_row(|row| {
+                    row.create_button(|b| {
+                        b.custom_id("todo.add.undo")
+                            .label("Undo")
+                            .style(serenity::ButtonStyle::Secondary)
+                    })
+                })
+            })
+        })
+        .await?;
+
+    let press = reply
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(user_id)
+        .timeout(INTERACTION_TIMEOUT)
+        .await;
+
+    // Remove the button whether or not it was pressed.
+    reply.edit(ctx, |m| m.components(|c| c)).await?;
+
+    if let Some(press) = press {
+        press.defer(ctx).await?;
+        apply_command(ctx, TodoCommand::UndoAdd(key), false).await?;
+    }
+
+    Ok(())
+}
+
+/// The most recent item a user added to their list, used to detect accidental
+/// repeated adds.
+#[derive(Debug, Clone)]
+pub struct RecentAdd {
+    key: String,
+    at: Instant,
+}
+
+/// Adding the same key again within this window counts as a rapid repeat.
+const RAPID_ADD_WINDOW: Duration = Duration::from_secs(10);
+
+/// Checks whether adding `key` at `now` repeats the user's previous add within
+/// [`RAPID_ADD_WINDOW`].
+fn is_rapid_repeat(previous: Option<&RecentAdd>, key: &str, now: Instant) -> bool {
+    match previous {
+        Some(previous) => {
+            previous.key == key && now.saturating_duration_since(previous.at) < RAPID_ADD_WINDOW
+        }
+        None => false,
+    }
 }
 
+/// Remove an item from your list.
 #[poise::command(prefix_command, slash_command)]
-pub async fn remove(ctx: Context<'_>, key: String) -> Result<(), Error> {
+pub async fn remove(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_key"] key: String,
+) -> Result<(), Error> {
     run_command(ctx, TodoCommand::Remove(key)).await
 }
 
+/// Bring back an item that you removed.
+#[poise::command(prefix_command, slash_command)]
+pub async fn restore(ctx: Context<'_>, key: String) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::Restore(key)).await
+}
+
+/// Permanently delete the items you've removed.
+#[poise::command(prefix_command, slash_command)]
+pub async fn purge(ctx: Context<'_>) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::Purge).await
+}
+
+/// Mark an item as done.
 #[poise::command(prefix_command, slash_command)]
-pub async fn done(ctx: Context<'_>, key: String) -> Result<(), Error> {
+pub async fn done(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_key"] key: String,
+) -> Result<(), Error> {
     run_command(ctx, TodoCommand::Finish(key)).await
 }
 
-/// Loads the user's TODO list state from the database and then process the
-/// command.
-async fn run_command(ctx: Context<'_>, command: TodoCommand) -> Result<()> {
-    let user_id = ctx.author().id;
+/// Mark a completed item as not done.
+#[poise::command(prefix_command, slash_command, aliases("reopen"))]
+pub async fn undone(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_key"] key: String,
+) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::Unfinish(key)).await
+}
 
-    // Get the collection of user TODO lists and find the document for the user that
-    // sent the message.
-    let collection = ctx.data().db.collection("user_todos");
-    let query = doc! { "user_id": user_id.to_string() };
+/// Remove every completed item from your list.
+#[poise::command(prefix_command, slash_command)]
+pub async fn clear(ctx: Context<'_>) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::ClearDone).await
+}
 
-    // Attempt to load the user's TODO list state from the database.
-    let doc = collection
-        .find_one(query.clone(), None)
-        .await
-        .with_context(|| format!("Failed to get TODO list for user {user_id}"))?;
-    debug!("Loaded TODO list for user {user_id}: {doc:#?}");
+/// Move an item to a different category without changing its priority.
+#[poise::command(prefix_command, slash_command, rename = "move")]
+pub async fn move_(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_key"] key: String,
+    category: Option<String>,
+) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::Move { key, category }).await
+}
 
-    // If this is the first time the user is using the `!todo` command we need to
-    // insert a new document for the user.
-    let mut user_list = match doc {
-        Some(doc) => doc,
+/// Rename an item, keeping its priority and category.
+#[poise::command(prefix_command, slash_command)]
+pub async fn edit(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_key"] from: String,
+    to: String,
+) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::Rename { from, to }).await
+}
 
-        None => {
-            info!("First time usage of `!todo` for user {user_id}, inserting empty list");
+/// Renumber your priorities to 1, 2, 3, etc. without changing their order.
+#[poise::command(prefix_command, slash_command, rename = "reassign-priorities")]
+pub async fn reassign_priorities(ctx: Context<'_>) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::CompactPriorities).await
+}
 
-            let new = TodoList::new(user_id);
-            collection.insert_one(new.clone(), None).await?;
-            new
-        }
+/// Explain which servers your TODO list is shared between.
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "where",
+    aliases("list-all-guilds")
+)]
+pub async fn where_(ctx: Context<'_>) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::Where).await
+}
+
+/// Move an item to a new position in your list using select menus.
+#[poise::command(prefix_command, slash_command)]
+pub async fn reorder(ctx: Context<'_>) -> Result<(), Error> {
+    // Only the selection changes the list, so the cooldown starts once it's made.
+    if on_cooldown(ctx, false).await? {
+        return Ok(());
+    }
+
+    let user_id = ctx.author().id;
+    let todo_list = load_list(ctx).await?;
+
+    // Discord only allows 25 options in a select menu, so only the top of the list
+    // can be reordered this way.
+    let keys = todo_list.sorted_keys();
+    let keys = &keys[..keys.len().min(MAX_SELECT_OPTIONS)];
+    if keys.is_empty() {
+        ctx.say("Your list is empty, there's nothing to reorder")
+            .await?;
+        return Ok(());
+    }
+
+    // Ask the user which item they want to move.
+    let reply = ctx
+        .send(|m| {
+            m.content("Which item do you want to move?")
+                .components(|c| {
+                    c.create_action_row(|row| {
+                        row.create_select_menu(|menu| {
+                            // Keys can be too long for an option, so the value is
+                            // the item's position in `keys` instead.
+                            menu.custom_id("todo.reorder.item").options(|options| {
+                                for (index, key) in keys.iter().enumerate() {
+                                    options.create_option(|option| {
+                                        option.label(select_label(key)).value(index)
+                                    });
+                                }
+                                options
+                            })
+                        })
+                    })
+                })
+        })
+        .await?;
+
+    let message = reply.message().await?;
+    let Some(item_selection) = message
+        .await_component_interaction(ctx)
+        .author_id(user_id)
+        .timeout(INTERACTION_TIMEOUT)
+        .await
+    else {
+        reply
+            .edit(ctx, |m| m.content("Reorder timed out").components(|c| c))
+            .await?;
+        return Ok(());
     };
+    item_selection.defer(ctx).await?;
 
-    // Handle the message, updating `todo_state` and getting the response message.
-    let response = handle_command(command, &mut user_list, ctx.author());
-
-    // Write the updated TODO state to the database.
-    collection
-        .update_one(
-            query,
-            doc! {
-                "$set": {
-                    "items": bson::to_bson(&user_list.items).unwrap(),
-                },
-            },
-            None,
-        )
+    // Ask the user where the item should go.
+    reply
+        .edit(ctx, |m| {
+            m.content("Which position should it move to?")
+                .components(|c| {
+                    c.create_action_row(|row| {
+                        row.create_select_menu(|menu| {
+                            menu.custom_id("todo.reorder.position").options(|options| {
+                                for position in 1..=keys.len() {
+                                    options.create_option(|option| {
+                                        option.label(position).value(position)
+                                    });
+                                }
+                                options
+                            })
+                        })
+                    })
+                })
+        })
+        .await?;
+
+    let Some(position_selection) = message
+        .await_component_interaction(ctx)
+        .author_id(user_id)
+        .timeout(INTERACTION_TIMEOUT)
         .await
-        .with_context(|| format!("Failed to update TODO items for user {user_id}"))?;
+    else {
+        reply
+            .edit(ctx, |m| m.content("Reorder timed out").components(|c| c))
+            .await?;
+        return Ok(());
+    };
+    position_selection.defer(ctx).await?;
 
-    // Send the response to the channel where the command was sent.
-    if let Err(e) = ctx.channel_id().say(ctx.http(), response).await {
-        error!("Error sending message: {:?}", e);
+    // The user may have changed their list some other way while picking.
+    if on_cooldown(ctx, true).await? {
+        reply
+            .edit(ctx, |m| m.content("Reorder cancelled").components(|c| c))
+            .await?;
+        return Ok(());
     }
 
+    // Remove the menus now that the selection is complete.
+    reply
+        .edit(ctx, |m| m.content("Reordering...").components(|c| c))
+        .await?;
+
+    let command = reorder_command(
+        &item_selection.data.values,
+        &position_selection.data.values,
+        keys,
+    )
+    .context("Received an invalid reorder selection")?;
+    apply_command(ctx, command, false).await?;
     Ok(())
 }
 
-/// A TODO list for a single user.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
-pub struct TodoList {
-    user_id: serenity::UserId,
+/// Show your uncategorized items, with menus to pick a category for each.
+#[poise::command(prefix_command, slash_command)]
+pub async fn inbox(ctx: Context<'_>) -> Result<(), Error> {
+    // Only the selections change the list, so each one is checked when it's made.
+    if on_cooldown(ctx, false).await? {
+        return Ok(());
+    }
 
-    /// The items in the user's list. The key is the item key, and the value is the
-    /// item state.
-    items: HashMap<String, TodoItem>,
-}
+    let user_id = ctx.author().id;
+    let todo_list = load_list(ctx).await?;
 
-impl TodoList {
-    fn new(user_id: serenity::UserId) -> Self {
-        TodoList {
-            user_id,
-            items: Default::default(),
-        }
+    let keys = todo_list.inbox_keys();
+    if keys.is_empty() {
+        ctx.say("Your inbox is empty").await?;
+        return Ok(());
     }
-}
 
-/// A single TODO item in a user's TODO list.
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
-pub struct TodoItem {
-    pub priority: u32,
-    pub done: bool,
-    pub category: Option<String>,
-}
+    let max_priority = keys.iter().map(|key| todo_list.items[key].priority).max();
+    let priority_width = priority_width(max_priority.unwrap_or_default());
+    let mut content = format!("Inbox for {}:\n```\n", ctx.author().name);
+    for key in &keys {
+        let item = &todo_list.items[key];
+        let line = item.display_line(
+            key,
+            false,
+            todo_list.priority_style,
+            priority_width,
+            Utc::now(),
+        );
+        writeln!(&mut content, "{line}").unwrap();
+    }
+    content.push_str("```\n");
 
-#[derive(Debug, Clone)]
-enum TodoCommand {
-    Print {
-        category: Option<String>,
-    },
+    // Items can only be triaged into categories that are already in use.
+    let mut categories = todo_list.categories();
+    categories.truncate(MAX_SELECT_OPTIONS);
+    if categories.is_empty() {
+        content.push_str("Add a category to an item to start sorting your inbox");
+        ctx.say(content).await?;
+        return Ok(());
+    }
 
-    Add {
-        key: String,
-        category: Option<String>,
-    },
+    // Discord allows at most 5 action rows per message, so only the top of the inbox
+    // gets a menu.
+    let reply = ctx
+        .send(|m| {
+            m.content(content).components(|c| {
+                for (index, key) in keys.iter().take(MAX_ACTION_ROWS).enumerate() {
+                    c.create_action_row(|row| {
+                        row.create_select_menu(|menu| {
+                            // Categories can be too long for an option, so the
+                            // value is the category's position in `categories`.
+                            menu.custom_id(format!("{INBOX_MENU_PREFIX}{index}"))
+                                .placeholder(format!("Categorize {:?}", select_label(key)))
+                                .options(|options| {
+                                    for (index, category) in categories.iter().enumerate() {
+                                        options.create_option(|option| {
+                                            option.label(select_label(category)).value(index)
+                                        });
+                                    }
+                                    options
+                                })
+                        })
+                    });
+                }
+                c
+            })
+        })
+        .await?;
 
-    Remove(String),
-    Finish(String),
+    let message = reply.message().await?;
+    while let Some(selection) = message
+        .await_component_interaction(ctx)
+        .author_id(user_id)
+        .timeout(INTERACTION_TIMEOUT)
+        .await
+    {
+        selection.defer(ctx).await?;
+
+        let data = &selection.data;
+        let Some(command) = inbox_command(&data.custom_id, &data.values, &keys, &categories) else {
+            continue;
+        };
+        if on_cooldown(ctx, true).await? {
+            continue;
+        }
+        apply_command(ctx, command, false).await?;
+    }
+
+    // Remove the menus once the user is done triaging.
+    reply.edit(ctx, |m| m.components(|c| c)).await?;
+
+    Ok(())
 }
 
-/// Performs the core logic for handling a `!todo` command.
+/// Builds the command for a category selected from one of the menus sent by
+/// `!todo inbox`.
 ///
-/// Updates the state of `todo_list` to reflect the new list state, and returns
-/// the message that should be sent back to the channel where the command was
-/// given.
-fn handle_command(command: TodoCommand, todo_list: &mut TodoList, author: &User) -> String {
-    let user_id = author.id;
+/// `keys` are the inbox keys in the order they were displayed, which the menu's
+/// custom ID indexes into, and `categories` are the categories in the order they
+/// were offered, which the selected value indexes into.
+fn inbox_command(
+    custom_id: &str,
+    values: &[String],
+    keys: &[String],
+    categories: &[&str],
+) -> Option<TodoCommand> {
+    let index: usize = custom_id.strip_prefix(INBOX_MENU_PREFIX)?.parse().ok()?;
+    let key = keys.get(index)?.clone();
+    let index: usize = values.first()?.parse().ok()?;
+    let category = categories.get(index)?.to_string();
+    Some(TodoCommand::Move {
+        key,
+        category: Some(category),
+    })
+}
 
-    // Handle the selected command.
-    match command {
-        TodoCommand::Add { key, category } => {
-            let item = todo_list.items.entry(key.clone()).or_default();
-            item.priority += 1;
+/// Builds the reorder command from the values selected in the item and position
+/// menus sent by `!todo reorder`.
+///
+/// `keys` are the keys in the order they were offered in the item menu, which its
+/// values index into.
+fn reorder_command(
+    item_values: &[String],
+    position_values: &[String],
+    keys: &[String],
+) -> Option<TodoCommand> {
+    let index: usize = item_values.first()?.parse().ok()?;
+    let key = keys.get(index)?.clone();
+    let position = position_values.first()?.parse().ok()?;
+    Some(TodoCommand::Reorder { key, position })
+}
 
-            // Update the item's category if one was specified.
-            if category.is_some() {
-                item.category = category;
-            }
+/// Shortens `text` to fit in a select menu option, marking it with an ellipsis
+/// if it was cut off.
+fn select_label(text: &str) -> String {
+    if text.chars().count() <= MAX_SELECT_OPTION_LENGTH {
+        return text.into();
+    }
 
-            let key_display = match &item.category {
-                Some(category) => format!("[{category}] {key:?}"),
-                None => format!("{key:?}"),
-            };
+    let mut label = text
+        .chars()
+        .take(MAX_SELECT_OPTION_LENGTH - 1)
+        .collect::<String>();
+    label.push('…');
+    label
+}
 
-            info!(
-                "Updated TODO item {key_display} for user {user_id}, priority: {}",
-                item.priority,
-            );
+/// Copy an item under a new key, defaulting to "<key> (copy)".
+#[poise::command(prefix_command, slash_command, rename = "clone-item")]
+pub async fn clone_item(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_key"] key: String,
+    new_key: Option<String>,
+) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::Duplicate { key, new_key }).await
+}
 
-            let response = match item.priority {
-                1 => format!("Added item {key_display} to your list"),
-                _ => format!("Updated item {key_display}, priority is {}", item.priority),
-            };
+/// Set or clear an estimate of how long an item will take, e.g. "2h30m".
+#[poise::command(prefix_command, slash_command)]
+pub async fn estimate(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_key"] key: String,
+    #[rest] estimate: Option<String>,
+) -> Result<(), Error> {
+    let estimate = match estimate.as_deref().map(duration::parse).transpose() {
+        Ok(estimate) => estimate,
+        Err(error) => {
+            ctx.say(format!("{error}")).await?;
+            return Ok(());
+        }
+    };
 
-            response
+    run_command(ctx, TodoCommand::Estimate { key, estimate }).await
+}
+
+/// Set or clear an item's due date, e.g. "2024-05-01", "tomorrow", or "3d".
+#[poise::command(prefix_command, slash_command)]
+pub async fn due(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_key"] key: String,
+    #[rest] date: Option<String>,
+) -> Result<(), Error> {
+    let due = match date.map(|date| parse_due(&date, Utc::now())).transpose() {
+        Ok(due) => due,
+        Err(error) => {
+            ctx.say(format!("{error}")).await?;
+            return Ok(());
         }
+    };
 
-        TodoCommand::Remove(key) => {
-            let _old = todo_list.items.remove(&key);
+    run_command(ctx, TodoCommand::Due { key, due }).await
+}
 
-            info!("Removed TODO item {key:?} for user {user_id}");
+/// Parses a due date relative to `now`.
+///
+/// Accepts `today`, `tomorrow`, a date in the form `YYYY-MM-DD`, or a duration
+/// from now like `3d`. Dates are due at the end of the day, in UTC.
+fn parse_due(input: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let input = input.trim();
+    let date = match input.to_lowercase().as_str() {
+        "today" => Some(now.date_naive()),
+        "tomorrow" => now.date_naive().succ_opt(),
+        _ => NaiveDate::parse_from_str(input, "%Y-%m-%d").ok(),
+    };
 
-            format!("Removed {key:?} from your list")
-        }
+    if let Some(date) = date {
+        let end_of_day = date.and_hms_opt(23, 59, 59).unwrap();
+        return Ok(Utc.from_utc_datetime(&end_of_day));
+    }
 
-        TodoCommand::Finish(key) => {
-            let item = todo_list.items.entry(key.clone()).or_default();
-            item.done = true;
+    let offset = duration::parse(input).map_err(|_| {
+        anyhow!("Expected a due date like `2024-05-01`, `tomorrow`, or `3d`, got {input:?}")
+    })?;
+    chrono::Duration::from_std(offset)
+        .ok()
+        .and_then(|offset| now.checked_add_signed(offset))
+        .with_context(|| format!("Due date {input:?} is too far in the future"))
+}
 
-            info!("Finished TODO item {key:?} for user {user_id}");
+/// Make an item repeat daily, weekly, or monthly.
+#[poise::command(prefix_command, slash_command)]
+pub async fn recur(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_key"] key: String,
+    #[description = "How often the item repeats. Leave out to stop it repeating"] interval: Option<
+        Recurrence,
+    >,
+) -> Result<(), Error> {
+    let command = TodoCommand::Recur {
+        key,
+        recurrence: interval,
+    };
+    run_command(ctx, command).await
+}
 
-            format!("Marked {key:?} as done")
+/// Hide an item from your list for a while, e.g. "2h" or "3d".
+#[poise::command(prefix_command, slash_command)]
+pub async fn snooze(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_key"] key: String,
+    #[description = "How long to hide the item for. Leave out to unsnooze it"]
+    #[rest]
+    duration: Option<String>,
+) -> Result<(), Error> {
+    let until = match duration.map(|duration| parse_snooze(&duration, Utc::now())) {
+        Some(Ok(until)) => Some(until),
+        Some(Err(error)) => {
+            ctx.say(format!("{error}")).await?;
+            return Ok(());
+        }
+        None => None,
+    };
+
+    run_command(ctx, TodoCommand::Snooze { key, until }).await
+}
+
+/// Parses how long to snooze an item for, returning when the snooze ends.
+fn parse_snooze(input: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let duration = duration::parse(input)?;
+    chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|duration| now.checked_add_signed(duration))
+        .with_context(|| format!("Snooze {input:?} is too long"))
+}
+
+/// Set or clear a note with more details about an item.
+#[poise::command(prefix_command, slash_command)]
+pub async fn note(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_key"] key: String,
+    #[rest] text: Option<String>,
+) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::Note { key, text }).await
+}
+
+/// Show the total estimated time of your pending items.
+#[poise::command(prefix_command, slash_command)]
+pub async fn workload(ctx: Context<'_>, category: Option<String>) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::Workload { category }).await
+}
+
+/// Show a summary of how many items are in your list.
+#[poise::command(prefix_command, slash_command)]
+pub async fn stats(ctx: Context<'_>) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::Stats).await
+}
+
+/// Show how many pending and done items you have, optionally in a category.
+#[poise::command(prefix_command, slash_command)]
+pub async fn count(ctx: Context<'_>, category: Option<String>) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::Count { category }).await
+}
+
+/// Show how many items are in each of your categories.
+#[poise::command(prefix_command, slash_command, rename = "by-tag-cloud")]
+pub async fn tag_cloud(ctx: Context<'_>) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::TagCloud).await
+}
+
+/// Choose whether priorities are shown as numbers or colored emoji.
+#[poise::command(prefix_command, slash_command, rename = "priority-style")]
+pub async fn priority_style(
+    ctx: Context<'_>,
+    #[description = "Either \"numeric\" or \"emoji\""] style: String,
+    #[description = "Lowest priority shown as high (emoji style only)"] high: Option<u32>,
+    #[description = "Lowest priority shown as medium (emoji style only)"] medium: Option<u32>,
+) -> Result<(), Error> {
+    let style = match style.to_lowercase().as_str() {
+        "numeric" => PriorityStyle::Numeric,
+        "emoji" => PriorityStyle::Emoji {
+            high: high.unwrap_or(DEFAULT_HIGH_PRIORITY),
+            medium: medium.unwrap_or(DEFAULT_MEDIUM_PRIORITY),
+        },
+        _ => {
+            ctx.say(format!(
+                "Unknown priority style {style:?}, expected \"numeric\" or \"emoji\""
+            ))
+            .await?;
+            return Ok(());
+        }
+    };
+
+    run_command(ctx, TodoCommand::SetPriorityStyle(style)).await
+}
+
+/// Move items to a category when they're marked done, or turn this off.
+#[poise::command(prefix_command, slash_command, rename = "done-category")]
+pub async fn done_category(ctx: Context<'_>, category: Option<String>) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::SetDoneCategory(category)).await
+}
+
+/// Choose whether item keys match exactly or ignoring case.
+#[poise::command(prefix_command, slash_command, rename = "key-matching")]
+pub async fn key_matching(
+    ctx: Context<'_>,
+    #[description = "Either \"exact\" or \"ignore-case\""] mode: String,
+) -> Result<(), Error> {
+    let ignore_case = match mode.to_lowercase().as_str() {
+        "exact" => false,
+        "ignore-case" => true,
+        _ => {
+            ctx.say(format!(
+                "Unknown key matching mode {mode:?}, expected \"exact\" or \"ignore-case\""
+            ))
+            .await?;
+            return Ok(());
+        }
+    };
+
+    run_command(ctx, TodoCommand::SetIgnoreKeyCase(ignore_case)).await
+}
+
+/// Choose whether items lose priority for each week since they were last added.
+#[poise::command(prefix_command, slash_command, rename = "priority-decay")]
+pub async fn priority_decay(
+    ctx: Context<'_>,
+    #[description = "Whether stale items lose priority"] enabled: bool,
+) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::SetPriorityDecay(enabled)).await
+}
+
+/// Get a daily DM with your top items at a UTC time like "09:00", or leave out to turn off.
+#[poise::command(prefix_command, slash_command)]
+pub async fn reminder(ctx: Context<'_>, time: Option<String>) -> Result<(), Error> {
+    let time = match time.as_deref().map(parse_reminder_time).transpose() {
+        Ok(time) => time,
+        Err(error) => {
+            ctx.say(format!("{error}")).await?;
+            return Ok(());
+        }
+    };
+
+    run_command(ctx, TodoCommand::SetReminder(time)).await
+}
+
+/// Parses the time of day for `!todo reminder`, e.g. `09:00`.
+fn parse_reminder_time(input: &str) -> Result<NaiveTime> {
+    let input = input.trim();
+    NaiveTime::parse_from_str(input, "%H:%M")
+        .map_err(|_| anyhow!("Expected a time like `09:00` or `17:30`, got {input:?}"))
+}
+
+/// Replace your list with the items in an uploaded text file.
+#[poise::command(prefix_command, slash_command, rename = "bulk-edit")]
+pub async fn bulk_edit(ctx: Context<'_>, file: serenity::Attachment) -> Result<(), Error> {
+    let contents = file
+        .download()
+        .await
+        .with_context(|| format!("Failed to download attachment {:?}", file.filename))?;
+
+    let Ok(contents) = String::from_utf8(contents) else {
+        ctx.say(format!("{:?} isn't a plain-text file", file.filename))
+            .await?;
+        return Ok(());
+    };
+
+    let items = match parse_bulk_edit(&contents) {
+        Ok(items) => items,
+        Err(errors) => {
+            ctx.say(format!(
+                "Couldn't read {:?}:\n```\n{}\n```",
+                file.filename,
+                errors.join("\n"),
+            ))
+            .await?;
+            return Ok(());
+        }
+    };
+
+    // Replacing the whole list is destructive, so have the user confirm first.
+    let reply = ctx
+        .send(|m| {
+            m.content(format!(
+                "This will replace your list with the {} items in {:?}. Continue?",
+                items.len(),
+                file.filename,
+            ))
+            .components(|c| {
+                c.create_action_row(|row| {
+                    row.create_button(|b| {
+                        b.custom_id("todo.bulk_edit.confirm")
+                            .label("Replace my list")
+                            .style(serenity::ButtonStyle::Danger)
+                    })
+                    .create_button(|b| {
+                        b.custom_id("todo.bulk_edit.cancel")
+                            .label("Cancel")
+                            .style(serenity::ButtonStyle::Secondary)
+                    })
+                })
+            })
+        })
+        .await?;
+
+    let interaction = reply
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(INTERACTION_TIMEOUT)
+        .await;
+
+    let content = match &interaction {
+        Some(press) if press.data.custom_id == "todo.bulk_edit.confirm" => "Replacing your list...",
+        Some(_) => "Bulk edit cancelled",
+        None => "Bulk edit timed out",
+    };
+    reply
+        .edit(ctx, |m| m.content(content).components(|c| c))
+        .await?;
+
+    match interaction {
+        Some(press) if press.data.custom_id == "todo.bulk_edit.confirm" => {
+            press.defer(ctx).await?;
+            run_command(ctx, TodoCommand::Replace(items)).await
+        }
+
+        Some(press) => {
+            press.defer(ctx).await?;
+            Ok(())
+        }
+
+        None => Ok(()),
+    }
+}
+
+/// Download your list as JSON.
+#[poise::command(prefix_command, slash_command)]
+pub async fn export(ctx: Context<'_>) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::Export).await
+}
+
+/// Add items from `!todo export` to your list, or replace your list with them.
+#[poise::command(prefix_command, slash_command)]
+pub async fn import(
+    ctx: Context<'_>,
+    #[description = "Replace your list instead of adding to it"] replace: Option<bool>,
+    #[description = "A file from `/todo export`"] file: Option<serenity::Attachment>,
+    #[description = "The JSON from `/todo export`"]
+    #[rest]
+    json: Option<String>,
+) -> Result<(), Error> {
+    // Prefix commands pass `--replace` at the start of the text instead.
+    let mut json = json.unwrap_or_default();
+    let mut replace = replace.unwrap_or(false);
+    if let Some(rest) = json.trim_start().strip_prefix("--replace") {
+        replace = true;
+        json = rest.into();
+    }
+
+    if let Some(file) = file {
+        let contents = file
+            .download()
+            .await
+            .with_context(|| format!("Failed to download attachment {:?}", file.filename))?;
+
+        let Ok(contents) = String::from_utf8(contents) else {
+            ctx.say(format!("{:?} isn't a JSON file", file.filename))
+                .await?;
+            return Ok(());
+        };
+        json = contents;
+    }
+
+    if json.trim().is_empty() {
+        ctx.say("Paste the JSON from `!todo export`, or attach it as a file")
+            .await?;
+        return Ok(());
+    }
+
+    let items = match parse_import(&json) {
+        Ok(items) => items,
+        Err(error) => {
+            ctx.say(format!("{error}")).await?;
+            return Ok(());
+        }
+    };
+
+    let command = if replace {
+        TodoCommand::Replace(items)
+    } else {
+        TodoCommand::Merge(items)
+    };
+    run_command(ctx, command).await
+}
+
+/// Parses the JSON for `!todo import`, as produced by `!todo export`. The JSON
+/// may be wrapped in a code block, e.g. if it was copied from an export message.
+fn parse_import(json: &str) -> Result<HashMap<String, TodoItem>> {
+    let mut json = json.trim();
+    if let Some(inner) = json.strip_prefix("```").and_then(|j| j.strip_suffix("```")) {
+        json = inner.strip_prefix("json").unwrap_or(inner);
+    }
+
+    serde_json::from_str(json).map_err(|e| anyhow!("Couldn't read the imported list: {e}"))
+}
+
+/// Restore your list from before your last bulk edit.
+#[poise::command(prefix_command, slash_command, rename = "bulk-undo")]
+pub async fn bulk_undo(ctx: Context<'_>) -> Result<(), Error> {
+    run_command(ctx, TodoCommand::RestoreBackup).await
+}
+
+/// Parses the contents of a `!todo bulk-edit` file into a set of TODO items.
+///
+/// Returns a list of line-level errors if any line is malformed.
+fn parse_bulk_edit(contents: &str) -> Result<HashMap<String, TodoItem>, Vec<String>> {
+    let mut items = HashMap::new();
+    let mut errors = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match parse_bulk_edit_line(line) {
+            Ok((key, item)) => {
+                if items.insert(key.clone(), item).is_some() {
+                    errors.push(format!("Line {line_number}: Duplicate key {key:?}"));
+                }
+            }
+
+            Err(error) => errors.push(format!("Line {line_number}: {error}")),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(items)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Parses a single `priority<TAB>done<TAB>category<TAB>key` line of a
+/// `!todo bulk-edit` file.
+fn parse_bulk_edit_line(line: &str) -> Result<(String, TodoItem), String> {
+    let fields = line.split('\t').collect::<Vec<_>>();
+    let [priority, done, category, key] = fields[..] else {
+        return Err(format!(
+            "Expected 4 tab-separated fields (priority, done, category, key), found {}",
+            fields.len(),
+        ));
+    };
+
+    let priority = priority
+        .parse()
+        .map_err(|_| format!("Invalid priority {priority:?}, expected a whole number"))?;
+
+    let done = match done {
+        "true" => true,
+        "false" => false,
+        _ => {
+            return Err(format!(
+                "Invalid done value {done:?}, expected true or false"
+            ))
+        }
+    };
+
+    let category = match category {
+        "" => None,
+        category => Some(category.to_string()),
+    };
+
+    if key.is_empty() {
+        return Err("Item key can't be empty".into());
+    }
+
+    let item = TodoItem {
+        priority,
+        done,
+        category,
+        ..Default::default()
+    };
+    Ok((key.to_string(), item))
+}
+
+/// Loads the user's TODO list from the database, or an empty list if they don't
+/// have one yet.
+async fn load_list(ctx: Context<'_>) -> Result<TodoList> {
+    let user_id = ctx.author().id;
+    let store = DbListStore::new(ctx.data());
+    let todo_list = find_cached(&store, &ctx.data().todo_cache, user_id, Instant::now())
+        .await?
+        .unwrap_or_else(|| TodoList::new(user_id));
+    Ok(todo_list)
+}
+
+/// The in-memory cache of users' TODO lists, so that each command doesn't need to
+/// load the list from the database.
+pub type ListCache = TtlCache<serenity::UserId, TodoList>;
+
+/// Where users' TODO lists are stored, i.e. [`DbListStore`] outside of tests.
+trait ListStore {
+    async fn find(&self, user_id: serenity::UserId) -> Result<Option<TodoList>>;
+    async fn insert(&self, todo_list: &TodoList) -> Result<()>;
+    async fn update(&self, todo_list: &TodoList) -> Result<()>;
+}
+
+/// The database collection of TODO lists.
+struct DbListStore {
+    collection: mongodb::Collection<TodoList>,
+    retries: u32,
+}
+
+impl DbListStore {
+    fn new(data: &Data) -> Self {
+        Self {
+            collection: data.db.collection("user_todos"),
+            retries: data.db_retries,
+        }
+    }
+}
+
+impl ListStore for DbListStore {
+    async fn find(&self, user_id: serenity::UserId) -> Result<Option<TodoList>> {
+        let query = list_query(user_id);
+        let todo_list = with_retry(self.retries, BASE_DELAY, || {
+            self.collection.find_one(query.clone(), None)
+        })
+        .await
+        .with_context(|| format!("Failed to get TODO list for user {user_id}"))?;
+        debug!("Loaded TODO list for user {user_id}: {todo_list:#?}");
+        Ok(todo_list)
+    }
+
+    // Inserts aren't retried, since retrying one that reached the database before
+    // failing would duplicate the list.
+    async fn insert(&self, todo_list: &TodoList) -> Result<()> {
+        let user_id = todo_list.user_id;
+        self.collection
+            .insert_one(todo_list, None)
+            .await
+            .with_context(|| format!("Failed to insert TODO list for user {user_id}"))?;
+        Ok(())
+    }
+
+    async fn update(&self, todo_list: &TodoList) -> Result<()> {
+        let user_id = todo_list.user_id;
+        let query = list_query(user_id);
+        let update = list_update(todo_list)
+            .with_context(|| format!("Failed to serialize TODO list for user {user_id}"))?;
+        with_retry(self.retries, BASE_DELAY, || {
+            self.collection
+                .update_one(query.clone(), update.clone(), None)
+        })
+        .await
+        .with_context(|| format!("Failed to update TODO items for user {user_id}"))?;
+        Ok(())
+    }
+}
+
+/// Builds the update that saves a user's TODO list over the stored copy.
+///
+/// Fails if the list can't be stored, e.g. because a value is too large for BSON.
+fn list_update(todo_list: &TodoList) -> Result<Document> {
+    Ok(doc! {
+        "$set": {
+            "items": bson::to_bson(&todo_list.items)?,
+            "backup": bson::to_bson(&todo_list.backup)?,
+            "priority_style": bson::to_bson(&todo_list.priority_style)?,
+            "done_category": bson::to_bson(&todo_list.done_category)?,
+            "reminder": bson::to_bson(&todo_list.reminder)?,
+            "ignore_key_case": todo_list.ignore_key_case,
+            "priority_decay": todo_list.priority_decay,
+        },
+    })
+}
+
+/// Gets a user's TODO list from the cache, falling back to the store if it isn't
+/// cached. Returns `None` if the user doesn't have a list yet.
+async fn find_cached(
+    store: &impl ListStore,
+    cache: &ListCache,
+    user_id: serenity::UserId,
+    now: Instant,
+) -> Result<Option<TodoList>> {
+    if let Some(todo_list) = cache.get(&user_id, now) {
+        return Ok(Some(todo_list));
+    }
+
+    let todo_list = store.find(user_id).await?;
+    if let Some(todo_list) = &todo_list {
+        cache.insert(user_id, todo_list.clone(), now);
+    }
+    Ok(todo_list)
+}
+
+/// Gets a user's TODO list like [`find_cached`], creating a new list for users
+/// that don't have one yet.
+///
+/// Returns the list and whether it was just created.
+async fn load_cached(
+    store: &impl ListStore,
+    cache: &ListCache,
+    user_id: serenity::UserId,
+    now: Instant,
+) -> Result<(TodoList, bool)> {
+    if let Some(todo_list) = find_cached(store, cache, user_id, now).await? {
+        return Ok((todo_list, false));
+    }
+
+    info!("First time usage of `!todo` for user {user_id}, inserting empty list");
+
+    let new = TodoList::new(user_id);
+    store.insert(&new).await?;
+    cache.insert(user_id, new.clone(), now);
+    Ok((new, true))
+}
+
+/// Saves a user's TODO list, updating the cached copy to match.
+///
+/// If saving fails the cached copy is dropped, since it isn't known whether the
+/// change reached the store.
+async fn save_cached(
+    store: &impl ListStore,
+    cache: &ListCache,
+    todo_list: &TodoList,
+    now: Instant,
+) -> Result<()> {
+    let user_id = todo_list.user_id;
+    match store.update(todo_list).await {
+        Ok(()) => {
+            cache.insert(user_id, todo_list.clone(), now);
+            Ok(())
+        }
+        Err(e) => {
+            cache.remove(&user_id);
+            Err(e)
+        }
+    }
+}
+
+/// Suggests keys from the user's list for slash command arguments.
+async fn autocomplete_key(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    match load_list(ctx).await {
+        Ok(todo_list) => matching_keys(&todo_list, partial),
+        Err(error) => {
+            error!("Failed to load TODO list for autocomplete: {error:?}");
+            Vec::new()
+        }
+    }
+}
+
+/// Finds the keys containing `partial`, ignoring case, highest priority first.
+fn matching_keys(todo_list: &TodoList, partial: &str) -> Vec<String> {
+    let partial = partial.to_lowercase();
+    todo_list
+        .sorted_keys()
+        .into_iter()
+        // Discord rejects the whole response if any suggestion is too long.
+        .filter(|key| key.chars().count() <= MAX_AUTOCOMPLETE_LENGTH)
+        .filter(|key| key.to_lowercase().contains(&partial))
+        .take(MAX_AUTOCOMPLETE_CHOICES)
+        .collect()
+}
+
+/// Loads the user's TODO list state from the database and then process the
+/// command, unless the user is on cooldown.
+async fn run_command(ctx: Context<'_>, command: TodoCommand) -> Result<()> {
+    run_command_with_visibility(ctx, command, false).await
+}
+
+/// Like [`run_command`], but if `ephemeral` is set the response is only shown to
+/// the user. This only works for slash commands.
+async fn run_command_with_visibility(
+    ctx: Context<'_>,
+    command: TodoCommand,
+    ephemeral: bool,
+) -> Result<()> {
+    if !command.is_read_only() && on_cooldown(ctx, true).await? {
+        return Ok(());
+    }
+
+    apply_command(ctx, command, ephemeral).await?;
+    Ok(())
+}
+
+/// Throttles commands that write to the database so that users can't spam it.
+///
+/// If the user is on cooldown, tells them how long to wait and returns `true`, in
+/// which case the command shouldn't run. Otherwise starts a new cooldown if `start`
+/// is set. Commands that only open a menu leave `start` unset, and check again
+/// with it set before applying what was selected.
+async fn on_cooldown(ctx: Context<'_>, start: bool) -> Result<bool> {
+    let remaining = {
+        let mut last_used = ctx.data().todo_cooldowns.lock().unwrap();
+        let user_id = ctx.author().id;
+        let now = Instant::now();
+        let cooldown = ctx.data().todo_cooldown;
+        if start {
+            start_cooldown(&mut last_used, user_id, now, cooldown)
+        } else {
+            cooldown_remaining(last_used.get(&user_id), now, cooldown)
+        }
+    };
+
+    let Some(remaining) = remaining else {
+        return Ok(false);
+    };
+
+    // Round up so that we never tell the user to try again too early.
+    let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    ctx.send(|m| {
+        m.content(format!("Slow down — try again in {secs}s"))
+            .ephemeral(true)
+    })
+    .await?;
+    Ok(true)
+}
+
+/// Like [`run_command_with_visibility`], but without checking the cooldown.
+///
+/// This is for commands that check the cooldown themselves, such as menus that
+/// check it for each selection, and for follow-up interactions like the undo
+/// button that shouldn't be rejected just because the command itself started the
+/// cooldown.
+///
+/// Returns whether the command changed the user's list and the change was saved.
+async fn apply_command(ctx: Context<'_>, command: TodoCommand, ephemeral: bool) -> Result<bool> {
+    let user_id = ctx.author().id;
+
+    // Load the user's TODO list, creating it if this is the first time the user is
+    // using the `!todo` command.
+    let store = DbListStore::new(ctx.data());
+    let cache = &ctx.data().todo_cache;
+    let (mut user_list, first_use) = load_cached(&store, cache, user_id, Instant::now()).await?;
+
+    // Handle the message, updating `todo_state` and getting the response message.
+    let now = Utc::now();
+    let max_items = ctx.data().todo_max_items;
+    let (response, changed) = handle_command_tracked(
+        command.clone(),
+        &mut user_list,
+        ctx.author(),
+        max_items,
+        now,
+    );
+    let response = with_welcome(response, first_use, &ctx.data().todo_welcome_message);
+
+    // Write the updated TODO state to the database, unless the command didn't change
+    // anything. If that fails the response would be misleading, so let the user know
+    // their change was lost instead.
+    if changed {
+        let result = save_cached(&store, cache, &user_list, Instant::now()).await;
+        if let Err(e) = result {
+            error!("Failed to update TODO items for user {user_id}: {:?}", e);
+            ctx.say("Couldn't save your list, try again").await?;
+            return Ok(false);
+        }
+    }
+
+    record_command(&ctx.data().db, user_id, &command, changed, max_items, now).await;
+
+    // Send the response to the channel where the command was sent.
+    let mut printed = None;
+    let result = match command {
+        TodoCommand::Print {
+            category,
+            sort,
+            all,
+        } if ctx.data().todo_use_embeds => {
+            let embed = list_embed(
+                &user_list,
+                &ctx.author().name,
+                category.as_deref(),
+                sort,
+                all,
+            );
+            ctx.send(|m| {
+                if first_use {
+                    m.content(&ctx.data().todo_welcome_message);
+                }
+                m.embed(|e| {
+                    *e = embed;
+                    e
+                })
+                .ephemeral(ephemeral)
+            })
+            .await
+            .map(|_| ())
+        }
+
+        // Long exports are sent as a file instead, since they don't fit in a message.
+        TodoCommand::Export if response.len() > MAX_MESSAGE_LENGTH => {
+            let json = export_json(&user_list);
+            ctx.send(|m| {
+                m.content("Your list is too long for a message, so here it is as a file")
+                    .attachment(serenity::AttachmentType::Bytes {
+                        data: Cow::Owned(json.into_bytes()),
+                        filename: EXPORT_FILENAME.into(),
+                    })
+            })
+            .await
+            .map(|_| ())
+        }
+
+        _ if ephemeral => ctx
+            .send(|m| m.content(response).ephemeral(true))
+            .await
+            .map(|_| ()),
+
+        // Remember which items were printed, so that they can be marked done with
+        // reactions.
+        TodoCommand::Print {
+            category,
+            sort,
+            all,
+        } => ctx
+            .channel_id()
+            .say(ctx.http(), response)
+            .await
+            .map(|message| {
+                let keys = user_list.print_keys(category.as_deref(), sort, all, Utc::now());
+                printed = Some((message, keys));
+            }),
+
+        _ => ctx.channel_id().say(ctx.http(), response).await.map(|_| ()),
+    };
+    result.context("Failed to send TODO response")?;
+
+    // Watch for reactions in the background, so that the command doesn't have to
+    // wait for them to time out.
+    if let Some((message, keys)) = printed {
+        tokio::spawn(collect_done_reactions(
+            ctx.serenity_context().clone(),
+            message,
+            keys,
+            ctx.author().clone(),
+            ListHandle::new(ctx.data()),
+        ));
+    }
+
+    Ok(changed)
+}
+
+/// Records a command that ran against a user's list in the command log and the
+/// audit trail, if it changed the list.
+///
+/// The list has already been saved at this point, so failures are logged rather
+/// than reported to the user.
+async fn record_command(
+    db: &Database,
+    user_id: serenity::UserId,
+    command: &TodoCommand,
+    changed: bool,
+    max_items: usize,
+    now: DateTime<Utc>,
+) {
+    // Append the command to the user's command log so that the list state can be
+    // reconstructed later with `replay`. Commands that didn't change the list
+    // don't affect the replayed state, so they're left out.
+    if changed {
+        let entry = CommandLogEntry {
+            user_id,
+            timestamp: now,
+            max_items,
+            command: command.clone(),
+        };
+        let result = db
+            .collection("user_todo_commands")
+            .insert_one(entry, None)
+            .await;
+        if let Err(e) = result {
+            error!("Failed to log TODO command for user {user_id}: {:?}", e);
+        }
+    }
+
+    // Record changes in the audit trail, which is only used for debugging.
+    if let Some(entry) = AuditEntry::new(user_id, command, changed, now) {
+        let result = db
+            .collection(AUDIT_COLLECTION_NAME)
+            .insert_one(entry, None)
+            .await;
+        if let Err(e) = result {
+            error!(
+                "Failed to write TODO audit entry for user {user_id}: {:?}",
+                e
+            );
+        }
+    }
+}
+
+/// The parts of [`Data`] needed to change a user's list from a background task,
+/// which can't borrow [`Data`] itself.
+struct ListHandle {
+    store: DbListStore,
+    cache: Arc<ListCache>,
+    db: Database,
+    max_items: usize,
+}
+
+impl ListHandle {
+    fn new(data: &Data) -> Self {
+        Self {
+            store: DbListStore::new(data),
+            cache: data.todo_cache.clone(),
+            db: data.db.clone(),
+            max_items: data.todo_max_items,
+        }
+    }
+}
+
+/// Adds numbered reactions to a printed list, then marks items done as the list's
+/// owner (`author`) clicks them, or not done if they remove the reaction again.
+///
+/// `keys` are the printed keys in display order.
+async fn collect_done_reactions(
+    ctx: serenity::Context,
+    message: serenity::Message,
+    keys: Vec<String>,
+    author: User,
+    list: ListHandle,
+) {
+    for emoji in DONE_REACTIONS.iter().take(keys.len()) {
+        let reaction = serenity::ReactionType::Unicode(emoji.to_string());
+        if let Err(e) = message.react(&ctx, reaction).await {
+            // The bot may not be allowed to react in this channel, which shouldn't
+            // stop the list from being used.
+            info!("Couldn't add TODO reactions to message {}: {e}", message.id);
+            return;
+        }
+    }
+
+    // Only the owner's reactions count, which also skips the bot's own reactions.
+    let mut reactions = message
+        .await_reactions(&ctx)
+        .author_id(author.id)
+        .added(true)
+        .removed(true)
+        .timeout(INTERACTION_TIMEOUT)
+        .build();
+    while let Some(action) = reactions.next().await {
+        let command = match &*action {
+            serenity::ReactionAction::Added(reaction) => {
+                reaction_command(&reaction.emoji, true, &keys)
+            }
+            serenity::ReactionAction::Removed(reaction) => {
+                reaction_command(&reaction.emoji, false, &keys)
+            }
+        };
+        let Some(command) = command else {
+            continue;
+        };
+
+        let now = Utc::now();
+        let result = apply_reaction(
+            &list.store,
+            &list.cache,
+            &author,
+            command.clone(),
+            list.max_items,
+            now,
+        )
+        .await;
+        match result {
+            Ok(changed) => {
+                record_command(&list.db, author.id, &command, changed, list.max_items, now).await;
+            }
+            Err(e) => error!(
+                "Failed to apply TODO reaction for user {}: {e:?}",
+                author.id
+            ),
+        }
+    }
+}
+
+/// Applies the command for a reaction on a printed list to the list's owner's
+/// list. Returns whether the list changed.
+///
+/// Reactions are follow-ups to the command that printed the list, so they skip
+/// the cooldown, and the reaction itself is the only response.
+async fn apply_reaction(
+    store: &impl ListStore,
+    cache: &ListCache,
+    author: &User,
+    command: TodoCommand,
+    max_items: usize,
+    now: DateTime<Utc>,
+) -> Result<bool> {
+    let (mut todo_list, _) = load_cached(store, cache, author.id, Instant::now()).await?;
+    let (_, changed) = handle_command_tracked(command, &mut todo_list, author, max_items, now);
+    if changed {
+        save_cached(store, cache, &todo_list, Instant::now()).await?;
+    }
+    Ok(changed)
+}
+
+/// Builds the command for a reaction on a printed list. Adding a numbered
+/// reaction marks the item at that position done, and removing it marks the item
+/// not done.
+///
+/// `keys` are the printed keys in display order.
+fn reaction_command(
+    emoji: &serenity::ReactionType,
+    added: bool,
+    keys: &[String],
+) -> Option<TodoCommand> {
+    let serenity::ReactionType::Unicode(emoji) = emoji else {
+        return None;
+    };
+    let index = DONE_REACTIONS
+        .iter()
+        .position(|reaction| reaction == emoji)?;
+    let key = keys.get(index)?.clone();
+    if added {
+        Some(TodoCommand::Finish(key))
+    } else {
+        Some(TodoCommand::Unfinish(key))
+    }
+}
+
+/// Serializes every item in the list, including removed items, to pretty-printed
+/// JSON for `!todo export`. Items are sorted by key so that exports are stable.
+fn export_json(todo_list: &TodoList) -> String {
+    let items = todo_list.items.iter().collect::<BTreeMap<_, _>>();
+    serde_json::to_string_pretty(&items).unwrap()
+}
+
+/// Starts a cooldown for `user_id` at `now`, unless they're still on one from
+/// their last command that changed their list, in which case it returns how much
+/// longer they have to wait.
+///
+/// `last_used` holds when each user last started a cooldown.
+fn start_cooldown(
+    last_used: &mut HashMap<serenity::UserId, Instant>,
+    user_id: serenity::UserId,
+    now: Instant,
+    cooldown: Duration,
+) -> Option<Duration> {
+    let remaining = cooldown_remaining(last_used.get(&user_id), now, cooldown);
+    if remaining.is_none() {
+        last_used.insert(user_id, now);
+    }
+    remaining
+}
+
+/// Returns how much longer a user has to wait before running another command
+/// that changes their list, or `None` if they're not on cooldown.
+///
+/// `last_used` is when the user last ran such a command, if ever.
+fn cooldown_remaining(
+    last_used: Option<&Instant>,
+    now: Instant,
+    cooldown: Duration,
+) -> Option<Duration> {
+    let elapsed = now.saturating_duration_since(*last_used?);
+    cooldown
+        .checked_sub(elapsed)
+        .filter(|remaining| !remaining.is_zero())
+}
+
+/// Sends users their daily `!todo reminder` DMs.
+///
+/// Checks for due reminders every [`REMINDER_CHECK_INTERVAL`] and never returns,
+/// so this should be spawned as a background task when the bot starts. `cache`
+/// should be the same cache as [`Data::todo_cache`], so that lists changed here
+/// aren't overwritten by stale cached copies.
+pub async fn send_reminders(
+    http: Arc<serenity::Http>,
+    db: Database,
+    cache: Arc<ListCache>,
+    retries: u32,
+) {
+    let mut interval = tokio::time::interval(REMINDER_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = send_due_reminders(&http, &db, &cache, retries).await {
+            error!("Error sending TODO reminders: {:?}", e);
+        }
+    }
+}
+
+/// Sends a reminder to every user whose reminder is due right now.
+async fn send_due_reminders(
+    http: &serenity::Http,
+    db: &Database,
+    cache: &ListCache,
+    retries: u32,
+) -> Result<()> {
+    let collection = db.collection::<TodoList>("user_todos");
+    let mut lists = collection
+        .find(doc! { "reminder": { "$ne": null } }, None)
+        .await
+        .context("Failed to find TODO lists with reminders")?;
+
+    let now = Utc::now();
+    while let Some(todo_list) = lists.try_next().await? {
+        let user_id = todo_list.user_id;
+        let Some(reminder) = &todo_list.reminder else {
+            continue;
+        };
+        if !is_reminder_due(reminder, now) {
+            continue;
+        }
+
+        // Mark the reminder as sent even if there's nothing to send, so that users
+        // with empty lists aren't checked again until tomorrow. If that fails the
+        // reminder is left for the next check rather than risk sending it twice,
+        // and the other users still get theirs.
+        let update = doc! { "$set": { "reminder.last_sent": bson::to_bson(&now).unwrap() } };
+        let result = with_retry(retries, BASE_DELAY, || {
+            collection.update_one(list_query(user_id), update.clone(), None)
+        })
+        .await;
+        if let Err(e) = result {
+            error!("Failed to update TODO reminder for user {user_id}: {:?}", e);
+            continue;
+        }
+
+        // Update the cached copy of the list too, rather than dropping it, so that a
+        // command saving the cached copy doesn't undo the change.
+        cache.update(&user_id, |todo_list| mark_reminder_sent(todo_list, now));
+
+        let Some(message) = reminder_message(&todo_list) else {
+            continue;
+        };
+
+        let result = match user_id.create_dm_channel(http).await {
+            Ok(channel) => channel.say(http, message).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => info!("Sent TODO reminder to user {user_id}"),
+            Err(e) => error!("Error sending TODO reminder to user {user_id}: {:?}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Records that the list's reminder was sent at `now`, if it has one.
+fn mark_reminder_sent(todo_list: &mut TodoList, now: DateTime<Utc>) {
+    if let Some(reminder) = &mut todo_list.reminder {
+        reminder.last_sent = Some(now);
+    }
+}
+
+/// Checks if a reminder should be sent at `now`, i.e. today's reminder time has
+/// passed and the reminder hasn't been sent since.
+fn is_reminder_due(reminder: &Reminder, now: DateTime<Utc>) -> bool {
+    let scheduled = Utc.from_utc_datetime(&now.date_naive().and_time(reminder.time));
+    if now < scheduled {
+        return false;
+    }
+
+    match reminder.last_sent {
+        Some(last_sent) => last_sent < scheduled,
+        None => true,
+    }
+}
+
+/// Builds the reminder DM for a list, listing the highest priority pending items.
+/// Returns `None` if there are no pending items to remind the user about.
+fn reminder_message(todo_list: &TodoList) -> Option<String> {
+    let keys = todo_list
+        .sorted_keys()
+        .into_iter()
+        .filter(|key| !todo_list.items[key].done)
+        .take(REMINDER_ITEMS)
+        .collect::<Vec<_>>();
+    if keys.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "Here are your top TODO items for today:\n{}",
+        list_table(todo_list, &keys, true),
+    ))
+}
+
+/// Builds the code block used to display the items with the given keys, in
+/// order, when printing a TODO list.
+fn list_table(todo_list: &TodoList, keys: &[String], show_category: bool) -> String {
+    // Determine how wide the priority output needs to be displayed by finding the
+    // highest priority and calculating how many digits it will be.
+    let max_priority = todo_list
+        .active_items()
+        .map(|item| item.priority)
+        .max()
+        .unwrap_or_default();
+    let priority_width = priority_width(max_priority);
+
+    let now = Utc::now();
+    let mut table = "```\n".to_string();
+    for key in keys {
+        let item = todo_list.display_item(key, now);
+        let line = item.display_line(
+            key,
+            show_category,
+            todo_list.priority_style,
+            priority_width,
+            now,
+        );
+        writeln!(&mut table, "{line}").unwrap();
+    }
+    table.push_str("```\n");
+    table
+}
+
+/// Calculates how many characters numeric priorities are padded to so that they
+/// line up, i.e. the number of digits in the highest priority.
+///
+/// NOTE: This counts the digits directly rather than using `log10`, which gives
+/// a width of 0 for a priority of 0 and loses precision for very large explicit
+/// priorities.
+fn priority_width(max_priority: u32) -> usize {
+    max_priority.to_string().len().max(1)
+}
+
+/// Builds an embed displaying the user's TODO list, as an alternative to the
+/// text output of [`TodoCommand::Print`].
+///
+/// Each category is shown as a separate field, and done items are struck
+/// through. The embed's color shows the priority of the top pending item.
+fn list_embed(
+    todo_list: &TodoList,
+    user_name: &str,
+    category: Option<&str>,
+    sort: SortMode,
+    all: bool,
+) -> serenity::CreateEmbed {
+    // Group the items by category, keeping the categories in the order of their
+    // first item.
+    let now = Utc::now();
+    let mut fields: Vec<(Option<&str>, String)> = Vec::new();
+    let mut max_priority = None;
+    for key in todo_list.print_keys(category, sort, all, now) {
+        let item = todo_list.display_item(&key, now);
+        if !item.done {
+            max_priority = max_priority.max(Some(item.priority));
+        }
+
+        let priority = item.priority_label(todo_list.priority_style, 0);
+        let details = item.details_suffix(now);
+        let line = if item.done {
+            format!("~~{priority} {key}{details}~~\n")
+        } else {
+            format!("{priority} {key}{details}\n")
+        };
+
+        // Decay doesn't change the category, so it can be borrowed from the list.
+        let item_category = todo_list.items[&key].category.as_deref();
+        let index = match fields.iter().position(|(name, _)| *name == item_category) {
+            Some(index) => index,
+            None => {
+                fields.push((item_category, String::new()));
+                fields.len() - 1
+            }
+        };
+
+        // Drop any items that don't fit in the field rather than failing to send the
+        // whole list.
+        let value = &mut fields[index].1;
+        if value.len() + line.len() <= MAX_EMBED_FIELD_LENGTH {
+            value.push_str(&line);
+        }
+    }
+
+    let mut embed = serenity::CreateEmbed::default();
+    match category {
+        Some(category) => embed.title(format!(
+            "TODO list for {user_name} in category [{category}]"
+        )),
+        None => embed.title(format!("TODO list for {user_name}")),
+    };
+    if let Some(max_priority) = max_priority {
+        embed.color(priority_color(max_priority));
+    }
+    embed.fields(
+        fields
+            .into_iter()
+            .take(MAX_EMBED_FIELDS)
+            .map(|(name, value)| (name.unwrap_or("Uncategorized"), value, false)),
+    );
+    embed
+}
+
+/// Picks the sidebar color for an embedded list from the priority of its top
+/// pending item: red for high priorities, yellow for medium ones, and green
+/// otherwise.
+fn priority_color(max_priority: u32) -> serenity::Colour {
+    if max_priority >= HIGH_PRIORITY_COLOR_THRESHOLD {
+        serenity::Colour::RED
+    } else if max_priority >= MEDIUM_PRIORITY_COLOR_THRESHOLD {
+        serenity::Colour::GOLD
+    } else {
+        serenity::Colour::DARK_GREEN
+    }
+}
+
+/// Prepends the welcome message to the response for a user's first `!todo`
+/// command, i.e. when their list was just created.
+fn with_welcome(response: String, first_use: bool, welcome_message: &str) -> String {
+    if first_use {
+        format!("{welcome_message}\n\n{response}")
+    } else {
+        response
+    }
+}
+
+/// Builds the query for finding a user's TODO list document.
+///
+/// Lists are keyed purely by user ID so that the same list is used regardless of
+/// which server (or DM) the command is sent from.
+fn list_query(user_id: serenity::UserId) -> Document {
+    doc! { "user_id": user_id.to_string() }
+}
+
+/// A TODO list for a single user.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TodoList {
+    user_id: serenity::UserId,
+
+    /// The items in the user's list. The key is the item key, and the value is the
+    /// item state.
+    items: HashMap<String, TodoItem>,
+
+    /// A snapshot of the items from before the last `!todo bulk-edit`, so that it
+    /// can be undone.
+    #[serde(default)]
+    backup: Option<HashMap<String, TodoItem>>,
+
+    /// How the user wants item priorities to be displayed.
+    #[serde(default)]
+    priority_style: PriorityStyle,
+
+    /// The category items are moved to when they're marked done, if any.
+    #[serde(default)]
+    done_category: Option<String>,
+
+    /// The user's daily reminder, if they've opted into one.
+    #[serde(default)]
+    reminder: Option<Reminder>,
+
+    /// Whether keys given to commands match existing items ignoring case and
+    /// surrounding whitespace. See [`TodoList::resolve_key`].
+    #[serde(default)]
+    ignore_key_case: bool,
+
+    /// Whether items lose priority as they go stale. See [`effective_priority`].
+    #[serde(default)]
+    priority_decay: bool,
+}
+
+impl TodoList {
+    fn new(user_id: serenity::UserId) -> Self {
+        TodoList {
+            user_id,
+            items: Default::default(),
+            backup: None,
+            priority_style: PriorityStyle::Numeric,
+            done_category: None,
+            reminder: None,
+            ignore_key_case: false,
+            priority_decay: false,
+        }
+    }
+
+    /// Finds the key of the existing item that `key` refers to.
+    ///
+    /// If the user has turned on `ignore_key_case`, keys match ignoring case and
+    /// surrounding whitespace, and the existing item's key is returned so that its
+    /// original casing is kept. Otherwise, or if there's no matching item, `key` is
+    /// returned unchanged (apart from trimming whitespace in the first case).
+    fn resolve_key(&self, key: String) -> String {
+        if !self.ignore_key_case {
+            return key;
+        }
+
+        let normalized = normalize_key(&key);
+        self.items
+            .iter()
+            .filter(|(_, item)| !item.archived)
+            .map(|(existing, _)| existing)
+            .find(|existing| normalize_key(existing) == normalized)
+            .cloned()
+            .unwrap_or_else(|| key.trim().to_string())
+    }
+
+    /// Returns the item with the given key, unless it's been removed.
+    fn get(&self, key: &str) -> Option<&TodoItem> {
+        self.items.get(key).filter(|item| !item.archived)
+    }
+
+    /// Returns the item with the given key for editing, unless it's been removed.
+    fn get_mut(&mut self, key: &str) -> Option<&mut TodoItem> {
+        self.items.get_mut(key).filter(|item| !item.archived)
+    }
+
+    /// Returns the items that haven't been removed, in no particular order.
+    fn active_items(&self) -> impl Iterator<Item = &TodoItem> {
+        self.items.values().filter(|item| !item.archived)
+    }
+
+    /// Returns the item keys in display order, i.e. highest priority first. Items
+    /// with the same priority are ordered by key.
+    fn sorted_keys(&self) -> Vec<String> {
+        let mut keys = self
+            .items
+            .iter()
+            .filter(|(_, item)| !item.archived)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        keys.sort_by(|a, b| {
+            let (a_priority, b_priority) = (self.items[a].priority, self.items[b].priority);
+            b_priority.cmp(&a_priority).then_with(|| a.cmp(b))
+        });
+        keys
+    }
+
+    /// Returns the keys of the items to print for `!todo show`, in the order given
+    /// by `sort`, optionally only including the items in `category`.
+    ///
+    /// Items snoozed past `now` are left out unless `all` is set.
+    fn print_keys(
+        &self,
+        category: Option<&str>,
+        sort: SortMode,
+        all: bool,
+        now: DateTime<Utc>,
+    ) -> Vec<String> {
+        let mut keys = self.sorted_keys();
+        if category.is_some() {
+            keys.retain(|key| self.items[key].category.as_deref() == category);
+        }
+        if !all {
+            keys.retain(|key| !self.items[key].is_snoozed(now));
+        }
+        if self.priority_decay {
+            keys.sort_by(|a, b| {
+                let a_priority = effective_priority(&self.items[a], now);
+                let b_priority = effective_priority(&self.items[b], now);
+                b_priority.cmp(&a_priority).then_with(|| a.cmp(b))
+            });
+        }
+
+        // When sorting by due date the sort is stable, so items with the same due date
+        // (or no due date) stay in priority order.
+        if sort == SortMode::Due {
+            keys.sort_by_key(|key| {
+                let due = self.items[key].due;
+                (due.is_none(), due)
+            });
+        }
+
+        keys
+    }
+
+    /// Gets an item as it's displayed in the list, i.e. with its decayed priority if
+    /// the user has turned on priority decay.
+    fn display_item(&self, key: &str, now: DateTime<Utc>) -> Cow<'_, TodoItem> {
+        let mut item = Cow::Borrowed(&self.items[key]);
+        if self.priority_decay {
+            item.to_mut().priority = effective_priority(&item, now);
+        }
+        item
+    }
+
+    /// Returns the keys of pending items that don't have a category yet, in display
+    /// order.
+    fn inbox_keys(&self) -> Vec<String> {
+        let mut keys = self.sorted_keys();
+        keys.retain(|key| {
+            let item = &self.items[key];
+            !item.done && item.category.is_none()
+        });
+        keys
+    }
+
+    /// Returns the categories in use in the list, sorted by name.
+    fn categories(&self) -> Vec<&str> {
+        let mut categories = self
+            .active_items()
+            .filter_map(|item| item.category.as_deref())
+            .collect::<Vec<_>>();
+        categories.sort_unstable();
+        categories.dedup();
+        categories
+    }
+}
+
+/// A single TODO item in a user's TODO list.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoItem {
+    pub priority: u32,
+    pub done: bool,
+    pub category: Option<String>,
+
+    /// The item's category from before it was moved to the user's done category
+    /// when it was marked done.
+    #[serde(default)]
+    pub category_before_done: Option<String>,
+
+    /// How long the user expects the item to take.
+    #[serde(default)]
+    pub estimate: Option<Duration>,
+
+    /// When the item needs to be done by.
+    #[serde(default)]
+    pub due: Option<DateTime<Utc>>,
+
+    /// Freeform details about the item.
+    #[serde(default)]
+    pub note: Option<String>,
+
+    /// Whether the item has been removed. Removed items are hidden everywhere
+    /// until they're restored, or deleted for good by `!todo purge`.
+    #[serde(default)]
+    pub archived: bool,
+
+    /// When the item was first added. Items added before this was tracked don't
+    /// have one.
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+
+    /// The item is hidden from the list until this time.
+    #[serde(default)]
+    pub snoozed_until: Option<DateTime<Utc>>,
+
+    /// When the item was last added or bumped with `!todo add`. Items bumped
+    /// before this was tracked don't have one.
+    #[serde(default)]
+    pub last_bumped: Option<DateTime<Utc>>,
+
+    /// How often the item repeats. Repeating items are never marked done, their
+    /// due date moves to the next occurrence instead.
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+}
+
+impl TodoItem {
+    /// Checks if the item is still snoozed at `now`.
+    fn is_snoozed(&self, now: DateTime<Utc>) -> bool {
+        matches!(self.snoozed_until, Some(until) if until > now)
+    }
+
+    /// Builds the line used to display the item when printing a TODO list.
+    ///
+    /// `priority_width` is the number of digits to pad numeric priorities to so
+    /// that the list lines up. Pending items that were due before `now` are
+    /// marked as overdue.
+    fn display_line(
+        &self,
+        key: &str,
+        show_category: bool,
+        priority_style: PriorityStyle,
+        priority_width: usize,
+        now: DateTime<Utc>,
+    ) -> String {
+        let check_mark = if self.done { 'X' } else { ' ' };
+        let priority = self.priority_label(priority_style, priority_width);
+
+        let category_str = match &self.category {
+            Some(category) if show_category => format!(" [{category}]"),
+            _ => "".into(),
+        };
+
+        let details = self.details_suffix(now);
+        format!("{priority} [{check_mark}]{category_str} {key}{details}")
+    }
+
+    /// Builds the item's priority as displayed in the list, e.g. `( 3)` or 🔴.
+    fn priority_label(&self, priority_style: PriorityStyle, priority_width: usize) -> String {
+        match priority_style {
+            PriorityStyle::Numeric => format!("({: >priority_width$})", self.priority),
+            PriorityStyle::Emoji { high, medium } => {
+                priority_emoji(self.priority, high, medium).to_string()
+            }
+        }
+    }
+
+    /// Builds the text shown after the item's key in the list, marking whether
+    /// it has a note and when it's due.
+    fn details_suffix(&self, now: DateTime<Utc>) -> String {
+        // Notes can be long, so only show that the item has one.
+        let note_str = if self.note.is_some() { " *" } else { "" };
+
+        let due_str = match self.due {
+            Some(due) if !self.done && due < now => format!(" (due {}) ⚠", due.format("%Y-%m-%d")),
+            Some(due) => format!(" (due {})", due.format("%Y-%m-%d")),
+            None => "".into(),
+        };
+
+        format!("{note_str}{due_str}")
+    }
+}
+
+/// A user's daily `!todo reminder` settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reminder {
+    /// The time of day, in UTC, to send the reminder.
+    time: NaiveTime,
+
+    /// When the reminder was last sent, so that it's only sent once a day.
+    last_sent: Option<DateTime<Utc>>,
+}
+
+/// How priorities are displayed when printing a user's TODO list.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PriorityStyle {
+    /// Display the priority value itself.
+    #[default]
+    Numeric,
+
+    /// Display a colored emoji based on which range the priority falls in. See
+    /// [`priority_emoji`].
+    Emoji { high: u32, medium: u32 },
+}
+
+/// The default `high` threshold for [`PriorityStyle::Emoji`].
+pub const DEFAULT_HIGH_PRIORITY: u32 = 10;
+
+/// The default `medium` threshold for [`PriorityStyle::Emoji`].
+pub const DEFAULT_MEDIUM_PRIORITY: u32 = 5;
+
+/// Picks the emoji used to display `priority`: 🔴 for priorities of at least
+/// `high`, 🟠 for priorities of at least `medium`, and 🟢 otherwise.
+fn priority_emoji(priority: u32, high: u32, medium: u32) -> char {
+    if priority >= high {
+        '🔴'
+    } else if priority >= medium {
+        '🟠'
+    } else {
+        '🟢'
+    }
+}
+
+/// How often a repeating item is due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, poise::ChoiceParameter)]
+pub enum Recurrence {
+    #[name = "daily"]
+    Daily,
+
+    #[name = "weekly"]
+    Weekly,
+
+    /// On the same day each month, or the last day of the month for days that
+    /// not every month has.
+    #[name = "monthly"]
+    Monthly,
+}
+
+/// Calculates an item's priority with priority decay turned on, i.e. its priority
+/// minus [`PRIORITY_DECAY_PER_WEEK`] for each full week since it was last bumped,
+/// down to a minimum of 1.
+///
+/// Items that haven't been bumped since this was tracked decay from when they
+/// were created, and items without either timestamp don't decay.
+fn effective_priority(item: &TodoItem, now: DateTime<Utc>) -> u32 {
+    let Some(last_bumped) = item.last_bumped.or(item.created_at) else {
+        return item.priority;
+    };
+
+    let weeks = (now - last_bumped).num_weeks();
+    if weeks <= 0 {
+        return item.priority;
+    }
+
+    let decay = u32::try_from(weeks)
+        .unwrap_or(u32::MAX)
+        .saturating_mul(PRIORITY_DECAY_PER_WEEK);
+    item.priority
+        .saturating_sub(decay)
+        .max(item.priority.min(1))
+}
+
+/// Calculates when a repeating item is next due after `from`.
+fn next_occurrence(from: DateTime<Utc>, recurrence: Recurrence) -> DateTime<Utc> {
+    match recurrence {
+        Recurrence::Daily => from + chrono::Duration::days(1),
+        Recurrence::Weekly => from + chrono::Duration::weeks(1),
+        Recurrence::Monthly => from + Months::new(1),
+    }
+}
+
+/// How items are ordered when printing a user's TODO list.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub enum SortMode {
+    /// Highest priority first.
+    #[default]
+    #[name = "priority"]
+    Priority,
+
+    /// Soonest due date first, with items that have no due date last.
+    #[name = "due"]
+    Due,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TodoCommand {
+    Print {
+        category: Option<String>,
+
+        #[serde(default)]
+        sort: SortMode,
+
+        /// Whether to include snoozed items.
+        #[serde(default)]
+        all: bool,
+    },
+
+    /// Prints all the details of a single item.
+    PrintOne(String),
+
+    /// Prints the items whose key, category, or note contains the search term,
+    /// ignoring case.
+    Search(String),
+
+    /// Adds an item, or bumps its priority if it's already in the list.
+    ///
+    /// If `priority` is given the item's priority is set to exactly that value
+    /// instead of being increased by 1.
+    Add {
+        key: String,
+        category: Option<String>,
+        priority: Option<u32>,
+    },
+
+    Remove(String),
+
+    /// Brings back an item that was removed.
+    Restore(String),
+
+    /// Permanently deletes every removed item.
+    Purge,
+    Finish(String),
+
+    /// Marks a done item as not done, moving it back out of the done category.
+    Unfinish(String),
+
+    /// Removes every item that's marked done.
+    ClearDone,
+
+    Rename {
+        from: String,
+        to: String,
+    },
+
+    CompactPriorities,
+    Where,
+
+    /// Moves an item to the given 1-based position in the list.
+    Reorder {
+        key: String,
+        position: usize,
+    },
+
+    /// Changes an item's category without changing its priority.
+    Move {
+        key: String,
+        category: Option<String>,
+    },
+
+    /// Reverts the priority bump from the last time the item was added.
+    UndoAdd(String),
+
+    TagCloud,
+
+    /// Summarizes the number of items in the list.
+    Stats,
+
+    /// Counts the pending and done items, optionally only in one category.
+    Count {
+        category: Option<String>,
+    },
+
+    Estimate {
+        key: String,
+        estimate: Option<Duration>,
+    },
+
+    Due {
+        key: String,
+        due: Option<DateTime<Utc>>,
+    },
+
+    /// Makes an item repeat, or stops it repeating if `recurrence` is `None`.
+    Recur {
+        key: String,
+        recurrence: Option<Recurrence>,
+    },
+
+    /// Hides an item from the list until `until`, or shows it again if `until`
+    /// is `None`.
+    Snooze {
+        key: String,
+        until: Option<DateTime<Utc>>,
+    },
+
+    /// Sets or clears an item's note.
+    Note {
+        key: String,
+        text: Option<String>,
+    },
+
+    Workload {
+        category: Option<String>,
+    },
+
+    /// Copies an item to a new key. The new key defaults to `"{key} (copy)"`.
+    Duplicate {
+        key: String,
+        new_key: Option<String>,
+    },
+
+    SetPriorityStyle(PriorityStyle),
+    SetDoneCategory(Option<String>),
+    SetReminder(Option<NaiveTime>),
+    SetIgnoreKeyCase(bool),
+
+    /// Turns priority decay on or off.
+    SetPriorityDecay(bool),
+
+    /// Replaces every item in the list, keeping a backup of the old items.
+    Replace(HashMap<String, TodoItem>),
+
+    /// Restores the items backed up by the last `Replace`.
+    RestoreBackup,
+
+    /// Adds items to the list. Where an item is already in the list, whichever
+    /// copy has the higher priority is kept.
+    Merge(HashMap<String, TodoItem>),
+
+    /// Exports every item in the list as JSON.
+    Export,
+}
+
+impl TodoCommand {
+    /// Checks if the command only reads the list, i.e. it never changes it.
+    fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            TodoCommand::Print { .. }
+                | TodoCommand::PrintOne(_)
+                | TodoCommand::Search(_)
+                | TodoCommand::Where
+                | TodoCommand::TagCloud
+                | TodoCommand::Stats
+                | TodoCommand::Count { .. }
+                | TodoCommand::Workload { .. }
+                | TodoCommand::Export
+        )
+    }
+
+    /// Gets the name of the command's variant, e.g. `Add`.
+    fn name(&self) -> &'static str {
+        match self {
+            TodoCommand::Print { .. } => "Print",
+            TodoCommand::PrintOne(_) => "PrintOne",
+            TodoCommand::Search(_) => "Search",
+            TodoCommand::Add { .. } => "Add",
+            TodoCommand::Remove(_) => "Remove",
+            TodoCommand::Restore(_) => "Restore",
+            TodoCommand::Purge => "Purge",
+            TodoCommand::Finish(_) => "Finish",
+            TodoCommand::Unfinish(_) => "Unfinish",
+            TodoCommand::ClearDone => "ClearDone",
+            TodoCommand::Rename { .. } => "Rename",
+            TodoCommand::CompactPriorities => "CompactPriorities",
+            TodoCommand::Where => "Where",
+            TodoCommand::Reorder { .. } => "Reorder",
+            TodoCommand::Move { .. } => "Move",
+            TodoCommand::UndoAdd(_) => "UndoAdd",
+            TodoCommand::TagCloud => "TagCloud",
+            TodoCommand::Stats => "Stats",
+            TodoCommand::Count { .. } => "Count",
+            TodoCommand::Estimate { .. } => "Estimate",
+            TodoCommand::Due { .. } => "Due",
+            TodoCommand::Recur { .. } => "Recur",
+            TodoCommand::Snooze { .. } => "Snooze",
+            TodoCommand::Note { .. } => "Note",
+            TodoCommand::Workload { .. } => "Workload",
+            TodoCommand::Duplicate { .. } => "Duplicate",
+            TodoCommand::SetPriorityStyle(_) => "SetPriorityStyle",
+            TodoCommand::SetDoneCategory(_) => "SetDoneCategory",
+            TodoCommand::SetReminder(_) => "SetReminder",
+            TodoCommand::SetIgnoreKeyCase(_) => "SetIgnoreKeyCase",
+            TodoCommand::SetPriorityDecay(_) => "SetPriorityDecay",
+            TodoCommand::Replace(_) => "Replace",
+            TodoCommand::RestoreBackup => "RestoreBackup",
+            TodoCommand::Merge(_) => "Merge",
+            TodoCommand::Export => "Export",
+        }
+    }
+
+    /// Gets the key of the item the command applies to, for commands that apply to
+    /// a single item.
+    fn key(&self) -> Option<&str> {
+        match self {
+            TodoCommand::PrintOne(key)
+            | TodoCommand::Add { key, .. }
+            | TodoCommand::Remove(key)
+            | TodoCommand::Restore(key)
+            | TodoCommand::Finish(key)
+            | TodoCommand::Unfinish(key)
+            | TodoCommand::Rename { from: key, .. }
+            | TodoCommand::Reorder { key, .. }
+            | TodoCommand::Move { key, .. }
+            | TodoCommand::UndoAdd(key)
+            | TodoCommand::Estimate { key, .. }
+            | TodoCommand::Due { key, .. }
+            | TodoCommand::Recur { key, .. }
+            | TodoCommand::Snooze { key, .. }
+            | TodoCommand::Note { key, .. }
+            | TodoCommand::Duplicate { key, .. } => Some(key),
+            _ => None,
+        }
+    }
+}
+
+/// A record of a change to a user's list, kept in an append-only audit trail for
+/// debugging user reports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub user_id: serenity::UserId,
+    pub timestamp: DateTime<Utc>,
+
+    /// The name of the command that made the change, e.g. `Add`.
+    pub command: String,
+
+    /// The key of the item that was changed, if the command changes a single
+    /// item.
+    pub key: Option<String>,
+}
+
+impl AuditEntry {
+    /// Builds the audit entry for a command run at `timestamp`, or `None` if
+    /// running the command didn't change the list, e.g. because it was rejected.
+    /// See [`handle_command_tracked`].
+    fn new(
+        user_id: serenity::UserId,
+        command: &TodoCommand,
+        changed: bool,
+        timestamp: DateTime<Utc>,
+    ) -> Option<Self> {
+        if !changed {
+            return None;
+        }
+
+        Some(AuditEntry {
+            user_id,
+            timestamp,
+            command: command.name().into(),
+            key: command.key().map(String::from),
+        })
+    }
+}
+
+/// A single entry in a user's append-only command log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandLogEntry {
+    pub user_id: serenity::UserId,
+
+    /// When the command ran, so that replaying it sets the same timestamps.
+    pub timestamp: DateTime<Utc>,
+
+    /// The item limit the command ran with.
+    pub max_items: usize,
+
+    pub command: TodoCommand,
+}
+
+/// Reconstructs a user's TODO list by replaying their logged commands, in order,
+/// onto a fresh list.
+///
+/// Each command runs with the time and item limit it was logged with, so the
+/// replayed list matches the original exactly.
+pub fn replay(
+    user_id: serenity::UserId,
+    entries: impl IntoIterator<Item = CommandLogEntry>,
+) -> TodoList {
+    let mut user = User::default();
+    user.id = user_id;
+
+    let mut todo_list = TodoList::new(user_id);
+    for entry in entries {
+        handle_command(
+            entry.command,
+            &mut todo_list,
+            &user,
+            entry.max_items,
+            entry.timestamp,
+        );
+    }
+
+    todo_list
+}
+
+/// Counts the items in each category for `!todo by-tag-cloud`.
+///
+/// Returns the category name, item count, and bar width for each category, most
+/// common first. Bar widths are proportional to the count, with the most common
+/// category getting a bar of [`TAG_CLOUD_BAR_WIDTH`].
+fn tag_cloud_rows(todo_list: &TodoList) -> Vec<(&str, usize, usize)> {
+    let mut counts = HashMap::<&str, usize>::new();
+    for category in todo_list
+        .active_items()
+        .filter_map(|item| item.category.as_deref())
+    {
+        *counts.entry(category).or_default() += 1;
+    }
+
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(a_name, a_count), (b_name, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_name.cmp(b_name))
+    });
+
+    let max_count = counts.first().map(|&(_, count)| count).unwrap_or_default();
+    counts
+        .into_iter()
+        .map(|(name, count)| {
+            // Round to the nearest width, but always show at least some bar.
+            let width = (count * TAG_CLOUD_BAR_WIDTH + max_count / 2) / max_count;
+            (name, count, width.max(1))
+        })
+        .collect()
+}
+
+/// Sums the estimates of the pending items in `category` (or all pending items if
+/// no category is given).
+///
+/// Returns the total estimate, the number of items that have an estimate, and the
+/// number of items that don't.
+fn total_estimate(todo_list: &TodoList, category: Option<&str>) -> (Duration, usize, usize) {
+    let pending = todo_list
+        .active_items()
+        .filter(|item| !item.done)
+        .filter(|item| category.is_none() || item.category.as_deref() == category);
+
+    let mut total = Duration::ZERO;
+    let mut estimated = 0;
+    let mut unestimated = 0;
+    for item in pending {
+        match item.estimate {
+            Some(estimate) => {
+                // Lists saved before estimates were limited could overflow.
+                total = total.saturating_add(estimate);
+                estimated += 1;
+            }
+            None => unestimated += 1,
+        }
+    }
+
+    (total, estimated, unestimated)
+}
+
+/// Normalizes a key for matching ignoring case and surrounding whitespace, e.g.
+/// `" Fix Build"` and `"fix build"` both normalize to `"fix build"`.
+fn normalize_key(key: &str) -> String {
+    key.trim().to_lowercase()
+}
+
+/// Formats how long ago an item was added, e.g. `3d` or `4h 12m`.
+///
+/// Ages of a day or more are truncated to whole days, and anything shorter to
+/// whole minutes.
+fn format_age(age: Duration) -> String {
+    const MINUTE: u64 = 60;
+    const DAY: u64 = 24 * 60 * MINUTE;
+
+    let unit = if age.as_secs() >= DAY { DAY } else { MINUTE };
+    duration::format(Duration::from_secs(age.as_secs() / unit * unit))
+}
+
+/// Runs [`handle_command`], also returning whether the command changed the list,
+/// i.e. whether the list needs to be saved.
+///
+/// Commands can leave the list unchanged even if they aren't read-only, e.g.
+/// marking a missing item done, so the list is compared to a copy from before
+/// the command ran.
+fn handle_command_tracked(
+    command: TodoCommand,
+    todo_list: &mut TodoList,
+    author: &User,
+    max_items: usize,
+    now: DateTime<Utc>,
+) -> (String, bool) {
+    // Read-only commands never change the list, so there's no need to copy it.
+    if command.is_read_only() {
+        let response = handle_command(command, todo_list, author, max_items, now);
+        return (response, false);
+    }
+
+    let before = todo_list.clone();
+    let response = handle_command(command, todo_list, author, max_items, now);
+    let changed = *todo_list != before;
+    (response, changed)
+}
+
+/// Performs the core logic for handling a `!todo` command.
+///
+/// Updates the state of `todo_list` to reflect the new list state, and returns
+/// the message that should be sent back to the channel where the command was
+/// given. Commands that would create a new item are rejected once the list has
+/// `max_items` items, and `now` is used as the time the command ran.
+fn handle_command(
+    command: TodoCommand,
+    todo_list: &mut TodoList,
+    author: &User,
+    max_items: usize,
+    now: DateTime<Utc>,
+) -> String {
+    let user_id = author.id;
+
+    // Handle the selected command.
+    match command {
+        TodoCommand::Add {
+            key,
+            category,
+            priority,
+        } => {
+            let key = todo_list.resolve_key(key);
+
+            // Bumping an existing item is always allowed, even when the list is full.
+            // Removed items don't count towards the limit.
+            if todo_list.get(&key).is_none() && todo_list.active_items().count() >= max_items {
+                return format!("You've reached the maximum of {max_items} items");
+            }
+
+            // Adding an item that was removed starts it over as a new item.
+            let is_new = todo_list.get(&key).is_none();
+            if let Some(item) = todo_list.items.get_mut(&key) {
+                if item.archived {
+                    *item = TodoItem::default();
+                }
+            }
+
+            let item = todo_list.items.entry(key.clone()).or_default();
+            if is_new {
+                item.created_at = Some(now);
+            }
+            item.last_bumped = Some(now);
+            match priority {
+                Some(priority) => item.priority = priority,
+                None => item.priority = item.priority.saturating_add(1),
+            }
+
+            // Update the item's category if one was specified.
+            if category.is_some() {
+                item.category = category;
+            }
+
+            let key_display = match &item.category {
+                Some(category) => format!("[{category}] {key:?}"),
+                None => format!("{key:?}"),
+            };
+
+            info!(
+                "Updated TODO item {key_display} for user {user_id}, priority: {}",
+                item.priority,
+            );
+
+            let response = match (priority, item.priority) {
+                (Some(priority), _) => format!("Set priority of {key_display} to {priority}"),
+                (None, 1) => format!("Added item {key_display} to your list"),
+                (None, _) => format!("Updated item {key_display}, priority is {}", item.priority),
+            };
+
+            response
+        }
+
+        TodoCommand::Remove(key) => {
+            let key = todo_list.resolve_key(key);
+
+            // Keep the item around so that it can be restored if it was removed by
+            // mistake.
+            let Some(item) = todo_list.get_mut(&key) else {
+                return format!("No item {key:?} in your list");
+            };
+            item.archived = true;
+
+            info!("Removed TODO item {key:?} for user {user_id}");
+
+            format!("Removed {key:?} from your list")
+        }
+
+        TodoCommand::Restore(key) => {
+            if todo_list.active_items().count() >= max_items {
+                return format!("You've reached the maximum of {max_items} items");
+            }
+
+            let Some(item) = todo_list.items.get_mut(&key).filter(|item| item.archived) else {
+                return format!("No removed item {key:?} to restore");
+            };
+
+            item.archived = false;
+
+            info!("Restored TODO item {key:?} for user {user_id}");
+
+            format!("Restored {key:?} to your list")
+        }
+
+        TodoCommand::Purge => {
+            let before = todo_list.items.len();
+            todo_list.items.retain(|_, item| !item.archived);
+            let purged = before - todo_list.items.len();
+
+            info!("Purged {purged} removed TODO items for user {user_id}");
+
+            match purged {
+                0 => "No removed items to purge".into(),
+                1 => "Permanently deleted 1 removed item".into(),
+                _ => format!("Permanently deleted {purged} removed items"),
+            }
+        }
+
+        TodoCommand::Finish(key) => {
+            let key = todo_list.resolve_key(key);
+            let done_category = todo_list.done_category.clone();
+            let Some(item) = todo_list.get_mut(&key) else {
+                return format!("No item {key:?} in your list");
+            };
+
+            // Repeating items stay open, and are due again at their next occurrence.
+            // Overdue items skip ahead so that they aren't immediately overdue again.
+            if let Some(recurrence) = item.recurrence {
+                let mut next = next_occurrence(item.due.unwrap_or(now), recurrence);
+                if next <= now {
+                    next = next_occurrence(now, recurrence);
+                }
+                item.due = Some(next);
+
+                info!("Finished repeating TODO item {key:?} for user {user_id}, next due {next}");
+
+                return format!(
+                    "Marked {key:?} as done, it's due again {}",
+                    next.format("%Y-%m-%d")
+                );
+            }
+
+            // Move the item to the user's done category, if they have one, remembering
+            // its old category so it can be restored.
+            if let Some(done_category) = done_category {
+                if !item.done {
+                    item.category_before_done = item.category.replace(done_category);
+                }
+            }
+
+            item.done = true;
+
+            info!("Finished TODO item {key:?} for user {user_id}");
+
+            format!("Marked {key:?} as done")
+        }
+
+        TodoCommand::Unfinish(key) => {
+            let key = todo_list.resolve_key(key);
+            let done_category = todo_list.done_category.clone();
+            let Some(item) = todo_list.get_mut(&key) else {
+                return format!("No item {key:?} in your list");
+            };
+
+            // Move the item back to the category it had before it was moved to the
+            // done category.
+            if item.done && item.category.is_some() && item.category == done_category {
+                item.category = item.category_before_done.take();
+            }
+
+            item.done = false;
+
+            info!("Reopened TODO item {key:?} for user {user_id}");
+
+            format!("Marked {key:?} as not done")
+        }
+
+        TodoCommand::ClearDone => {
+            let before = todo_list.items.len();
+            todo_list
+                .items
+                .retain(|_, item| item.archived || !item.done);
+            let cleared = before - todo_list.items.len();
+
+            info!("Cleared {cleared} completed TODO items for user {user_id}");
+
+            match cleared {
+                0 => "No completed items to clear".into(),
+                1 => "Cleared 1 completed item".into(),
+                _ => format!("Cleared {cleared} completed items"),
+            }
+        }
+
+        TodoCommand::Rename { from, to } => {
+            if todo_list.get(&from).is_none() {
+                return format!("No item {from:?} in your list");
+            }
+
+            // A removed item with the new key is replaced.
+            if todo_list.get(&to).is_some() {
+                return format!("There's already an item {to:?} in your list");
+            }
+
+            let item = todo_list.items.remove(&from).unwrap();
+            todo_list.items.insert(to.clone(), item);
+
+            info!("Renamed TODO item {from:?} to {to:?} for user {user_id}");
+
+            format!("Renamed {from:?} to {to:?}")
+        }
+
+        TodoCommand::SetDoneCategory(category) => {
+            info!("Set TODO done category for user {user_id} to {category:?}");
+
+            let response = match &category {
+                Some(category) => format!("Items will be moved to [{category}] when marked done"),
+                None => "Items will keep their category when marked done".into(),
+            };
+
+            todo_list.done_category = category;
+            response
+        }
+
+        TodoCommand::SetPriorityDecay(enabled) => {
+            todo_list.priority_decay = enabled;
+
+            info!("Set TODO priority decay for user {user_id} to {enabled}");
+
+            if enabled {
+                "Items will now lose priority for each week since you last added them".into()
+            } else {
+                "Items will keep their priority".into()
+            }
+        }
+
+        TodoCommand::SetIgnoreKeyCase(ignore_case) => {
+            todo_list.ignore_key_case = ignore_case;
+
+            info!("Set TODO key matching for user {user_id} to ignore case: {ignore_case}");
+
+            if ignore_case {
+                "Keys will match your items ignoring case".into()
+            } else {
+                "Keys will only match your items exactly".into()
+            }
+        }
+
+        TodoCommand::SetReminder(time) => {
+            info!("Set TODO reminder for user {user_id} to {time:?}");
+
+            // Count the reminder as already sent, so that setting a time that's
+            // already passed today doesn't send one straight away.
+            todo_list.reminder = time.map(|time| Reminder {
+                time,
+                last_sent: Some(now),
+            });
+
+            match time {
+                Some(time) => format!(
+                    "I'll DM you your top items every day at {} UTC",
+                    time.format("%H:%M")
+                ),
+                None => "Turned off your daily reminder".into(),
+            }
+        }
+
+        TodoCommand::CompactPriorities => {
+            // Collect the distinct priority values in ascending order. Each item's new
+            // priority is the position of its old priority in this list, so items that
+            // shared a priority still share one and the display order is unchanged.
+            let mut priorities = todo_list
+                .active_items()
+                .map(|item| item.priority)
+                .collect::<Vec<_>>();
+            priorities.sort_unstable();
+            priorities.dedup();
+
+            for item in todo_list.items.values_mut().filter(|item| !item.archived) {
+                let rank = priorities.binary_search(&item.priority).unwrap();
+                item.priority = rank as u32 + 1;
+            }
+
+            info!("Compacted TODO priorities for user {user_id}");
+
+            match priorities.len() {
+                0 => "Your list is empty, there's nothing to renumber".into(),
+                max => format!("Renumbered your list, priorities now range from 1 to {max}"),
+            }
+        }
+
+        TodoCommand::Reorder { key, position } => {
+            if todo_list.get(&key).is_none() {
+                return format!("No item {key:?} in your list");
+            }
+
+            // Move the key to its new position in the display order, then reassign
+            // priorities so that the list displays in exactly that order.
+            let mut keys = todo_list.sorted_keys();
+            keys.retain(|other| *other != key);
+            let position = position.clamp(1, keys.len() + 1);
+            keys.insert(position - 1, key.clone());
+
+            let count = keys.len();
+            for (index, key) in keys.iter().enumerate() {
+                todo_list.items.get_mut(key).unwrap().priority = (count - index) as u32;
+            }
+
+            info!("Moved TODO item {key:?} to position {position} for user {user_id}");
+
+            format!("Moved {key:?} to position {position}")
+        }
+
+        TodoCommand::Move { key, category } => {
+            let Some(item) = todo_list.get_mut(&key) else {
+                return format!("No item {key:?} in your list");
+            };
+
+            item.category = category;
+
+            info!(
+                "Moved TODO item {key:?} for user {user_id} to category {:?}",
+                item.category,
+            );
+
+            match &item.category {
+                Some(category) => format!("Moved {key:?} to [{category}]"),
+                None => format!("Removed {key:?} from its category"),
+            }
+        }
+
+        TodoCommand::UndoAdd(key) => {
+            let Some(item) = todo_list.get_mut(&key) else {
+                return format!("No item {key:?} in your list");
+            };
+
+            item.priority = item.priority.saturating_sub(1).max(1);
+
+            info!("Undid add of TODO item {key:?} for user {user_id}");
+
+            format!(
+                "Undone, the priority of {key:?} is back to {}",
+                item.priority
+            )
+        }
+
+        TodoCommand::Estimate { key, estimate } => {
+            if estimate.is_some_and(|estimate| estimate > MAX_ESTIMATE) {
+                return format!(
+                    "Estimates can be at most {}",
+                    duration::format(MAX_ESTIMATE)
+                );
+            }
+
+            let Some(item) = todo_list.get_mut(&key) else {
+                return format!("No item {key:?} in your list");
+            };
+
+            item.estimate = estimate;
+
+            info!("Set estimate of TODO item {key:?} for user {user_id} to {estimate:?}");
+
+            match estimate {
+                Some(estimate) => {
+                    format!("Estimated {key:?} at {}", duration::format(estimate))
+                }
+                None => format!("Cleared the estimate for {key:?}"),
+            }
+        }
+
+        TodoCommand::Due { key, due } => {
+            let Some(item) = todo_list.get_mut(&key) else {
+                return format!("No item {key:?} in your list");
+            };
+
+            item.due = due;
+
+            info!("Set due date of TODO item {key:?} for user {user_id} to {due:?}");
+
+            match due {
+                Some(due) => format!("{key:?} is due {}", due.format("%Y-%m-%d")),
+                None => format!("Cleared the due date for {key:?}"),
+            }
+        }
+
+        TodoCommand::Recur { key, recurrence } => {
+            let Some(item) = todo_list.get_mut(&key) else {
+                return format!("No item {key:?} in your list");
+            };
+
+            item.recurrence = recurrence;
+
+            info!("Set recurrence of TODO item {key:?} for user {user_id} to {recurrence:?}");
+
+            match recurrence {
+                Some(recurrence) => format!("{key:?} now repeats {recurrence}"),
+                None => format!("{key:?} no longer repeats"),
+            }
+        }
+
+        TodoCommand::Snooze { key, until } => {
+            let Some(item) = todo_list.get_mut(&key) else {
+                return format!("No item {key:?} in your list");
+            };
+
+            item.snoozed_until = until;
+
+            info!("Snoozed TODO item {key:?} for user {user_id} until {until:?}");
+
+            match until {
+                Some(until) => format!(
+                    "Snoozed {key:?} until {}",
+                    until.format("%Y-%m-%d %H:%M UTC")
+                ),
+                None => format!("{key:?} is no longer snoozed"),
+            }
+        }
+
+        TodoCommand::Note { key, text } => {
+            let Some(item) = todo_list.get_mut(&key) else {
+                return format!("No item {key:?} in your list");
+            };
+
+            info!("Set note of TODO item {key:?} for user {user_id}");
+
+            let response = match &text {
+                Some(_) => format!("Updated the note for {key:?}"),
+                None => format!("Removed the note from {key:?}"),
+            };
+
+            item.note = text;
+            response
+        }
+
+        TodoCommand::Workload { category } => {
+            let (total, estimated, unestimated) = total_estimate(todo_list, category.as_deref());
+
+            let mut response = match &category {
+                Some(category) => format!(
+                    "Estimated work in [{category}]: {} across {estimated} items",
+                    duration::format(total),
+                ),
+                None => format!(
+                    "Estimated work: {} across {estimated} items",
+                    duration::format(total),
+                ),
+            };
+
+            if unestimated > 0 {
+                write!(
+                    &mut response,
+                    " ({unestimated} pending items have no estimate)"
+                )
+                .unwrap();
+            }
+
+            response
+        }
+
+        TodoCommand::TagCloud => {
+            let rows = tag_cloud_rows(todo_list);
+            if rows.is_empty() {
+                return "None of your items have a category yet".into();
+            }
+
+            let name_width = rows.iter().map(|(name, ..)| name.chars().count()).max();
+            let name_width = name_width.unwrap_or_default();
+
+            let mut response = format!("Categories for {}:\n```\n", author.name);
+            for (name, count, bar_width) in rows {
+                let bar = "█".repeat(bar_width);
+                writeln!(&mut response, "{name: <name_width$} {bar} {count}").unwrap();
+            }
+            response.push_str("```\n");
+
+            response
+        }
+
+        TodoCommand::Stats => {
+            let total = todo_list.active_items().count();
+            let done = todo_list.active_items().filter(|item| item.done).count();
+
+            let mut response = format!(
+                "Stats for {}:\n\
+                Items: {total} ({} pending, {done} done)\n",
+                author.name,
+                total - done,
+            );
+
+            let categories = todo_list.categories();
+            if !categories.is_empty() {
+                response.push_str("Categories:\n");
+                for category in categories {
+                    let count = todo_list
+                        .active_items()
+                        .filter(|item| item.category.as_deref() == Some(category))
+                        .count();
+                    writeln!(&mut response, "  [{category}]: {count}").unwrap();
+                }
+
+                let uncategorized = todo_list
+                    .active_items()
+                    .filter(|item| item.category.is_none())
+                    .count();
+                writeln!(&mut response, "  Uncategorized: {uncategorized}").unwrap();
+            }
+
+            let top = todo_list
+                .sorted_keys()
+                .into_iter()
+                .find(|key| !todo_list.items[key].done);
+            if let Some(key) = top {
+                let priority = todo_list.items[&key].priority;
+                writeln!(&mut response, "Top priority: {key:?} ({priority})").unwrap();
+            }
+
+            response
+        }
+
+        TodoCommand::Count { category } => {
+            let items = todo_list.active_items().filter(|item| {
+                category.is_none() || item.category.as_deref() == category.as_deref()
+            });
+            let (done, pending): (Vec<_>, Vec<_>) = items.partition(|item| item.done);
+            let (pending, done) = (pending.len(), done.len());
+
+            match category {
+                Some(category) => {
+                    format!("You have {pending} pending and {done} done items in [{category}]")
+                }
+                None => format!("You have {pending} pending and {done} done items"),
+            }
+        }
+
+        TodoCommand::Duplicate { key, new_key } => {
+            let Some(item) = todo_list.get(&key) else {
+                return format!("No item {key:?} in your list");
+            };
+
+            // A removed item with the new key is replaced.
+            let new_key = new_key.unwrap_or_else(|| format!("{key} (copy)"));
+            if todo_list.get(&new_key).is_some() {
+                return format!("There's already an item {new_key:?} in your list");
+            }
+
+            if todo_list.active_items().count() >= max_items {
+                return format!("You've reached the maximum of {max_items} items");
+            }
+
+            // The copy is a new task, so it starts out not done.
+            let item = TodoItem {
+                done: false,
+                category_before_done: None,
+                created_at: Some(now),
+                last_bumped: Some(now),
+                ..item.clone()
+            };
+            todo_list.items.insert(new_key.clone(), item);
+
+            info!("Cloned TODO item {key:?} to {new_key:?} for user {user_id}");
+
+            format!("Copied {key:?} to {new_key:?}")
+        }
+
+        TodoCommand::SetPriorityStyle(style) => {
+            if let PriorityStyle::Emoji { high, medium } = style {
+                if medium == 0 || medium >= high {
+                    return format!(
+                        "Invalid thresholds: medium ({medium}) must be at least 1 and lower than \
+                        high ({high})"
+                    );
+                }
+            }
+
+            todo_list.priority_style = style;
+
+            info!("Set TODO priority style for user {user_id} to {style:?}");
+
+            match style {
+                PriorityStyle::Numeric => "Priorities will be shown as numbers".into(),
+                PriorityStyle::Emoji { high, medium } => format!(
+                    "Priorities will be shown as 🔴 from {high}, 🟠 from {medium}, and 🟢 below that"
+                ),
+            }
+        }
+
+        TodoCommand::Replace(items) => {
+            // Used by both `!todo import --replace` and `!todo bulk-edit`.
+            let total = items.values().filter(|item| !item.archived).count();
+            if total > max_items {
+                return format!(
+                    "That would give you {total} items, but the maximum is {max_items}"
+                );
+            }
+
+            let count = items.len();
+            let old = std::mem::replace(&mut todo_list.items, items);
+            todo_list.backup = Some(old);
+
+            info!("Replaced TODO list for user {user_id} with {count} items");
+
+            format!(
+                "Replaced your list with {count} items. Use `!todo bulk-undo` to restore your \
+                previous list"
+            )
+        }
+
+        TodoCommand::Merge(items) => {
+            let new_count = items
+                .keys()
+                .filter(|key| todo_list.get(key).is_none())
+                .count();
+            let total = todo_list.active_items().count() + new_count;
+            if total > max_items {
+                return format!(
+                    "Importing would give you {total} items, but the maximum is {max_items}"
+                );
+            }
+
+            let count = items.len();
+            let mut updated = 0;
+            for (key, item) in items {
+                match todo_list.get(&key) {
+                    Some(existing) if existing.priority >= item.priority => {}
+                    Some(_) => {
+                        todo_list.items.insert(key, item);
+                        updated += 1;
+                    }
+                    None => {
+                        todo_list.items.insert(key, item);
+                    }
+                }
+            }
+
+            info!("Imported {count} TODO items for user {user_id}");
+
+            format!(
+                "Imported {count} items: {new_count} added, {updated} updated, {} unchanged",
+                count - new_count - updated,
+            )
+        }
+
+        TodoCommand::RestoreBackup => match todo_list.backup.take() {
+            Some(backup) => {
+                let count = backup.len();
+                todo_list.items = backup;
+
+                info!("Restored TODO list backup for user {user_id}");
+
+                format!("Restored the {count} items from before your last bulk edit")
+            }
+
+            None => "There's no bulk edit to undo".into(),
+        },
+
+        TodoCommand::Export => {
+            info!("Exporting TODO list for user {user_id}");
+
+            format!("```json\n{}\n```\n", export_json(todo_list))
+        }
+
+        TodoCommand::Where => {
+            let items = match todo_list.active_items().count() {
+                1 => "1 item".into(),
+                count => format!("{count} items"),
+            };
+            format!("You have a single TODO list with {items}, shared across every server and DMs")
+        }
+
+        TodoCommand::PrintOne(key) => {
+            let Some(item) = todo_list.get(&key) else {
+                return format!("No item {key:?} in your list");
+            };
+
+            info!("Printing TODO item {key:?} for user {user_id}");
+
+            let mut response = format!("{key:?} in {}'s TODO list:\n```\n", author.name);
+            writeln!(&mut response, "Priority: {}", item.priority).unwrap();
+
+            let status = if item.done { "Done" } else { "Not done" };
+            writeln!(&mut response, "Status:   {status}").unwrap();
+
+            if let Some(category) = &item.category {
+                writeln!(&mut response, "Category: {category}").unwrap();
+            }
+
+            if let Some(estimate) = item.estimate {
+                writeln!(&mut response, "Estimate: {}", duration::format(estimate)).unwrap();
+            }
+
+            if let Some(due) = item.due {
+                let overdue = if !item.done && due < now {
+                    " ⚠ overdue"
+                } else {
+                    ""
+                };
+                writeln!(
+                    &mut response,
+                    "Due:      {}{overdue}",
+                    due.format("%Y-%m-%d")
+                )
+                .unwrap();
+            }
+
+            if let Some(recurrence) = item.recurrence {
+                writeln!(&mut response, "Repeats:  {recurrence}").unwrap();
+            }
+
+            if let Some(until) = item.snoozed_until.filter(|_| item.is_snoozed(now)) {
+                writeln!(
+                    &mut response,
+                    "Snoozed:  until {}",
+                    until.format("%Y-%m-%d %H:%M UTC")
+                )
+                .unwrap();
+            }
+
+            if let Some(created_at) = item.created_at {
+                let age = (now - created_at).to_std().unwrap_or_default();
+                writeln!(
+                    &mut response,
+                    "Added:    {} ({} ago)",
+                    created_at.format("%Y-%m-%d"),
+                    format_age(age),
+                )
+                .unwrap();
+            }
+
+            if let Some(note) = &item.note {
+                writeln!(&mut response, "\n{note}").unwrap();
+            }
+
+            response.push_str("```\n");
+            response
+        }
+
+        TodoCommand::Print {
+            category,
+            sort,
+            all,
+        } => {
+            info!("Printing TODO list for user {user_id}");
+
+            let user_name = &author.name;
+            let mut response = match &category {
+                Some(category) => format!("TODO list for {user_name} in category [{category}]:\n"),
+                None => format!("TODO list for {user_name}:\n"),
+            };
+
+            let keys = todo_list.print_keys(category.as_deref(), sort, all, now);
+            response.push_str(&list_table(todo_list, &keys, category.is_none()));
+
+            let snoozed = todo_list
+                .print_keys(category.as_deref(), sort, true, now)
+                .len()
+                - keys.len();
+            if snoozed > 0 {
+                write!(
+                    &mut response,
+                    "{snoozed} snoozed item(s) hidden, use `!todo show --all` to see them"
+                )
+                .unwrap();
+            }
+            response
+        }
+
+        TodoCommand::Search(term) => {
+            let query = term.to_lowercase();
+            let mut keys = todo_list.sorted_keys();
+            keys.retain(|key| {
+                let item = &todo_list.items[key];
+                [Some(key), item.category.as_ref(), item.note.as_ref()]
+                    .into_iter()
+                    .flatten()
+                    .any(|text| text.to_lowercase().contains(&query))
+            });
+
+            if keys.is_empty() {
+                return format!("No items matched {term:?}");
+            }
+
+            let mut response = format!("TODO items matching {term:?}:\n");
+            response.push_str(&list_table(todo_list, &keys, true));
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::todo::{
+        self, AuditEntry, CommandLogEntry, ListCache, ListStore, PriorityStyle, RecentAdd,
+        Recurrence, Reminder, SortMode, TodoCommand, TodoItem, TodoList, CACHE_TTL,
+        DEFAULT_WELCOME_MESSAGE,
+    };
+    use anyhow::{anyhow, Result};
+    use chrono::{NaiveTime, TimeZone, Utc};
+    use mongodb::bson::doc;
+    use poise::serenity_prelude::json::json;
+    use poise::serenity_prelude::model::user::User;
+    use poise::serenity_prelude::{Colour, EmojiId, ReactionType, UserId};
+    use pretty_assertions::assert_eq;
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use std::future::Future;
+    use std::time::{Duration, Instant};
+
+    static USER_NAME: &str = "randomPoison";
+
+    /// An in-memory [`ListStore`] that counts how often it's used.
+    #[derive(Default)]
+    struct FakeStore {
+        lists: RefCell<HashMap<UserId, TodoList>>,
+        finds: Cell<usize>,
+        writes: Cell<usize>,
+        fail_updates: Cell<bool>,
+    }
+
+    impl ListStore for FakeStore {
+        async fn find(&self, user_id: UserId) -> Result<Option<TodoList>> {
+            self.finds.set(self.finds.get() + 1);
+            Ok(self.lists.borrow().get(&user_id).cloned())
+        }
+
+        async fn insert(&self, todo_list: &TodoList) -> Result<()> {
+            self.writes.set(self.writes.get() + 1);
+            let mut lists = self.lists.borrow_mut();
+            lists.insert(todo_list.user_id, todo_list.clone());
+            Ok(())
+        }
+
+        async fn update(&self, todo_list: &TodoList) -> Result<()> {
+            self.writes.set(self.writes.get() + 1);
+            if self.fail_updates.get() {
+                return Err(anyhow!("update failed"));
+            }
+
+            let mut lists = self.lists.borrow_mut();
+            lists.insert(todo_list.user_id, todo_list.clone());
+            Ok(())
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    /// Builds a [Message] from the given `text`.
+    fn send_command(command: TodoCommand, state: &mut TodoList) -> String {
+        let mut user = User::default();
+        user.name = USER_NAME.into();
+
+        todo::handle_command(command, state, &user, todo::DEFAULT_MAX_ITEMS, Utc::now())
+    }
+
+    // Adds an item and verifies that the response is correct.
+    fn add_item(state: &mut TodoList, key: impl Into<String>, priority: u32) {
+        let key = key.into();
+        let response = send_command(
+            TodoCommand::Add {
+                key: key.clone(),
+                category: None,
+                priority: None,
+            },
+            state,
+        );
+
+        let expected = match priority {
+            1 => format!("Added item {key:?} to your list"),
+            _ => format!("Updated item {key:?}, priority is {priority}"),
+        };
+        assert_eq!(expected, response);
+    }
+
+    // Adds an item and verifies that the response is correct.
+    fn add_with_category(
+        state: &mut TodoList,
+        key: impl Into<String>,
+        category: impl Into<String>,
+        priority: u32,
+    ) {
+        let key = key.into();
+        let category = category.into();
+
+        let response = send_command(
+            TodoCommand::Add {
+                key: key.clone(),
+                category: Some(category.clone()),
+                priority: None,
+            },
+            state,
+        );
+
+        let expected = match priority {
+            1 => format!("Added item [{category}] {key:?} to your list"),
+            _ => format!("Updated item [{category}] {key:?}, priority is {priority}"),
+        };
+        assert_eq!(expected, response);
+    }
+
+    /// Tests that an item can be added from the list, displayed, and then removed.
+    #[test]
+    fn add_display_remove() {
+        let mut state = TodoList::default();
+
+        // Add an item with the key "foo" to the list.
+        add_item(&mut state, "foo", 1);
+
+        // Verify that the item can be displayed in the TODO list.
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (1) [ ] foo\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        // Remove the item from the list.
+        let response = send_command(TodoCommand::Remove("foo".into()), &mut state);
+        assert_eq!(r#"Removed "foo" from your list"#, response);
+
+        // Verify that the list is now empty when printed.
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                ```\n"
+            ),
+            response,
+        );
+    }
+
+    /// Verifies that removed items are hidden but kept until they're purged, and
+    /// can be restored in the meantime.
+    #[test]
+    fn archive_restore_purge() {
+        let mut state = TodoList::default();
+        add_with_category(&mut state, "foo", "Work", 1);
+        add_with_category(&mut state, "foo", "Work", 2);
+        add_item(&mut state, "bar", 1);
+        add_item(&mut state, "baz", 1);
+
+        send_command(TodoCommand::Remove("foo".into()), &mut state);
+        send_command(TodoCommand::Remove("bar".into()), &mut state);
+        assert!(state.items["foo"].archived);
+        assert_eq!(vec!["baz"], state.sorted_keys());
+        assert!(state.categories().is_empty());
+
+        // Removed items can't be edited, or removed again.
+        let response = send_command(TodoCommand::Finish("foo".into()), &mut state);
+        assert_eq!(r#"No item "foo" in your list"#, response);
+        let response = send_command(TodoCommand::Remove("foo".into()), &mut state);
+        assert_eq!(r#"No item "foo" in your list"#, response);
+        let response = send_command(TodoCommand::Remove("missing".into()), &mut state);
+        assert_eq!(r#"No item "missing" in your list"#, response);
+
+        // Restoring an item brings it back as it was.
+        let response = send_command(TodoCommand::Restore("foo".into()), &mut state);
+        assert_eq!(r#"Restored "foo" to your list"#, response);
+        assert_eq!(vec!["foo", "baz"], state.sorted_keys());
+        assert_eq!(2, state.items["foo"].priority);
+        assert_eq!(Some("Work"), state.items["foo"].category.as_deref());
+
+        let response = send_command(TodoCommand::Restore("foo".into()), &mut state);
+        assert_eq!(r#"No removed item "foo" to restore"#, response);
+
+        // Purging deletes removed items for good.
+        let response = send_command(TodoCommand::Purge, &mut state);
+        assert_eq!("Permanently deleted 1 removed item", response);
+        assert!(!state.items.contains_key("bar"));
+        assert_eq!(vec!["foo", "baz"], state.sorted_keys());
+
+        let response = send_command(TodoCommand::Restore("bar".into()), &mut state);
+        assert_eq!(r#"No removed item "bar" to restore"#, response);
+        let response = send_command(TodoCommand::Purge, &mut state);
+        assert_eq!("No removed items to purge", response);
+
+        // Adding a removed item starts it over as a new item.
+        send_command(TodoCommand::Remove("foo".into()), &mut state);
+        add_item(&mut state, "foo", 1);
+        assert_eq!(None, state.items["foo"].category);
+    }
+
+    // Verifies that items in the TODO list are displayed in priority order.
+    #[test]
+    fn priority_sort() {
+        let mut state = TodoList::default();
+
+        // Create 3 TODO items, each with different priority values.
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "foo", 2);
+        add_item(&mut state, "foo", 3);
+        add_item(&mut state, "foo", 4);
+        add_item(&mut state, "foo", 5);
+        add_item(&mut state, "foo", 6);
+        add_item(&mut state, "foo", 7);
+        add_item(&mut state, "foo", 8);
+        add_item(&mut state, "foo", 9);
+        add_item(&mut state, "foo", 10);
+
+        add_item(&mut state, "foo bar", 1);
+        add_item(&mut state, "foo bar", 2);
+
+        add_item(&mut state, "foo bar baz", 1);
+
+        // Verify that the items are displayed in the correct order.
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (10) [ ] foo\n\
+                ( 2) [ ] foo bar\n\
+                ( 1) [ ] foo bar baz\n\
+                ```\n"
+            ),
+            response,
+        );
+    }
+
+    /// Verifies that items can be marked done.
+    #[test]
+    fn mark_items_done() {
+        let mut state = TodoList::default();
+
+        // Create 2 TODO items with different priority values so that they'll print
+        // in a deterministic order.
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "foo", 2);
+
+        add_item(&mut state, "foo bar", 1);
+
+        let response = send_command(TodoCommand::Finish("foo".into()), &mut state);
+        assert_eq!(r#"Marked "foo" as done"#, response);
+
+        // Verify that the items are displayed in the correct order.
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (2) [X] foo\n\
+                (1) [ ] foo bar\n\
+                ```\n"
+            ),
+            response,
+        );
+    }
+
+    /// Verifies that a category can be set for each item and that categories are
+    /// correctly handled when displaying the TODO list.
+    #[test]
+    fn categories() {
+        let mut state = TodoList::default();
+
+        // Create 2 TODO items with different priority values so that they'll print
+        // in a deterministic order.
+        add_with_category(&mut state, "foo", "Foo", 1);
+        add_with_category(&mut state, "foo", "Foo", 2);
+        add_item(&mut state, "foo bar", 1);
+
+        // Verify that all items are displayed if no category is specified.
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (2) [ ] [Foo] foo\n\
+                (1) [ ] foo bar\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        // Verify that a specific category can be displayed.
+        let response = send_command(
+            TodoCommand::Print {
+                category: Some("Foo".into()),
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME} in category [Foo]:\n\
+                ```\n\
+                (2) [ ] foo\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        // Verify that we can change the category of an existing item.
+        add_with_category(&mut state, "foo", "Bar", 3);
+        add_with_category(&mut state, "foo bar", "Foo", 2);
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (3) [ ] [Bar] foo\n\
+                (2) [ ] [Foo] foo bar\n\
+                ```\n"
+            ),
+            response,
+        );
+    }
+
+    /// Verifies that replaying a recorded command log reproduces the same list
+    /// state as running the commands live.
+    #[test]
+    fn replay_command_log() {
+        let mut state = TodoList::default();
+
+        let commands = vec![
+            TodoCommand::Add {
+                key: "foo".into(),
+                category: None,
+                priority: None,
+            },
+            TodoCommand::Add {
+                key: "foo".into(),
+                category: Some("Foo".into()),
+                priority: None,
+            },
+            TodoCommand::Add {
+                key: "bar".into(),
+                category: None,
+                priority: None,
+            },
+            TodoCommand::Add {
+                key: "baz".into(),
+                category: None,
+                priority: None,
+            },
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            TodoCommand::Finish("bar".into()),
+            TodoCommand::Remove("baz".into()),
+            TodoCommand::Add {
+                key: "daily".into(),
+                category: None,
+                priority: None,
+            },
+            TodoCommand::Recur {
+                key: "daily".into(),
+                recurrence: Some(Recurrence::Daily),
+            },
+            TodoCommand::Finish("daily".into()),
+            // Rejected by the item limit, so it isn't logged.
+            TodoCommand::Add {
+                key: "qux".into(),
+                category: None,
+                priority: None,
+            },
+        ];
+
+        // Run the commands live, recording each one that changed the list to the log
+        // the same way `run_command` does.
+        let mut user = User::default();
+        user.id = state.user_id;
+        let max_items = 3;
+        let start = Utc.with_ymd_and_hms(2023, 6, 15, 12, 0, 0).unwrap();
+        let mut log = Vec::new();
+        for (hours, command) in commands.into_iter().enumerate() {
+            let timestamp = start + chrono::Duration::hours(hours as i64);
+            let (_, changed) = todo::handle_command_tracked(
+                command.clone(),
+                &mut state,
+                &user,
+                max_items,
+                timestamp,
+            );
+            if changed {
+                let entry = CommandLogEntry {
+                    user_id: state.user_id,
+                    timestamp,
+                    max_items,
+                    command,
+                };
+                log.push(bson::to_document(&entry).unwrap());
+            }
+        }
+        assert!(!state.items.contains_key("qux"));
+        assert_eq!(9, log.len());
+
+        // Replay the log onto a fresh list and verify it matches, including the
+        // timestamps.
+        let replayed = todo::replay(
+            state.user_id,
+            log.into_iter()
+                .map(|doc| bson::from_document::<CommandLogEntry>(doc).unwrap()),
+        );
+        assert_eq!(state, replayed);
+    }
+
+    /// Verifies that compacting priorities makes them contiguous without changing
+    /// the order the items are displayed in.
+    #[test]
+    fn compact_priorities() {
+        let mut state = TodoList::default();
+
+        // Create items with sparse priorities, including two that share a priority.
+        for (key, priority) in [("foo", 42), ("bar", 7), ("baz", 7), ("qux", 1)] {
+            state.items.insert(
+                key.into(),
+                TodoItem {
+                    priority,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let response = send_command(TodoCommand::CompactPriorities, &mut state);
+        assert_eq!(
+            "Renumbered your list, priorities now range from 1 to 3",
+            response,
+        );
+
+        let priorities = ["foo", "bar", "baz", "qux"].map(|key| state.items[key].priority);
+        assert_eq!([3, 2, 2, 1], priorities);
+
+        // Compacting an empty list is a no-op.
+        let mut state = TodoList::default();
+        let response = send_command(TodoCommand::CompactPriorities, &mut state);
+        assert_eq!("Your list is empty, there's nothing to renumber", response);
+        assert!(state.items.is_empty());
+    }
+
+    /// Verifies that a user's list is looked up by user ID alone, so the same list
+    /// is used from any server or DM.
+    #[test]
+    fn single_list_per_user() {
+        let user_id = UserId(1234);
+        assert_eq!(doc! { "user_id": "1234" }, todo::list_query(user_id));
+        assert_ne!(todo::list_query(user_id), todo::list_query(UserId(5678)));
+
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "bar", 1);
+
+        let response = send_command(TodoCommand::Where, &mut state);
+        assert_eq!(
+            "You have a single TODO list with 2 items, shared across every server and DMs",
+            response,
+        );
+    }
+
+    /// Verifies that the values selected in the reorder menus map to the right
+    /// reorder command, and that reordering produces the expected display order.
+    #[test]
+    fn reorder() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "foo", 2);
+        add_item(&mut state, "foo", 3);
+        add_item(&mut state, "bar", 1);
+        add_item(&mut state, "bar", 2);
+        add_item(&mut state, "baz", 1);
+
+        let keys = state.sorted_keys();
+        assert_eq!(vec!["foo", "bar", "baz"], keys);
+        let reorder = |item: &str, position: &str, keys: &[String]| {
+            todo::reorder_command(&[item.into()], &[position.into()], keys).unwrap()
+        };
+
+        // Malformed selections don't produce a command.
+        assert!(todo::reorder_command(&[], &["1".into()], &keys).is_none());
+        assert!(todo::reorder_command(&["2".into()], &[], &keys).is_none());
+        assert!(todo::reorder_command(&["2".into()], &["top".into()], &keys).is_none());
+        assert!(todo::reorder_command(&["baz".into()], &["1".into()], &keys).is_none());
+        assert!(todo::reorder_command(&["3".into()], &["1".into()], &keys).is_none());
+
+        // Move the bottom item to the top of the list.
+        let command = reorder("2", "1", &keys);
+        let response = send_command(command, &mut state);
+        assert_eq!(r#"Moved "baz" to position 1"#, response);
+
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (3) [ ] baz\n\
+                (2) [ ] foo\n\
+                (1) [ ] bar\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        // Move the top item to the middle of the list.
+        let keys = state.sorted_keys();
+        send_command(reorder("0", "2", &keys), &mut state);
+        assert_eq!(vec!["foo", "baz", "bar"], state.sorted_keys());
+
+        // Positions past the end of the list move the item to the bottom.
+        let keys = state.sorted_keys();
+        let command = reorder("0", "10", &keys);
+        let response = send_command(command, &mut state);
+        assert_eq!(r#"Moved "foo" to position 3"#, response);
+        assert_eq!(vec!["baz", "bar", "foo"], state.sorted_keys());
+
+        // Unknown keys leave the list unchanged.
+        let response = send_command(
+            TodoCommand::Reorder {
+                key: "qux".into(),
+                position: 1,
+            },
+            &mut state,
+        );
+        assert_eq!(r#"No item "qux" in your list"#, response);
+        assert_eq!(vec!["baz", "bar", "foo"], state.sorted_keys());
+
+        // Long keys are cut off in the menu, but still reorder the full key.
+        let long_key = "x".repeat(150);
+        add_item(&mut state, &long_key, 1);
+        let label = todo::select_label(&long_key);
+        assert_eq!(100, label.chars().count());
+        assert!(label.ends_with('…'));
+        assert_eq!("x".repeat(100), todo::select_label(&"x".repeat(100)));
+
+        let keys = state.sorted_keys();
+        let index = keys.iter().position(|key| *key == long_key).unwrap();
+        let command = reorder(&index.to_string(), "1", &keys);
+        send_command(command, &mut state);
+        assert_eq!(long_key, state.sorted_keys()[0]);
+    }
+
+    /// Verifies that the welcome message is only shown when the user's list was
+    /// just created, going through the store the same way `!todo` commands do.
+    #[test]
+    fn welcome_message() {
+        let store = FakeStore::default();
+        let cache = ListCache::new(CACHE_TTL);
+        let mut user = User::default();
+        user.id = UserId(1);
+        user.name = USER_NAME.into();
+
+        let run = |command, now| {
+            let (mut state, first_use) =
+                block_on(todo::load_cached(&store, &cache, user.id, now)).unwrap();
+            let max_items = todo::DEFAULT_MAX_ITEMS;
+            let (response, changed) =
+                todo::handle_command_tracked(command, &mut state, &user, max_items, Utc::now());
+            if changed {
+                block_on(todo::save_cached(&store, &cache, &state, now)).unwrap();
+            }
+            todo::with_welcome(response, first_use, DEFAULT_WELCOME_MESSAGE)
+        };
+
+        let start = Instant::now();
+        let add = TodoCommand::Add {
+            key: "foo".into(),
+            category: None,
+            priority: None,
+        };
+        assert_eq!(
+            format!("{DEFAULT_WELCOME_MESSAGE}\n\nAdded item \"foo\" to your list"),
+            run(add, start),
+        );
+        assert!(store.lists.borrow()[&user.id].items.contains_key("foo"));
+
+        let where_ = "You have a single TODO list with 1 item, shared across every server and DMs";
+        assert_eq!(where_, run(TodoCommand::Where, start));
+
+        // Once the cached copy expires the list is loaded from the store, which isn't
+        // a first use either.
+        assert_eq!(where_, run(TodoCommand::Where, start + CACHE_TTL));
+        assert_eq!(2, store.finds.get());
+    }
+
+    /// Verifies that each line of a bulk edit file is parsed strictly.
+    #[test]
+    fn parse_bulk_edit_line() {
+        assert_eq!(
+            Ok((
+                "foo bar".to_string(),
+                TodoItem {
+                    priority: 3,
+                    done: true,
+                    category: Some("Foo".into()),
+                    ..Default::default()
+                }
+            )),
+            todo::parse_bulk_edit_line("3\ttrue\tFoo\tfoo bar"),
+        );
+
+        // An empty category means the item is uncategorized.
+        assert_eq!(
+            Ok(("foo".to_string(), TodoItem::default())),
+            todo::parse_bulk_edit_line("0\tfalse\t\tfoo"),
+        );
+
+        // Malformed lines are rejected.
+        assert!(todo::parse_bulk_edit_line("3 true Foo foo").is_err());
+        assert!(todo::parse_bulk_edit_line("3\ttrue\tfoo").is_err());
+        assert!(todo::parse_bulk_edit_line("3\ttrue\tFoo\tfoo\textra").is_err());
+        assert!(todo::parse_bulk_edit_line("-1\ttrue\tFoo\tfoo").is_err());
+        assert!(todo::parse_bulk_edit_line("3\tyes\tFoo\tfoo").is_err());
+        assert!(todo::parse_bulk_edit_line("3\ttrue\tFoo\t").is_err());
+    }
+
+    /// Verifies that a whole bulk edit file replaces the list and can be undone,
+    /// and that errors are reported per line.
+    #[test]
+    fn bulk_edit() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+
+        // Errors are reported for every malformed line, with line numbers.
+        let errors =
+            todo::parse_bulk_edit("1\tfalse\t\tbar\n\nbad\n2\tfalse\t\tbar\n").unwrap_err();
+        assert_eq!(
+            vec![
+                "Line 3: Expected 4 tab-separated fields (priority, done, category, key), found 1",
+                "Line 4: Duplicate key \"bar\"",
+            ],
+            errors,
+        );
+
+        let items = todo::parse_bulk_edit("2\tfalse\tBar\tbar\n1\ttrue\t\tbaz\n").unwrap();
+        let response = send_command(TodoCommand::Replace(items), &mut state);
+        assert_eq!(
+            "Replaced your list with 2 items. Use `!todo bulk-undo` to restore your previous list",
+            response,
+        );
+
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (2) [ ] [Bar] bar\n\
+                (1) [X] baz\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        // Undoing restores the original list, and can only be done once.
+        let response = send_command(TodoCommand::RestoreBackup, &mut state);
+        assert_eq!(
+            "Restored the 1 items from before your last bulk edit",
+            response
+        );
+        assert_eq!(vec!["foo"], state.sorted_keys());
+
+        let response = send_command(TodoCommand::RestoreBackup, &mut state);
+        assert_eq!("There's no bulk edit to undo", response);
+        assert_eq!(vec!["foo"], state.sorted_keys());
+    }
+
+    /// Verifies that cloning an item copies its fields into a new, independent
+    /// item.
+    #[test]
+    fn clone_item() {
+        let mut state = TodoList::default();
+        add_with_category(&mut state, "foo", "Foo", 1);
+        add_with_category(&mut state, "foo", "Foo", 2);
+        send_command(TodoCommand::Finish("foo".into()), &mut state);
+
+        let response = send_command(
+            TodoCommand::Duplicate {
+                key: "foo".into(),
+                new_key: None,
+            },
+            &mut state,
+        );
+        assert_eq!(r#"Copied "foo" to "foo (copy)""#, response);
+        assert_eq!(
+            TodoItem {
+                priority: 2,
+                done: false,
+                category: Some("Foo".into()),
+                created_at: state.items["foo (copy)"].created_at,
+                last_bumped: state.items["foo (copy)"].last_bumped,
+                ..Default::default()
+            },
+            state.items["foo (copy)"],
+        );
+
+        // The copy counts as a new item.
+        assert!(state.items["foo (copy)"].created_at >= state.items["foo"].created_at);
+
+        // Changing the clone doesn't affect the original.
+        add_with_category(&mut state, "foo (copy)", "Bar", 3);
+        assert_eq!(
+            TodoItem {
+                priority: 2,
+                done: true,
+                category: Some("Foo".into()),
+                created_at: state.items["foo"].created_at,
+                last_bumped: state.items["foo"].last_bumped,
+                ..Default::default()
+            },
+            state.items["foo"],
+        );
+
+        // An explicit key can be given, but existing items aren't overwritten.
+        let response = send_command(
+            TodoCommand::Duplicate {
+                key: "foo".into(),
+                new_key: Some("foo (copy)".into()),
+            },
+            &mut state,
+        );
+        assert_eq!(
+            r#"There's already an item "foo (copy)" in your list"#,
+            response
+        );
+        assert_eq!(3, state.items["foo (copy)"].priority);
+
+        // Cloning a missing item is an error.
+        let response = send_command(
+            TodoCommand::Duplicate {
+                key: "bar".into(),
+                new_key: Some("baz".into()),
+            },
+            &mut state,
+        );
+        assert_eq!(r#"No item "bar" in your list"#, response);
+        assert_eq!(2, state.items.len());
+    }
+
+    /// Verifies that priorities map to the right emoji for the given thresholds.
+    #[test]
+    fn priority_emoji() {
+        let emoji = [0, 1, 4, 5, 9, 10, 100].map(|priority| todo::priority_emoji(priority, 10, 5));
+        assert_eq!(['🟢', '🟢', '🟢', '🟠', '🟠', '🔴', '🔴'], emoji);
+
+        let emoji = [1, 2, 3].map(|priority| todo::priority_emoji(priority, 3, 2));
+        assert_eq!(['🟢', '🟠', '🔴'], emoji);
+    }
+
+    /// Verifies that the emoji priority style is validated and used when printing
+    /// the list.
+    #[test]
+    fn emoji_priority_style() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "foo", 2);
+        add_item(&mut state, "foo", 3);
+        add_item(&mut state, "bar", 1);
+        add_item(&mut state, "bar", 2);
+        add_item(&mut state, "baz", 1);
+
+        // Thresholds must be in order, and the medium threshold can't be zero.
+        for (high, medium) in [(2, 3), (2, 2), (2, 0)] {
+            let response = send_command(
+                TodoCommand::SetPriorityStyle(PriorityStyle::Emoji { high, medium }),
+                &mut state,
+            );
+            assert_eq!(
+                format!(
+                    "Invalid thresholds: medium ({medium}) must be at least 1 and lower than \
+                    high ({high})"
+                ),
+                response,
+            );
+            assert_eq!(PriorityStyle::Numeric, state.priority_style);
+        }
+
+        let response = send_command(
+            TodoCommand::SetPriorityStyle(PriorityStyle::Emoji { high: 3, medium: 2 }),
+            &mut state,
+        );
+        assert_eq!(
+            "Priorities will be shown as 🔴 from 3, 🟠 from 2, and 🟢 below that",
+            response,
+        );
+
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                🔴 [ ] foo\n\
+                🟠 [ ] bar\n\
+                🟢 [ ] baz\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        // Switching back to numeric restores the default display.
+        send_command(
+            TodoCommand::SetPriorityStyle(PriorityStyle::Numeric),
+            &mut state,
+        );
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (3) [ ] foo\n\
+                (2) [ ] bar\n\
+                (1) [ ] baz\n\
+                ```\n"
+            ),
+            response,
+        );
+    }
+
+    /// Verifies that only repeated adds of the same key within the window are
+    /// detected as rapid repeats.
+    #[test]
+    fn rapid_repeat_detection() {
+        let start = Instant::now();
+        let previous = RecentAdd {
+            key: "foo".into(),
+            at: start,
+        };
+
+        assert!(!todo::is_rapid_repeat(None, "foo", start));
+        assert!(todo::is_rapid_repeat(Some(&previous), "foo", start));
+        assert!(todo::is_rapid_repeat(
+            Some(&previous),
+            "foo",
+            start + Duration::from_secs(9)
+        ));
+        assert!(!todo::is_rapid_repeat(
+            Some(&previous),
+            "foo",
+            start + Duration::from_secs(10)
+        ));
+        assert!(!todo::is_rapid_repeat(Some(&previous), "bar", start));
+    }
+
+    /// Verifies that undoing an add decrements the priority without removing the
+    /// item.
+    #[test]
+    fn undo_add() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "foo", 2);
+        add_item(&mut state, "foo", 3);
+
+        let response = send_command(TodoCommand::UndoAdd("foo".into()), &mut state);
+        assert_eq!(r#"Undone, the priority of "foo" is back to 2"#, response);
+
+        send_command(TodoCommand::UndoAdd("foo".into()), &mut state);
+        let response = send_command(TodoCommand::UndoAdd("foo".into()), &mut state);
+        assert_eq!(r#"Undone, the priority of "foo" is back to 1"#, response);
+        assert_eq!(1, state.items["foo"].priority);
+
+        let response = send_command(TodoCommand::UndoAdd("bar".into()), &mut state);
+        assert_eq!(r#"No item "bar" in your list"#, response);
+        assert!(!state.items.contains_key("bar"));
+    }
+
+    /// Verifies the category counts and bar widths shown by the tag cloud.
+    #[test]
+    fn tag_cloud() {
+        let mut state = TodoList::default();
+
+        let response = send_command(TodoCommand::TagCloud, &mut state);
+        assert_eq!("None of your items have a category yet", response);
+
+        for index in 0..4 {
+            add_with_category(&mut state, format!("foo {index}"), "Foo", 1);
+        }
+        for index in 0..2 {
+            add_with_category(&mut state, format!("bar {index}"), "Bar", 1);
+        }
+        add_with_category(&mut state, "baz", "Baz", 1);
+        add_with_category(&mut state, "qux", "Quux", 1);
+        add_item(&mut state, "uncategorized", 1);
+
+        assert_eq!(
+            vec![
+                ("Foo", 4, 20),
+                ("Bar", 2, 10),
+                ("Baz", 1, 5),
+                ("Quux", 1, 5)
+            ],
+            todo::tag_cloud_rows(&state),
+        );
+
+        let response = send_command(TodoCommand::TagCloud, &mut state);
+        assert_eq!(
+            format!(
+                "Categories for {USER_NAME}:\n\
+                ```\n\
+                Foo  ████████████████████ 4\n\
+                Bar  ██████████ 2\n\
+                Baz  █████ 1\n\
+                Quux █████ 1\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        // Tiny categories still get a visible bar.
+        let mut state = TodoList::default();
+        for index in 0..50 {
+            add_with_category(&mut state, format!("foo {index}"), "Foo", 1);
+        }
+        add_with_category(&mut state, "bar", "Bar", 1);
+        assert_eq!(
+            vec![("Foo", 50, 20), ("Bar", 1, 1)],
+            todo::tag_cloud_rows(&state)
+        );
+    }
+
+    /// Verifies that marking an item done moves it to the done category, if one
+    /// is set, and remembers the category it came from.
+    #[test]
+    fn done_category() {
+        let mut state = TodoList::default();
+        add_with_category(&mut state, "foo", "Foo", 1);
+        add_item(&mut state, "bar", 1);
+        add_item(&mut state, "baz", 1);
+
+        // Without a done category, items keep their category.
+        send_command(TodoCommand::Finish("baz".into()), &mut state);
+        assert_eq!(None, state.items["baz"].category);
+        assert_eq!(None, state.items["baz"].category_before_done);
+
+        let response = send_command(
+            TodoCommand::SetDoneCategory(Some("Archive".into())),
+            &mut state,
+        );
+        assert_eq!(
+            "Items will be moved to [Archive] when marked done",
+            response
+        );
+
+        send_command(TodoCommand::Finish("foo".into()), &mut state);
+        send_command(TodoCommand::Finish("bar".into()), &mut state);
+
+        let foo = &state.items["foo"];
+        assert_eq!(Some("Archive".into()), foo.category);
+        assert_eq!(Some("Foo".into()), foo.category_before_done);
+
+        let bar = &state.items["bar"];
+        assert_eq!(Some("Archive".into()), bar.category);
+        assert_eq!(None, bar.category_before_done);
+
+        // Finishing an item that's already done doesn't lose its old category.
+        send_command(TodoCommand::Finish("foo".into()), &mut state);
+        assert_eq!(Some("Foo".into()), state.items["foo"].category_before_done);
+
+        let response = send_command(TodoCommand::SetDoneCategory(None), &mut state);
+        assert_eq!("Items will keep their category when marked done", response);
+        assert_eq!(None, state.done_category);
+    }
+
+    /// Verifies that the workload sums the estimates of pending items, optionally
+    /// in a single category.
+    #[test]
+    fn workload() {
+        let mut state = TodoList::default();
+        add_with_category(&mut state, "foo", "Foo", 1);
+        add_with_category(&mut state, "bar", "Foo", 1);
+        add_item(&mut state, "baz", 1);
+        add_item(&mut state, "done", 1);
+        add_item(&mut state, "unestimated", 1);
+        send_command(TodoCommand::Finish("done".into()), &mut state);
+
+        let estimates = [
+            ("foo", Duration::from_secs(2 * 60 * 60)),
+            ("bar", Duration::from_secs(30 * 60)),
+            ("baz", Duration::from_secs(24 * 60 * 60)),
+            ("done", Duration::from_secs(60 * 60)),
+        ];
+        for (key, estimate) in estimates {
+            send_command(
+                TodoCommand::Estimate {
+                    key: key.into(),
+                    estimate: Some(estimate),
+                },
+                &mut state,
+            );
+        }
+        assert_eq!(
+            Some(Duration::from_secs(30 * 60)),
+            state.items["bar"].estimate
+        );
+
+        // Done items don't count towards the workload.
+        let response = send_command(TodoCommand::Workload { category: None }, &mut state);
+        assert_eq!(
+            "Estimated work: 1d 2h 30m across 3 items (1 pending items have no estimate)",
+            response,
+        );
+
+        let response = send_command(
+            TodoCommand::Workload {
+                category: Some("Foo".into()),
+            },
+            &mut state,
+        );
+        assert_eq!("Estimated work in [Foo]: 2h 30m across 2 items", response);
+
+        // Estimates can be cleared, and estimating a missing item is an error.
+        let response = send_command(
+            TodoCommand::Estimate {
+                key: "baz".into(),
+                estimate: None,
+            },
+            &mut state,
+        );
+        assert_eq!(r#"Cleared the estimate for "baz""#, response);
+        assert_eq!(
+            (Duration::from_secs(150 * 60), 2, 2),
+            todo::total_estimate(&state, None)
+        );
+
+        let response = send_command(
+            TodoCommand::Estimate {
+                key: "qux".into(),
+                estimate: Some(Duration::from_secs(60)),
+            },
+            &mut state,
+        );
+        assert_eq!(r#"No item "qux" in your list"#, response);
+    }
+
+    /// Verifies that huge estimates are rejected, and that ones that got into a
+    /// list anyway can't crash the workload or the save.
+    #[test]
+    fn oversized_estimates() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "bar", 1);
+
+        let huge = crate::duration::parse("20000000000000w").unwrap();
+        let response = send_command(
+            TodoCommand::Estimate {
+                key: "foo".into(),
+                estimate: Some(huge),
+            },
+            &mut state,
+        );
+        assert_eq!("Estimates can be at most 364d", response);
+        assert_eq!(None, state.items["foo"].estimate);
+
+        let response = send_command(
+            TodoCommand::Estimate {
+                key: "foo".into(),
+                estimate: Some(todo::MAX_ESTIMATE),
+            },
+            &mut state,
+        );
+        assert_eq!(r#"Estimated "foo" at 364d"#, response);
+
+        // Adding up estimates saturates instead of overflowing.
+        let max = Duration::from_secs(u64::MAX);
+        state.items.get_mut("foo").unwrap().estimate = Some(max);
+        state.items.get_mut("bar").unwrap().estimate = Some(max);
+        assert_eq!((Duration::MAX, 2, 0), todo::total_estimate(&state, None));
+        let response = send_command(TodoCommand::Workload { category: None }, &mut state);
+        assert!(response.starts_with("Estimated work: "));
+
+        // Estimates too large for BSON fail to save instead of panicking.
+        assert!(todo::list_update(&state).is_err());
+        state.items.get_mut("foo").unwrap().estimate = None;
+        state.items.get_mut("bar").unwrap().estimate = None;
+        assert!(todo::list_update(&state).is_ok());
+    }
+
+    /// Verifies that the inbox only contains pending, uncategorized items, and
+    /// that selecting a category from the inbox menus categorizes the right item.
+    #[test]
+    fn inbox() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "foo", 2);
+        add_item(&mut state, "bar", 1);
+        add_item(&mut state, "done", 1);
+        add_with_category(&mut state, "categorized", "Foo", 1);
+        add_with_category(&mut state, "other", "Bar", 1);
+        send_command(TodoCommand::Finish("done".into()), &mut state);
+
+        let keys = state.inbox_keys();
+        assert_eq!(vec!["foo", "bar"], keys);
+        let categories = state.categories();
+        assert_eq!(vec!["Bar", "Foo"], categories);
+        let inbox_command = |custom_id, values: &[String]| {
+            todo::inbox_command(custom_id, values, &keys, &categories)
+        };
+
+        // Selections from unknown menus, or for items or categories past the end,
+        // are ignored.
+        assert!(inbox_command("todo.reorder.item", &["1".into()]).is_none());
+        assert!(inbox_command("todo.inbox.2", &["1".into()]).is_none());
+        assert!(inbox_command("todo.inbox.1", &[]).is_none());
+        assert!(inbox_command("todo.inbox.1", &["2".into()]).is_none());
+        assert!(inbox_command("todo.inbox.1", &["Foo".into()]).is_none());
+
+        let command = inbox_command("todo.inbox.1", &["1".into()]).unwrap();
+        let response = send_command(command, &mut state);
+        assert_eq!(r#"Moved "bar" to [Foo]"#, response);
+        assert_eq!(Some("Foo".into()), state.items["bar"].category);
+        assert_eq!(1, state.items["bar"].priority);
+
+        assert_eq!(vec!["foo"], state.inbox_keys());
+
+        // Long categories are cut off in the menu, but items are still moved to the
+        // full category.
+        let long_category = "c".repeat(150);
+        add_with_category(&mut state, "long", &long_category, 1);
+        let keys = state.inbox_keys();
+        let categories = state.categories();
+        let index = categories
+            .iter()
+            .position(|category| *category == long_category)
+            .unwrap();
+        let values = [index.to_string()];
+        let command = todo::inbox_command("todo.inbox.0", &values, &keys, &categories).unwrap();
+        send_command(command, &mut state);
+        assert_eq!(Some(long_category), state.items["foo"].category);
+    }
+
+    /// Verifies that numbered reactions on a printed list refer to the item at
+    /// that position, and mark it done or not done.
+    #[test]
+    fn reaction_command() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "foo", 2);
+        add_item(&mut state, "bar", 1);
+
+        let keys = state.sorted_keys();
+        assert_eq!(vec!["foo", "bar"], keys);
+        let emoji = |emoji: &str| ReactionType::Unicode(emoji.into());
+
+        // Other emoji, and numbers past the end of the list, are ignored.
+        assert!(todo::reaction_command(&emoji("👍"), true, &keys).is_none());
+        assert!(todo::reaction_command(&emoji("3️⃣"), true, &keys).is_none());
+        let custom = ReactionType::Custom {
+            animated: false,
+            id: EmojiId(1),
+            name: Some("one".into()),
+        };
+        assert!(todo::reaction_command(&custom, true, &keys).is_none());
+
+        let command = todo::reaction_command(&emoji("2️⃣"), true, &keys).unwrap();
+        send_command(command, &mut state);
+        assert!(state.items["bar"].done);
+        assert!(!state.items["foo"].done);
+
+        let command = todo::reaction_command(&emoji("2️⃣"), false, &keys).unwrap();
+        send_command(command, &mut state);
+        assert!(!state.items["bar"].done);
+    }
+
+    /// Verifies that reactions on several items in a row are all applied and
+    /// saved, since they aren't subject to the cooldown.
+    #[test]
+    fn reaction_toggles() {
+        let store = FakeStore::default();
+        let cache = ListCache::new(CACHE_TTL);
+        let mut author = User::default();
+        author.id = UserId(1);
+        author.name = USER_NAME.into();
+
+        let mut state = TodoList::new(author.id);
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "bar", 1);
+        store.lists.borrow_mut().insert(author.id, state);
+
+        let apply = |command| {
+            let max_items = todo::DEFAULT_MAX_ITEMS;
+            let reaction =
+                todo::apply_reaction(&store, &cache, &author, command, max_items, Utc::now());
+            block_on(reaction).unwrap()
+        };
+        assert!(apply(TodoCommand::Finish("foo".into())));
+        assert!(apply(TodoCommand::Finish("bar".into())));
+        assert!(!apply(TodoCommand::Finish("bar".into())));
+
+        let saved = store.lists.borrow()[&author.id].clone();
+        assert!(saved.items["foo"].done);
+        assert!(saved.items["bar"].done);
+        assert_eq!(Some(saved), cache.get(&author.id, Instant::now()));
+
+        assert!(apply(TodoCommand::Unfinish("foo".into())));
+        assert!(!store.lists.borrow()[&author.id].items["foo"].done);
+        assert!(store.lists.borrow()[&author.id].items["bar"].done);
+    }
+
+    /// Verifies that renaming an item keeps its priority, done state, and category.
+    #[test]
+    fn rename_preserves_item() {
+        let mut state = TodoList::default();
+        add_with_category(&mut state, "fix biuld", "Foo", 1);
+        add_with_category(&mut state, "fix biuld", "Foo", 2);
+        add_with_category(&mut state, "fix biuld", "Foo", 3);
+        send_command(TodoCommand::Finish("fix biuld".into()), &mut state);
+        let old = state.items["fix biuld"].clone();
+
+        let response = send_command(
+            TodoCommand::Rename {
+                from: "fix biuld".into(),
+                to: "fix build".into(),
+            },
+            &mut state,
+        );
+        assert_eq!(r#"Renamed "fix biuld" to "fix build""#, response);
+        assert!(!state.items.contains_key("fix biuld"));
+        assert_eq!(old, state.items["fix build"]);
+
+        // Renaming an item that doesn't exist is an error.
+        let response = send_command(
+            TodoCommand::Rename {
+                from: "fix biuld".into(),
+                to: "foo".into(),
+            },
+            &mut state,
+        );
+        assert_eq!(r#"No item "fix biuld" in your list"#, response);
+        assert_eq!(vec!["fix build"], state.sorted_keys());
+    }
+
+    /// Verifies that renaming an item to an existing key doesn't overwrite the
+    /// existing item.
+    #[test]
+    fn rename_collision() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "foo", 2);
+        add_item(&mut state, "bar", 1);
+
+        let response = send_command(
+            TodoCommand::Rename {
+                from: "foo".into(),
+                to: "bar".into(),
+            },
+            &mut state,
+        );
+        assert_eq!(r#"There's already an item "bar" in your list"#, response);
+        assert_eq!(2, state.items["foo"].priority);
+        assert_eq!(1, state.items["bar"].priority);
+    }
+
+    /// Verifies that an explicit priority sets the item's priority directly, and
+    /// that very large priorities still line up when printed.
+    #[test]
+    fn explicit_priority() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "foo", 2);
+
+        let response = send_command(
+            TodoCommand::Add {
+                key: "bar".into(),
+                category: None,
+                priority: Some(50),
+            },
+            &mut state,
+        );
+        assert_eq!(r#"Set priority of "bar" to 50"#, response);
+        assert_eq!(50, state.items["bar"].priority);
+
+        // Adding without a priority goes back to bumping by 1.
+        add_item(&mut state, "bar", 51);
+
+        // Priorities can also be lowered.
+        let response = send_command(
+            TodoCommand::Add {
+                key: "bar".into(),
+                category: Some("Bar".into()),
+                priority: Some(1),
+            },
+            &mut state,
+        );
+        assert_eq!(r#"Set priority of [Bar] "bar" to 1"#, response);
+
+        send_command(
+            TodoCommand::Add {
+                key: "baz".into(),
+                category: None,
+                priority: Some(u32::MAX),
+            },
+            &mut state,
+        );
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (4294967295) [ ] baz\n\
+                (         2) [ ] foo\n\
+                (         1) [ ] [Bar] bar\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        // Bumping an item that's already at the highest priority leaves it there.
+        add_item(&mut state, "baz", u32::MAX);
+        assert_eq!(u32::MAX, state.items["baz"].priority);
+    }
+
+    /// Verifies that `!todo clear` removes only the completed items.
+    #[test]
+    fn clear_done() {
+        let mut state = TodoList::default();
+
+        let response = send_command(TodoCommand::ClearDone, &mut state);
+        assert_eq!("No completed items to clear", response);
+
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "bar", 1);
+        add_item(&mut state, "baz", 1);
+        add_item(&mut state, "qux", 1);
+        send_command(TodoCommand::Finish("foo".into()), &mut state);
+        send_command(TodoCommand::Finish("baz".into()), &mut state);
+
+        let response = send_command(TodoCommand::ClearDone, &mut state);
+        assert_eq!("Cleared 2 completed items", response);
+
+        let mut keys = state.items.keys().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(vec!["bar", "qux"], keys);
+
+        // Clearing again is a no-op.
+        let response = send_command(TodoCommand::ClearDone, &mut state);
+        assert_eq!("No completed items to clear", response);
+        assert_eq!(2, state.items.len());
+    }
+
+    /// Verifies that done items can be marked as not done again.
+    #[test]
+    fn unfinish() {
+        let mut state = TodoList::default();
+        add_with_category(&mut state, "foo", "Foo", 1);
+        add_item(&mut state, "bar", 1);
+
+        send_command(TodoCommand::Finish("foo".into()), &mut state);
+        let response = send_command(TodoCommand::Unfinish("foo".into()), &mut state);
+        assert_eq!(r#"Marked "foo" as not done"#, response);
+        assert!(!state.items["foo"].done);
+        assert_eq!(Some("Foo".into()), state.items["foo"].category);
+
+        // Items moved to the done category go back to their old category.
+        send_command(
+            TodoCommand::SetDoneCategory(Some("Archive".into())),
+            &mut state,
+        );
+        send_command(TodoCommand::Finish("foo".into()), &mut state);
+        send_command(TodoCommand::Finish("bar".into()), &mut state);
+        send_command(TodoCommand::Unfinish("foo".into()), &mut state);
+        send_command(TodoCommand::Unfinish("bar".into()), &mut state);
+
+        let foo = &state.items["foo"];
+        assert!(!foo.done);
+        assert_eq!(Some("Foo".into()), foo.category);
+        assert_eq!(None, foo.category_before_done);
+
+        let bar = &state.items["bar"];
+        assert!(!bar.done);
+        assert_eq!(None, bar.category);
+
+        // Reopening an item that isn't done leaves it unchanged.
+        send_command(TodoCommand::Unfinish("foo".into()), &mut state);
+        assert_eq!(Some("Foo".into()), state.items["foo"].category);
+    }
+
+    /// Verifies that reopening an unknown item doesn't create a new item.
+    #[test]
+    fn unfinish_missing_item() {
+        let mut state = TodoList::default();
+
+        let response = send_command(TodoCommand::Unfinish("foo".into()), &mut state);
+        assert_eq!(r#"No item "foo" in your list"#, response);
+        assert!(state.items.is_empty());
+    }
+
+    /// Verifies that finishing an unknown item doesn't create a new item.
+    #[test]
+    fn finish_missing_item() {
+        let mut state = TodoList::default();
+
+        let response = send_command(TodoCommand::Finish("foo".into()), &mut state);
+        assert_eq!(r#"No item "foo" in your list"#, response);
+        assert!(state.items.is_empty());
+    }
+
+    /// Verifies that due dates can be given as dates, relative durations, or
+    /// `today`/`tomorrow`.
+    #[test]
+    fn parse_due() {
+        let now = Utc.with_ymd_and_hms(2023, 6, 15, 12, 30, 0).unwrap();
+
+        assert_eq!(
+            Utc.with_ymd_and_hms(2023, 7, 1, 23, 59, 59).unwrap(),
+            todo::parse_due("2023-07-01", now).unwrap(),
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2023, 6, 15, 23, 59, 59).unwrap(),
+            todo::parse_due("today", now).unwrap(),
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2023, 6, 16, 23, 59, 59).unwrap(),
+            todo::parse_due(" Tomorrow ", now).unwrap(),
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2023, 6, 18, 12, 30, 0).unwrap(),
+            todo::parse_due("3d", now).unwrap(),
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2023, 6, 15, 14, 30, 0).unwrap(),
+            todo::parse_due("2h", now).unwrap(),
+        );
+
+        assert!(todo::parse_due("", now).is_err());
+        assert!(todo::parse_due("2023-13-01", now).is_err());
+        assert!(todo::parse_due("next week", now).is_err());
+        assert!(todo::parse_due("9999999999999999w", now).is_err());
+    }
+
+    /// Verifies that due dates are shown in the list and that overdue items are
+    /// marked.
+    #[test]
+    fn due_dates() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "foo", 2);
+        add_item(&mut state, "foo", 3);
+        add_item(&mut state, "bar", 1);
+        add_item(&mut state, "bar", 2);
+        add_item(&mut state, "baz", 1);
+
+        let past = Utc.with_ymd_and_hms(2000, 1, 1, 23, 59, 59).unwrap();
+        let future = Utc.with_ymd_and_hms(2999, 1, 1, 23, 59, 59).unwrap();
+
+        let response = send_command(
+            TodoCommand::Due {
+                key: "foo".into(),
+                due: Some(past),
+            },
+            &mut state,
+        );
+        assert_eq!(r#""foo" is due 2000-01-01"#, response);
+        send_command(
+            TodoCommand::Due {
+                key: "bar".into(),
+                due: Some(future),
+            },
+            &mut state,
+        );
+
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (3) [ ] foo (due 2000-01-01) ⚠\n\
+                (2) [ ] bar (due 2999-01-01)\n\
+                (1) [ ] baz\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        // Done items aren't overdue.
+        send_command(TodoCommand::Finish("foo".into()), &mut state);
+        assert_eq!(
+            "(3) [X] foo (due 2000-01-01)",
+            state.items["foo"].display_line("foo", true, PriorityStyle::Numeric, 1, Utc::now()),
+        );
+
+        let response = send_command(
+            TodoCommand::Due {
+                key: "foo".into(),
+                due: None,
+            },
+            &mut state,
+        );
+        assert_eq!(r#"Cleared the due date for "foo""#, response);
+        assert_eq!(None, state.items["foo"].due);
+
+        let response = send_command(
+            TodoCommand::Due {
+                key: "missing".into(),
+                due: Some(future),
+            },
+            &mut state,
+        );
+        assert_eq!(r#"No item "missing" in your list"#, response);
+    }
+
+    /// Verifies that due dates survive a round trip through BSON, and that items
+    /// saved before due dates existed load without one.
+    #[test]
+    fn due_date_serialization() {
+        let item = TodoItem {
+            priority: 1,
+            due: Some(Utc.with_ymd_and_hms(2023, 7, 1, 23, 59, 59).unwrap()),
+            ..Default::default()
+        };
+        let document = bson::to_document(&item).unwrap();
+        assert_eq!(item, bson::from_document(document).unwrap());
+
+        let old_item = doc! { "priority": 1, "done": false, "category": null };
+        let item: TodoItem = bson::from_document(old_item).unwrap();
+        assert_eq!(None, item.due);
+    }
+
+    /// Verifies that snoozed items are hidden from the list until their snooze
+    /// ends, unless all items are requested.
+    #[test]
+    fn snooze() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "foo", 2);
+        add_item(&mut state, "bar", 1);
+        add_item(&mut state, "baz", 1);
+
+        let past = Utc.with_ymd_and_hms(2000, 1, 1, 12, 0, 0).unwrap();
+        let future = Utc.with_ymd_and_hms(2999, 1, 1, 12, 0, 0).unwrap();
+
+        let snooze = |key: &str, until| TodoCommand::Snooze {
+            key: key.into(),
+            until,
+        };
+        let response = send_command(snooze("foo", Some(future)), &mut state);
+        assert_eq!(r#"Snoozed "foo" until 2999-01-01 12:00 UTC"#, response);
+        send_command(snooze("bar", Some(past)), &mut state);
+
+        let print = |all| TodoCommand::Print {
+            category: None,
+            sort: SortMode::Priority,
+            all,
+        };
+        let response = send_command(print(false), &mut state);
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (1) [ ] bar\n\
+                (1) [ ] baz\n\
+                ```\n\
+                1 snoozed item(s) hidden, use `!todo show --all` to see them"
+            ),
+            response,
+        );
+
+        let response = send_command(print(true), &mut state);
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (2) [ ] foo\n\
+                (1) [ ] bar\n\
+                (1) [ ] baz\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        let response = send_command(TodoCommand::PrintOne("foo".into()), &mut state);
+        assert!(response.contains("Snoozed:  until 2999-01-01 12:00 UTC\n"));
+        let response = send_command(TodoCommand::PrintOne("bar".into()), &mut state);
+        assert!(!response.contains("Snoozed"));
+
+        let response = send_command(snooze("foo", None), &mut state);
+        assert_eq!(r#""foo" is no longer snoozed"#, response);
+        assert!(!state.items["foo"].is_snoozed(Utc::now()));
+
+        let response = send_command(snooze("missing", Some(future)), &mut state);
+        assert_eq!(r#"No item "missing" in your list"#, response);
+
+        let now = Utc.with_ymd_and_hms(2023, 6, 15, 12, 30, 0).unwrap();
+        assert_eq!(
+            Utc.with_ymd_and_hms(2023, 6, 18, 14, 30, 0).unwrap(),
+            todo::parse_snooze("3d 2h", now).unwrap(),
+        );
+        assert!(todo::parse_snooze("tomorrow", now).is_err());
+        assert!(todo::parse_snooze("9999999999999999w", now).is_err());
+    }
+
+    /// Verifies the next due date for each kind of repeating item, including
+    /// months that are shorter than the current one.
+    #[test]
+    fn next_occurrence() {
+        let date = |y, m, d| Utc.with_ymd_and_hms(y, m, d, 23, 59, 59).unwrap();
+
+        assert_eq!(
+            date(2023, 7, 1),
+            todo::next_occurrence(date(2023, 6, 30), Recurrence::Daily),
+        );
+        assert_eq!(
+            date(2024, 1, 1),
+            todo::next_occurrence(date(2023, 12, 31), Recurrence::Daily),
+        );
+        assert_eq!(
+            date(2023, 7, 5),
+            todo::next_occurrence(date(2023, 6, 28), Recurrence::Weekly),
+        );
+        assert_eq!(
+            date(2023, 7, 15),
+            todo::next_occurrence(date(2023, 6, 15), Recurrence::Monthly),
+        );
+        assert_eq!(
+            date(2024, 1, 31),
+            todo::next_occurrence(date(2023, 12, 31), Recurrence::Monthly),
+        );
+
+        // Days past the end of the next month are clamped to its last day.
+        assert_eq!(
+            date(2023, 2, 28),
+            todo::next_occurrence(date(2023, 1, 31), Recurrence::Monthly),
+        );
+        assert_eq!(
+            date(2024, 2, 29),
+            todo::next_occurrence(date(2024, 1, 31), Recurrence::Monthly),
+        );
+        assert_eq!(
+            date(2023, 6, 30),
+            todo::next_occurrence(date(2023, 5, 31), Recurrence::Monthly),
+        );
+    }
+
+    /// Verifies that finishing a repeating item moves its due date forward instead
+    /// of marking it done.
+    #[test]
+    fn recurring_items() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "water plants", 1);
+        add_item(&mut state, "rent", 1);
+        state.done_category = Some("Done".into());
+
+        let recur = |key: &str, recurrence| TodoCommand::Recur {
+            key: key.into(),
+            recurrence,
+        };
+        let response = send_command(recur("water plants", Some(Recurrence::Weekly)), &mut state);
+        assert_eq!(r#""water plants" now repeats weekly"#, response);
+        send_command(recur("rent", Some(Recurrence::Monthly)), &mut state);
+
+        let due = Utc::now() + chrono::Duration::days(2);
+        state.items.get_mut("water plants").unwrap().due = Some(due);
+
+        let response = send_command(TodoCommand::Finish("water plants".into()), &mut state);
+        let next = due + chrono::Duration::weeks(1);
+        assert_eq!(
+            format!(
+                r#"Marked "water plants" as done, it's due again {}"#,
+                next.format("%Y-%m-%d")
+            ),
+            response,
+        );
+        let item = &state.items["water plants"];
+        assert!(!item.done);
+        assert_eq!(None, item.category);
+        assert_eq!(Some(next), item.due);
+
+        // Items that are overdue, or have no due date, are next due relative to now.
+        state.items.get_mut("rent").unwrap().due =
+            Some(Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap());
+        let before = Utc::now();
+        send_command(TodoCommand::Finish("rent".into()), &mut state);
+        let due = state.items["rent"].due.unwrap();
+        assert!(due > before + chrono::Duration::days(27));
+        assert!(!state.items["rent"].done);
+
+        let response = send_command(TodoCommand::PrintOne("rent".into()), &mut state);
+        assert!(response.contains("\nRepeats:  monthly\n"));
+
+        // Once an item stops repeating it can be finished as usual.
+        let response = send_command(recur("rent", None), &mut state);
+        assert_eq!(r#""rent" no longer repeats"#, response);
+        send_command(TodoCommand::Finish("rent".into()), &mut state);
+        assert!(state.items["rent"].done);
+
+        let response = send_command(recur("missing", None), &mut state);
+        assert_eq!(r#"No item "missing" in your list"#, response);
+    }
+
+    /// Verifies that the list can be sorted by due date, with undated items last.
+    #[test]
+    fn sort_by_due_date() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "undated high", 1);
+        add_item(&mut state, "undated high", 2);
+        add_item(&mut state, "undated high", 3);
+        add_item(&mut state, "undated low", 1);
+        add_item(&mut state, "later", 1);
+        add_item(&mut state, "later", 2);
+        add_item(&mut state, "sooner", 1);
+        add_item(&mut state, "sooner twin", 1);
+        add_item(&mut state, "sooner twin", 2);
+
+        let sooner = Utc.with_ymd_and_hms(2999, 1, 1, 23, 59, 59).unwrap();
+        let later = Utc.with_ymd_and_hms(2999, 6, 1, 23, 59, 59).unwrap();
+        for (key, due) in [
+            ("sooner", sooner),
+            ("sooner twin", sooner),
+            ("later", later),
+        ] {
+            send_command(
+                TodoCommand::Due {
+                    key: key.into(),
+                    due: Some(due),
+                },
+                &mut state,
+            );
+        }
+
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Due,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (2) [ ] sooner twin (due 2999-01-01)\n\
+                (1) [ ] sooner (due 2999-01-01)\n\
+                (2) [ ] later (due 2999-06-01)\n\
+                (3) [ ] undated high\n\
+                (1) [ ] undated low\n\
+                ```\n"
+            ),
+            response,
+        );
+    }
+
+    /// Verifies that notes can be attached to items and are only indicated in
+    /// the list view.
+    #[test]
+    fn notes() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "foo", 2);
+        add_item(&mut state, "bar", 1);
+
+        let response = send_command(
+            TodoCommand::Note {
+                key: "foo".into(),
+                text: Some("Remember to check the logs first".into()),
+            },
+            &mut state,
+        );
+        assert_eq!(r#"Updated the note for "foo""#, response);
+        assert_eq!(
+            Some("Remember to check the logs first"),
+            state.items["foo"].note.as_deref()
+        );
+
+        let response = send_command(
+            TodoCommand::Print {
+                category: None,
+                sort: SortMode::Priority,
+                all: false,
+            },
+            &mut state,
+        );
+        assert_eq!(
+            format!(
+                "TODO list for {USER_NAME}:\n\
+                ```\n\
+                (2) [ ] foo *\n\
+                (1) [ ] bar\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        let response = send_command(
+            TodoCommand::Note {
+                key: "foo".into(),
+                text: None,
+            },
+            &mut state,
+        );
+        assert_eq!(r#"Removed the note from "foo""#, response);
+        assert_eq!(None, state.items["foo"].note);
+
+        // Notes can't be added to missing items.
+        let response = send_command(
+            TodoCommand::Note {
+                key: "baz".into(),
+                text: Some("Nope".into()),
+            },
+            &mut state,
+        );
+        assert_eq!(r#"No item "baz" in your list"#, response);
+        assert!(!state.items.contains_key("baz"));
+    }
+
+    /// Verifies that notes survive a round trip through BSON, and that items
+    /// saved before notes existed load without one.
+    #[test]
+    fn note_serialization() {
+        let item = TodoItem {
+            priority: 1,
+            note: Some("Some details".into()),
+            ..Default::default()
+        };
+        let document = bson::to_document(&item).unwrap();
+        assert_eq!(item, bson::from_document(document).unwrap());
+
+        let old_item = doc! { "priority": 1, "done": false, "category": null };
+        let item: TodoItem = bson::from_document(old_item).unwrap();
+        assert_eq!(None, item.note);
+    }
+
+    /// Verifies that a single item's details can be printed.
+    #[test]
+    fn print_one() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_with_category(&mut state, "bar", "Bar", 1);
+
+        // Items from before creation times were tracked don't show one.
+        state.items.get_mut("foo").unwrap().created_at = None;
+        let created_at = Utc::now() - chrono::Duration::hours(3 * 24 + 5);
+        state.items.get_mut("bar").unwrap().created_at = Some(created_at);
+
+        let response = send_command(TodoCommand::PrintOne("foo".into()), &mut state);
+        assert_eq!(
+            format!(
+                "\"foo\" in {USER_NAME}'s TODO list:\n\
+                ```\n\
+                Priority: 1\n\
+                Status:   Not done\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        send_command(TodoCommand::Finish("bar".into()), &mut state);
+        send_command(
+            TodoCommand::Estimate {
+                key: "bar".into(),
+                estimate: Some(Duration::from_secs(90 * 60)),
+            },
+            &mut state,
+        );
+        send_command(
+            TodoCommand::Due {
+                key: "bar".into(),
+                due: Some(Utc.with_ymd_and_hms(2000, 1, 1, 23, 59, 59).unwrap()),
+            },
+            &mut state,
+        );
+        send_command(
+            TodoCommand::Note {
+                key: "bar".into(),
+                text: Some("Ask about the deadline".into()),
+            },
+            &mut state,
+        );
+
+        let response = send_command(TodoCommand::PrintOne("bar".into()), &mut state);
+        assert_eq!(
+            format!(
+                "\"bar\" in {USER_NAME}'s TODO list:\n\
+                ```\n\
+                Priority: 1\n\
+                Status:   Done\n\
+                Category: Bar\n\
+                Estimate: 1h 30m\n\
+                Due:      2000-01-01\n\
+                Added:    {} (3d ago)\n\
+                \n\
+                Ask about the deadline\n\
+                ```\n",
+                created_at.format("%Y-%m-%d"),
+            ),
+            response,
+        );
+
+        // Pending items past their due date are marked as overdue.
+        send_command(TodoCommand::Unfinish("bar".into()), &mut state);
+        let response = send_command(TodoCommand::PrintOne("bar".into()), &mut state);
+        assert!(response.contains("Due:      2000-01-01 ⚠ overdue\n"));
+
+        let response = send_command(TodoCommand::PrintOne("baz".into()), &mut state);
+        assert_eq!(r#"No item "baz" in your list"#, response);
+    }
+
+    /// Verifies that an item's creation time is set when it's first added, and not
+    /// changed when it's bumped.
+    #[test]
+    fn created_at() {
+        let mut state = TodoList::default();
+        let before = Utc::now();
+        add_item(&mut state, "foo", 1);
+        let created_at = state.items["foo"].created_at.unwrap();
+        assert!(created_at >= before && created_at <= Utc::now());
+
+        add_item(&mut state, "foo", 2);
+        send_command(
+            TodoCommand::Add {
+                key: "foo".into(),
+                category: None,
+                priority: Some(10),
+            },
+            &mut state,
+        );
+        assert_eq!(Some(created_at), state.items["foo"].created_at);
+
+        assert_eq!("0m", todo::format_age(Duration::from_secs(59)));
+        assert_eq!(
+            "4h 12m",
+            todo::format_age(Duration::from_secs(4 * 3600 + 12 * 60 + 5))
+        );
+        assert_eq!(
+            "3d",
+            todo::format_age(Duration::from_secs(3 * 86400 + 5 * 3600))
+        );
+    }
+
+    /// Verifies that commands are only throttled within the cooldown window.
+    #[test]
+    fn cooldown_remaining() {
+        let cooldown = Duration::from_secs(2);
+        let last_used = Instant::now();
+
+        // Users who haven't run a command yet aren't throttled.
+        assert_eq!(None, todo::cooldown_remaining(None, last_used, cooldown));
+
+        assert_eq!(
+            Some(cooldown),
+            todo::cooldown_remaining(Some(&last_used), last_used, cooldown),
+        );
+        assert_eq!(
+            Some(Duration::from_millis(500)),
+            todo::cooldown_remaining(
+                Some(&last_used),
+                last_used + Duration::from_millis(1500),
+                cooldown,
+            ),
+        );
+        assert_eq!(
+            None,
+            todo::cooldown_remaining(Some(&last_used), last_used + cooldown, cooldown),
+        );
+        assert_eq!(
+            None,
+            todo::cooldown_remaining(
+                Some(&last_used),
+                last_used + Duration::from_secs(60),
+                cooldown,
+            ),
+        );
+
+        // A zero cooldown turns throttling off.
+        assert_eq!(
+            None,
+            todo::cooldown_remaining(Some(&last_used), last_used, Duration::ZERO),
+        );
+
+        // Only commands that change the list are throttled.
+        assert!(TodoCommand::Stats.is_read_only());
+        assert!(!TodoCommand::Remove("foo".into()).is_read_only());
+    }
+
+    /// Verifies that adds rejected by the cooldown or the item limit aren't
+    /// remembered, so they don't hide a rapid repeat of the add before them.
+    #[test]
+    fn rejected_add() {
+        let cooldown = Duration::from_secs(2);
+        let user_id = UserId(1);
+        let start = Instant::now();
+        let mut user = User::default();
+        user.name = USER_NAME.into();
+        let mut state = TodoList::new(user_id);
+        let mut last_used = HashMap::new();
+        let mut recent_adds = HashMap::new();
+
+        // Mirrors `add_item`, returning whether the add ran and was a rapid repeat.
+        let mut add = |key: &str, now: Instant| {
+            if todo::start_cooldown(&mut last_used, user_id, now, cooldown).is_some() {
+                return None;
+            }
+            let rapid_repeat = todo::is_rapid_repeat(recent_adds.get(&user_id), key, now);
+            let command = TodoCommand::Add {
+                key: key.into(),
+                category: None,
+                priority: None,
+            };
+            let (_, changed) =
+                todo::handle_command_tracked(command, &mut state, &user, 1, Utc::now());
+            if !changed {
+                return None;
+            }
+            recent_adds.insert(
+                user_id,
+                RecentAdd {
+                    key: key.into(),
+                    at: now,
+                },
+            );
+            Some(rapid_repeat)
+        };
+
+        assert_eq!(Some(false), add("foo", start));
+        assert_eq!(None, add("bar", start + Duration::from_secs(1)));
+        assert_eq!(None, add("bar", start + Duration::from_secs(3)));
+        assert_eq!(Some(true), add("foo", start + Duration::from_secs(6)));
+    }
+
+    /// Verifies that exported JSON includes every item field and can be read back
+    /// into the same items.
+    #[test]
+    fn export() {
+        let mut state = TodoList::default();
+        add_with_category(&mut state, "foo", "Foo", 1);
+        add_item(&mut state, "bar", 1);
+        add_item(&mut state, "baz", 1);
+        send_command(TodoCommand::Finish("bar".into()), &mut state);
+        send_command(TodoCommand::Remove("baz".into()), &mut state);
+        send_command(
+            TodoCommand::Estimate {
+                key: "foo".into(),
+                estimate: Some(Duration::from_secs(90 * 60)),
+            },
+            &mut state,
+        );
+        send_command(
+            TodoCommand::Due {
+                key: "foo".into(),
+                due: Some(Utc.with_ymd_and_hms(2000, 1, 1, 23, 59, 59).unwrap()),
+            },
+            &mut state,
+        );
+        send_command(
+            TodoCommand::Note {
+                key: "foo".into(),
+                text: Some("Ask about the deadline".into()),
+            },
+            &mut state,
+        );
+
+        let json = todo::export_json(&state);
+        let items: HashMap<String, TodoItem> = serde_json::from_str(&json).unwrap();
+        assert_eq!(state.items, items);
+
+        let response = send_command(TodoCommand::Export, &mut state);
+        assert_eq!(format!("```json\n{json}\n```\n"), response);
+    }
+
+    /// Verifies that importing keeps whichever copy of an item has the higher
+    /// priority.
+    #[test]
+    fn import_merge() {
+        let add = |state: &mut TodoList, key: &str, category: &str, priority| {
+            let command = TodoCommand::Add {
+                key: key.into(),
+                category: Some(category.into()),
+                priority: Some(priority),
+            };
+            send_command(command, state);
+        };
+
+        let mut state = TodoList::default();
+        add(&mut state, "foo", "Old", 5);
+        add(&mut state, "bar", "Old", 1);
+
+        let mut imported = TodoList::default();
+        add(&mut imported, "foo", "New", 2);
+        add(&mut imported, "bar", "New", 3);
+        add_item(&mut imported, "baz", 1);
+
+        let items = todo::parse_import(&todo::export_json(&imported)).unwrap();
+        let response = send_command(TodoCommand::Merge(items), &mut state);
+        assert_eq!(
+            "Imported 3 items: 1 added, 1 updated, 1 unchanged",
+            response
+        );
+
+        assert_eq!(5, state.items["foo"].priority);
+        assert_eq!(Some("Old"), state.items["foo"].category.as_deref());
+        assert_eq!(3, state.items["bar"].priority);
+        assert_eq!(Some("New"), state.items["bar"].category.as_deref());
+        assert_eq!(vec!["foo", "bar", "baz"], state.sorted_keys());
+    }
+
+    /// Verifies that importing with `--replace` wipes the existing list, and that
+    /// bad JSON is reported rather than imported.
+    #[test]
+    fn import_replace() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "bar", 1);
+
+        let mut imported = TodoList::default();
+        add_item(&mut imported, "baz", 1);
+
+        // The export message can be pasted straight back in.
+        let export = send_command(TodoCommand::Export, &mut imported);
+        let items = todo::parse_import(&export).unwrap();
+        send_command(TodoCommand::Replace(items), &mut state);
+        assert_eq!(vec!["baz"], state.sorted_keys());
+
+        // Replacing the list can't go over the item limit.
+        let mut user = User::default();
+        user.name = USER_NAME.into();
+        let items = HashMap::from([
+            ("a".to_string(), TodoItem::default()),
+            ("b".to_string(), TodoItem::default()),
+            ("c".to_string(), TodoItem::default()),
+        ]);
+        let command = TodoCommand::Replace(items);
+        let response = todo::handle_command(command, &mut state, &user, 2, Utc::now());
+        assert_eq!(
+            "That would give you 3 items, but the maximum is 2",
+            response
+        );
+        assert_eq!(vec!["baz"], state.sorted_keys());
+
+        let error = todo::parse_import(r#"{"foo": {"priority": "high"}}"#).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Couldn't read the imported list: invalid type"));
+        assert!(todo::parse_import("not json").is_err());
+    }
+
+    /// Verifies that keys differing only in case collapse to one item once the user
+    /// turns on case-insensitive matching.
+    #[test]
+    fn ignore_key_case() {
+        let mut state = TodoList::default();
+
+        // By default keys must match exactly.
+        add_item(&mut state, "Fix Build", 1);
+        add_item(&mut state, "fix build", 1);
+        assert_eq!(vec!["Fix Build", "fix build"], state.sorted_keys());
+        send_command(TodoCommand::Remove("fix build".into()), &mut state);
+
+        let response = send_command(TodoCommand::SetIgnoreKeyCase(true), &mut state);
+        assert_eq!("Keys will match your items ignoring case", response);
+
+        // The existing item is updated, keeping its original casing.
+        add_item(&mut state, "Fix Build", 2);
+        let response = send_command(
+            TodoCommand::Add {
+                key: "  fix build ".into(),
+                category: None,
+                priority: None,
+            },
+            &mut state,
+        );
+        assert_eq!(r#"Updated item "Fix Build", priority is 3"#, response);
+        assert_eq!(vec!["Fix Build"], state.sorted_keys());
+
+        let response = send_command(TodoCommand::Finish("FIX BUILD".into()), &mut state);
+        assert_eq!(r#"Marked "Fix Build" as done"#, response);
+        send_command(TodoCommand::Unfinish("fix build".into()), &mut state);
+        assert!(!state.items["Fix Build"].done);
+
+        let response = send_command(TodoCommand::Remove("fix build".into()), &mut state);
+        assert_eq!(r#"Removed "Fix Build" from your list"#, response);
+        assert!(state.sorted_keys().is_empty());
+
+        // New keys are trimmed, but otherwise kept as typed.
+        add_item(&mut state, "Ship It", 1);
+        let response = send_command(
+            TodoCommand::Add {
+                key: " New Item ".into(),
+                category: None,
+                priority: None,
+            },
+            &mut state,
+        );
+        assert_eq!(r#"Added item "New Item" to your list"#, response);
+
+        assert_eq!("fix build", todo::normalize_key(" Fix Build\t"));
+    }
+
+    /// Verifies that the embed version of the list has a field per category, with
+    /// done items struck through.
+    #[test]
+    fn list_embed() {
+        let mut state = TodoList::default();
+        add_with_category(&mut state, "foo", "Foo", 1);
+        add_with_category(&mut state, "foo", "Foo", 2);
+        add_with_category(&mut state, "bar", "Foo", 1);
+        add_item(&mut state, "baz", 1);
+        add_item(&mut state, "baz", 2);
+        add_item(&mut state, "baz", 3);
+        send_command(TodoCommand::Finish("bar".into()), &mut state);
+
+        let embed = todo::list_embed(&state, USER_NAME, None, SortMode::Priority, false);
+        assert_eq!(
+            json!({
+                "title": format!("TODO list for {USER_NAME}"),
+                "type": "rich",
+                "color": Colour::DARK_GREEN.0,
+                "fields": [
+                    { "name": "Uncategorized", "value": "(3) baz\n", "inline": false },
+                    { "name": "Foo", "value": "(2) foo\n~~(1) bar~~\n", "inline": false },
+                ],
+            }),
+            json!(embed.0),
+        );
+
+        let embed = todo::list_embed(&state, USER_NAME, Some("Foo"), SortMode::Priority, false);
+        assert_eq!(
+            json!({
+                "title": format!("TODO list for {USER_NAME} in category [Foo]"),
+                "type": "rich",
+                "color": Colour::DARK_GREEN.0,
+                "fields": [
+                    { "name": "Foo", "value": "(2) foo\n~~(1) bar~~\n", "inline": false },
+                ],
+            }),
+            json!(embed.0),
+        );
+    }
+
+    /// Verifies that items can be found by their key, category, or note.
+    #[test]
+    fn search() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "Fix build", 1);
+        add_item(&mut state, "Fix build", 2);
+        add_with_category(&mut state, "update docs", "Build", 1);
+        add_item(&mut state, "buy milk", 1);
+        send_command(
+            TodoCommand::Note {
+                key: "buy milk".into(),
+                text: Some("The build shop is closed".into()),
+            },
+            &mut state,
+        );
+        add_item(&mut state, "walk dog", 1);
+
+        let response = send_command(TodoCommand::Search("BUILD".into()), &mut state);
+        assert_eq!(
+            format!(
+                "TODO items matching \"BUILD\":\n\
+                ```\n\
+                (2) [ ] Fix build\n\
+                (1) [ ] buy milk *\n\
+                (1) [ ] [Build] update docs\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        let response = send_command(TodoCommand::Search("dog".into()), &mut state);
+        assert_eq!(
+            format!(
+                "TODO items matching \"dog\":\n\
+                ```\n\
+                (1) [ ] walk dog\n\
+                ```\n"
+            ),
+            response,
+        );
+
+        let response = send_command(TodoCommand::Search("cat".into()), &mut state);
+        assert_eq!(r#"No items matched "cat""#, response);
+    }
+
+    /// Verifies that new items can't be added once the list is full, but existing
+    /// items can still be bumped.
+    #[test]
+    fn max_items() {
+        let mut state = TodoList::default();
+        for index in 0..todo::DEFAULT_MAX_ITEMS {
+            add_item(&mut state, format!("item {index}"), 1);
+        }
+
+        let limit_message = format!(
+            "You've reached the maximum of {} items",
+            todo::DEFAULT_MAX_ITEMS
+        );
+        let response = send_command(
+            TodoCommand::Add {
+                key: "one too many".into(),
+                category: None,
+                priority: None,
+            },
+            &mut state,
+        );
+        assert_eq!(limit_message, response);
+        assert_eq!(todo::DEFAULT_MAX_ITEMS, state.items.len());
+        assert!(!state.items.contains_key("one too many"));
+
+        // Copies are new items too.
+        let response = send_command(
+            TodoCommand::Duplicate {
+                key: "item 0".into(),
+                new_key: None,
+            },
+            &mut state,
+        );
+        assert_eq!(limit_message, response);
+        assert_eq!(todo::DEFAULT_MAX_ITEMS, state.items.len());
+
+        // Existing items can still be bumped.
+        add_item(&mut state, "item 0", 2);
+
+        // Once an item is removed there's room for a new one.
+        send_command(TodoCommand::Remove("item 1".into()), &mut state);
+        add_item(&mut state, "one too many", 1);
+    }
+
+    /// Verifies that moving an item only changes its category.
+    #[test]
+    fn move_item() {
+        let mut state = TodoList::default();
+        add_with_category(&mut state, "foo", "Foo", 1);
+        add_with_category(&mut state, "foo", "Foo", 2);
+        send_command(TodoCommand::Finish("foo".into()), &mut state);
+
+        let response = send_command(
+            TodoCommand::Move {
+                key: "foo".into(),
+                category: Some("Bar".into()),
+            },
+            &mut state,
+        );
+        assert_eq!(r#"Moved "foo" to [Bar]"#, response);
+        let foo = &state.items["foo"];
+        assert_eq!(Some("Bar".into()), foo.category);
+        assert_eq!(2, foo.priority);
+        assert!(foo.done);
+
+        let response = send_command(
+            TodoCommand::Move {
+                key: "foo".into(),
+                category: None,
+            },
+            &mut state,
+        );
+        assert_eq!(r#"Removed "foo" from its category"#, response);
+        assert_eq!(None, state.items["foo"].category);
+        assert_eq!(2, state.items["foo"].priority);
+
+        let response = send_command(
+            TodoCommand::Move {
+                key: "bar".into(),
+                category: Some("Bar".into()),
+            },
+            &mut state,
+        );
+        assert_eq!(r#"No item "bar" in your list"#, response);
+        assert!(!state.items.contains_key("bar"));
+    }
+
+    /// Verifies the counts in the list summary.
+    #[test]
+    fn stats() {
+        let mut state = TodoList::default();
+
+        let response = send_command(TodoCommand::Stats, &mut state);
+        assert_eq!(
+            format!(
+                "Stats for {USER_NAME}:\n\
+                Items: 0 (0 pending, 0 done)\n"
+            ),
+            response,
+        );
+
+        add_with_category(&mut state, "foo", "Foo", 1);
+        add_with_category(&mut state, "foo", "Foo", 2);
+        add_with_category(&mut state, "foo", "Foo", 3);
+        add_with_category(&mut state, "bar", "Foo", 1);
+        add_with_category(&mut state, "bar", "Foo", 2);
+        add_with_category(&mut state, "baz", "Baz", 1);
+        add_item(&mut state, "qux", 1);
+        send_command(TodoCommand::Finish("foo".into()), &mut state);
+        send_command(TodoCommand::Finish("baz".into()), &mut state);
+
+        let response = send_command(TodoCommand::Stats, &mut state);
+        assert_eq!(
+            format!(
+                "Stats for {USER_NAME}:\n\
+                Items: 4 (2 pending, 2 done)\n\
+                Categories:\n  \
+                [Baz]: 1\n  \
+                [Foo]: 2\n  \
+                Uncategorized: 1\n\
+                Top priority: \"bar\" (2)\n"
+            ),
+            response,
+        );
+    }
+
+    /// Verifies that autocomplete suggests matching keys in priority order.
+    #[test]
+    fn matching_keys() {
+        let mut state = TodoList::default();
+        add_item(&mut state, "Fix build", 1);
+        add_item(&mut state, "fix tests", 1);
+        add_item(&mut state, "fix tests", 2);
+        add_item(&mut state, "buy milk", 1);
+        add_item(&mut state, format!("fix {}", "x".repeat(100)), 1);
+
+        assert_eq!(
+            vec!["fix tests", "Fix build"],
+            todo::matching_keys(&state, "FIX")
+        );
+        assert_eq!(vec!["buy milk"], todo::matching_keys(&state, "milk"));
+        assert!(todo::matching_keys(&state, "dog").is_empty());
+
+        // Keys that are too long for Discord are left out.
+        assert_eq!(3, todo::matching_keys(&state, "").len());
+
+        // Suggestions are capped at Discord's limit.
+        for index in 0..30 {
+            add_item(&mut state, format!("item {index}"), 1);
         }
+        assert_eq!(25, todo::matching_keys(&state, "").len());
+    }
+
+    /// Verifies that a reminder is due once per day, after its scheduled time.
+    #[test]
+    fn reminder_due() {
+        let time = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let before = Utc.with_ymd_and_hms(2023, 6, 1, 8, 59, 0).unwrap();
+        let at = Utc.with_ymd_and_hms(2023, 6, 1, 9, 0, 0).unwrap();
+        let after = Utc.with_ymd_and_hms(2023, 6, 1, 17, 30, 0).unwrap();
+        let next_day = Utc.with_ymd_and_hms(2023, 6, 2, 9, 1, 0).unwrap();
+
+        // A reminder that's never been sent is due once the time has passed.
+        let reminder = Reminder {
+            time,
+            last_sent: None,
+        };
+        assert!(!todo::is_reminder_due(&reminder, before));
+        assert!(todo::is_reminder_due(&reminder, at));
+        assert!(todo::is_reminder_due(&reminder, after));
+
+        // Once sent, it isn't due again until the next day.
+        let reminder = Reminder {
+            time,
+            last_sent: Some(at),
+        };
+        assert!(!todo::is_reminder_due(&reminder, after));
+        assert!(todo::is_reminder_due(&reminder, next_day));
+
+        // Setting a reminder for a time that's already passed today doesn't send one
+        // until tomorrow.
+        let reminder = Reminder {
+            time,
+            last_sent: Some(after),
+        };
+        assert!(!todo::is_reminder_due(&reminder, after));
+        assert!(todo::is_reminder_due(&reminder, next_day));
+    }
 
-        TodoCommand::Print { category } => {
-            info!("Printing TODO list for user {user_id}");
+    /// Verifies that sending a reminder updates the cached copy of the list, so
+    /// that saving the cached copy doesn't make the reminder due again.
+    #[test]
+    fn mark_reminder_sent() {
+        let time = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let sent = Utc.with_ymd_and_hms(2023, 6, 1, 9, 0, 0).unwrap();
+        let user_id = UserId(1);
+        let now = Instant::now();
 
-            let user_name = &author.name;
-            let mut response = match &category {
-                Some(category) => format!("TODO list for {user_name} in category [{category}]:\n"),
-                None => format!("TODO list for {user_name}:\n"),
-            };
+        let cache = ListCache::new(CACHE_TTL);
+        let mut state = TodoList::new(user_id);
+        state.reminder = Some(Reminder {
+            time,
+            last_sent: None,
+        });
+        cache.insert(user_id, state, now);
 
-            // Get a list of the TODO list keys and sort it by item priority so that we
-            // can display the list in priority order.
-            let mut sorted_keys = todo_list
-                .items
-                .iter()
-                .filter(|(_, val)| category.is_none() || val.category == category)
-                .map(|(key, val)| (val.priority, key))
-                .collect::<Vec<_>>();
-            sorted_keys.sort_by_key(|(priority, _)| *priority);
+        cache.update(&user_id, |todo_list| {
+            todo::mark_reminder_sent(todo_list, sent)
+        });
+        let cached = cache.get(&user_id, now).unwrap();
+        assert_eq!(Some(sent), cached.reminder.unwrap().last_sent);
+        assert!(!todo::is_reminder_due(&cached.reminder.unwrap(), sent));
 
-            // Determine how wide the priority output needs to be displayed by finding the
-            // highest priority and calculating how many digits it will be.
-            let max_priority = todo_list
-                .items
-                .values()
-                .map(|item| item.priority)
-                .max()
-                .unwrap_or_default();
-            let priority_width = f32::log10((max_priority + 1) as f32).ceil() as usize;
-
-            // Build a string that displays the TODO list.
-            //
-            // NOTE: We iterate over the sorted keys in reverse order because
-            // `sort_by_key` sorts in ascending order and we want to print the list in
-            // descending order.
-            response.push_str("```\n");
-            for &(_, key) in sorted_keys.iter().rev() {
-                let item = &todo_list.items[key];
-                let check_mark = if item.done { 'X' } else { ' ' };
-                let priority = item.priority;
+        // Lists without a reminder are left alone.
+        let mut state = TodoList::new(user_id);
+        todo::mark_reminder_sent(&mut state, sent);
+        assert_eq!(None, state.reminder);
+    }
 
-                let category_str = if category.is_some() || item.category.is_none() {
-                    "".into()
-                } else {
-                    format!(" [{}]", item.category.as_ref().unwrap())
-                };
+    /// Verifies setting and clearing the daily reminder, and the reminder message.
+    #[test]
+    fn reminder() {
+        let mut state = TodoList::default();
 
-                writeln!(
-                    &mut response,
-                    "({priority: >priority_width$}) [{check_mark}]{category_str} {key}"
-                )
-                .unwrap();
-            }
-            response.push_str("```\n");
+        let time = NaiveTime::from_hms_opt(9, 30, 0).unwrap();
+        assert_eq!(time, todo::parse_reminder_time(" 09:30 ").unwrap());
+        assert!(todo::parse_reminder_time("9:30am").is_err());
+        assert!(todo::parse_reminder_time("25:00").is_err());
 
+        let response = send_command(TodoCommand::SetReminder(Some(time)), &mut state);
+        assert_eq!(
+            "I'll DM you your top items every day at 09:30 UTC",
             response
-        }
+        );
+        assert_eq!(Some(time), state.reminder.map(|reminder| reminder.time));
+
+        // Nothing is sent for an empty list.
+        assert_eq!(None, todo::reminder_message(&state));
+
+        // Only pending items are included, highest priority first.
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "bar", 1);
+        add_item(&mut state, "bar", 2);
+        add_item(&mut state, "baz", 1);
+        send_command(TodoCommand::Finish("baz".into()), &mut state);
+        assert_eq!(
+            Some(
+                "Here are your top TODO items for today:\n```\n(2) [ ] bar\n(1) [ ] foo\n```\n"
+                    .into()
+            ),
+            todo::reminder_message(&state)
+        );
+
+        let response = send_command(TodoCommand::SetReminder(None), &mut state);
+        assert_eq!("Turned off your daily reminder", response);
+        assert_eq!(None, state.reminder);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::todo::{self, TodoCommand, TodoList};
-    use poise::serenity_prelude::model::user::User;
-    use pretty_assertions::assert_eq;
+    /// Verifies that audit entries record the command and the item it changed,
+    /// and that read-only commands aren't audited.
+    #[test]
+    fn audit_entry() {
+        let user_id = UserId(1);
+        let now = Utc.with_ymd_and_hms(2023, 6, 15, 12, 30, 0).unwrap();
 
-    static USER_NAME: &str = "randomPoison";
+        let command = TodoCommand::Add {
+            key: "foo".into(),
+            category: Some("Bar".into()),
+            priority: None,
+        };
+        assert_eq!(
+            Some(AuditEntry {
+                user_id,
+                timestamp: now,
+                command: "Add".into(),
+                key: Some("foo".into()),
+            }),
+            AuditEntry::new(user_id, &command, true, now),
+        );
 
-    /// Builds a [Message] from the given `text`.
-    fn send_command(command: TodoCommand, state: &mut TodoList) -> String {
-        let mut user = User::default();
-        user.name = USER_NAME.into();
+        let command = TodoCommand::Rename {
+            from: "foo".into(),
+            to: "bar".into(),
+        };
+        let entry = AuditEntry::new(user_id, &command, true, now).unwrap();
+        assert_eq!("Rename", entry.command);
+        assert_eq!(Some("foo".into()), entry.key);
 
-        todo::handle_command(command, state, &user)
-    }
+        // Commands that change the whole list don't have a key.
+        let entry = AuditEntry::new(user_id, &TodoCommand::ClearDone, true, now).unwrap();
+        assert_eq!("ClearDone", entry.command);
+        assert_eq!(None, entry.key);
 
-    // Adds an item and verifies that the response is correct.
-    fn add_item(state: &mut TodoList, key: impl Into<String>, priority: u32) {
-        let key = key.into();
-        let response = send_command(
+        // Commands that didn't change the list aren't audited, whether they're
+        // read-only or were rejected.
+        let mut user = User::default();
+        user.id = user_id;
+        let mut state = TodoList::default();
+        add_item(&mut state, "foo", 1);
+        add_item(&mut state, "bar", 1);
+        let commands = [
+            TodoCommand::PrintOne("foo".into()),
+            TodoCommand::Remove("missing".into()),
             TodoCommand::Add {
-                key: key.clone(),
+                key: "baz".into(),
                 category: None,
+                priority: None,
             },
-            state,
-        );
+            TodoCommand::Rename {
+                from: "foo".into(),
+                to: "bar".into(),
+            },
+        ];
+        for command in commands {
+            let (_, changed) =
+                todo::handle_command_tracked(command.clone(), &mut state, &user, 2, now);
+            assert_eq!(None, AuditEntry::new(user_id, &command, changed, now));
+        }
 
-        let expected = match priority {
-            1 => format!("Added item {key:?} to your list"),
-            _ => format!("Updated item {key:?}, priority is {priority}"),
-        };
-        assert_eq!(expected, response);
+        let command = TodoCommand::Remove("foo".into());
+        let (_, changed) = todo::handle_command_tracked(command.clone(), &mut state, &user, 2, now);
+        assert!(AuditEntry::new(user_id, &command, changed, now).is_some());
     }
 
-    // Adds an item and verifies that the response is correct.
-    fn add_with_category(
-        state: &mut TodoList,
-        key: impl Into<String>,
-        category: impl Into<String>,
-        priority: u32,
-    ) {
-        let key = key.into();
-        let category = category.into();
+    /// Verifies that items lose priority for each week since they were last
+    /// bumped, down to a minimum of 1.
+    #[test]
+    fn effective_priority() {
+        let now = Utc.with_ymd_and_hms(2023, 6, 15, 12, 0, 0).unwrap();
+        let item = |priority, last_bumped| TodoItem {
+            priority,
+            last_bumped,
+            ..Default::default()
+        };
+        let days_ago = |days| Some(now - chrono::Duration::days(days));
 
-        let response = send_command(
-            TodoCommand::Add {
-                key: key.clone(),
-                category: Some(category.clone()),
-            },
-            state,
-        );
+        // Fresh items keep their priority.
+        assert_eq!(5, todo::effective_priority(&item(5, Some(now)), now));
+        assert_eq!(5, todo::effective_priority(&item(5, days_ago(6)), now));
 
-        let expected = match priority {
-            1 => format!("Added item [{category}] {key:?} to your list"),
-            _ => format!("Updated item [{category}] {key:?}, priority is {priority}"),
+        // Stale items lose priority for each full week.
+        assert_eq!(4, todo::effective_priority(&item(5, days_ago(7)), now));
+        assert_eq!(3, todo::effective_priority(&item(5, days_ago(20)), now));
+        assert_eq!(1, todo::effective_priority(&item(5, days_ago(365)), now));
+        assert_eq!(0, todo::effective_priority(&item(0, days_ago(365)), now));
+
+        // Items that were never bumped decay from when they were created, and items
+        // without either timestamp don't decay.
+        let created = TodoItem {
+            created_at: days_ago(14),
+            ..item(5, None)
         };
-        assert_eq!(expected, response);
+        assert_eq!(3, todo::effective_priority(&created, now));
+        assert_eq!(5, todo::effective_priority(&item(5, None), now));
     }
 
-    /// Tests that an item can be added from the list, displayed, and then removed.
+    /// Verifies that the list is sorted and displayed by decayed priority when
+    /// priority decay is turned on.
     #[test]
-    fn add_display_remove() {
+    fn priority_decay() {
         let mut state = TodoList::default();
+        for _ in 0..5 {
+            send_command(
+                TodoCommand::Add {
+                    key: "stale".into(),
+                    category: None,
+                    priority: None,
+                },
+                &mut state,
+            );
+        }
+        add_item(&mut state, "fresh", 1);
+        add_item(&mut state, "fresh", 2);
+        add_item(&mut state, "fresh", 3);
+        assert!(state.items["fresh"].last_bumped.is_some());
 
-        // Add an item with the key "foo" to the list.
-        add_item(&mut state, "foo", 1);
+        let stale = Utc::now() - chrono::Duration::weeks(3);
+        state.items.get_mut("stale").unwrap().last_bumped = Some(stale);
 
-        // Verify that the item can be displayed in the TODO list.
-        let response = send_command(TodoCommand::Print { category: None }, &mut state);
+        let print = TodoCommand::Print {
+            category: None,
+            sort: SortMode::Priority,
+            all: false,
+        };
+        let response = send_command(print.clone(), &mut state);
         assert_eq!(
             format!(
                 "TODO list for {USER_NAME}:\n\
                 ```\n\
-                (1) [ ] foo\n\
+                (5) [ ] stale\n\
+                (3) [ ] fresh\n\
                 ```\n"
             ),
             response,
         );
 
-        // Remove the item from the list.
-        let response = send_command(TodoCommand::Remove("foo".into()), &mut state);
-        assert_eq!(r#"Removed "foo" from your list"#, response);
-
-        // Verify that the list is now empty when printed.
-        let response = send_command(TodoCommand::Print { category: None }, &mut state);
+        let response = send_command(TodoCommand::SetPriorityDecay(true), &mut state);
+        assert_eq!(
+            "Items will now lose priority for each week since you last added them",
+            response
+        );
+        let response = send_command(print, &mut state);
         assert_eq!(
             format!(
                 "TODO list for {USER_NAME}:\n\
                 ```\n\
+                (3) [ ] fresh\n\
+                (2) [ ] stale\n\
                 ```\n"
             ),
             response,
         );
+
+        // Decay only changes how the list is shown, so bumping the item again
+        // restores its full priority.
+        assert_eq!(5, state.items["stale"].priority);
+        add_item(&mut state, "stale", 6);
+        assert_eq!(
+            6,
+            todo::effective_priority(&state.items["stale"], Utc::now())
+        );
     }
 
-    // Verifies that items in the TODO list are displayed in priority order.
     #[test]
-    fn priority_sort() {
-        let mut state = TodoList::default();
-
-        // Create 3 TODO items, each with different priority values.
-        add_item(&mut state, "foo", 1);
-        add_item(&mut state, "foo", 2);
-        add_item(&mut state, "foo", 3);
-        add_item(&mut state, "foo", 4);
-        add_item(&mut state, "foo", 5);
-        add_item(&mut state, "foo", 6);
-        add_item(&mut state, "foo", 7);
-        add_item(&mut state, "foo", 8);
-        add_item(&mut state, "foo", 9);
-        add_item(&mut state, "foo", 10);
-
-        add_item(&mut state, "foo bar", 1);
-        add_item(&mut state, "foo bar", 2);
+    fn parse_key_and_category() {
+        // Unquoted keys are a single word, and the rest is the category.
+        assert_eq!(("foo".into(), None), todo::parse_key_and_category("foo"));
+        assert_eq!(
+            ("foo".into(), Some("work".into())),
+            todo::parse_key_and_category("  foo   work ")
+        );
+        assert_eq!(
+            ("foo".into(), Some("big project".into())),
+            todo::parse_key_and_category("foo big project")
+        );
 
-        add_item(&mut state, "foo bar baz", 1);
+        // Quoted keys can have spaces, with the category quoted or not.
+        assert_eq!(
+            ("fix build".into(), None),
+            todo::parse_key_and_category("\"fix build\"")
+        );
+        assert_eq!(
+            ("fix build".into(), Some("big project".into())),
+            todo::parse_key_and_category("\"fix build\" big project")
+        );
+        assert_eq!(
+            ("fix build".into(), Some("big project".into())),
+            todo::parse_key_and_category("\"fix build\" \"big project\"")
+        );
 
-        // Verify that the items are displayed in the correct order.
-        let response = send_command(TodoCommand::Print { category: None }, &mut state);
+        // A key with an unclosed quote is a single word, quote and all.
         assert_eq!(
-            format!(
-                "TODO list for {USER_NAME}:\n\
-                ```\n\
-                (10) [ ] foo\n\
-                ( 2) [ ] foo bar\n\
-                ( 1) [ ] foo bar baz\n\
-                ```\n"
-            ),
-            response,
+            ("\"fix".into(), Some("build".into())),
+            todo::parse_key_and_category("\"fix build")
         );
     }
 
-    /// Verifies that items can be marked done.
     #[test]
-    fn mark_items_done() {
-        let mut state = TodoList::default();
-
-        // Create 2 TODO items with different priority values so that they'll print
-        // in a deterministic order.
-        add_item(&mut state, "foo", 1);
-        add_item(&mut state, "foo", 2);
-
-        add_item(&mut state, "foo bar", 1);
+    fn parse_category() {
+        // Unquoted categories can have several words.
+        assert_eq!(Some("work".into()), todo::parse_category("work"));
+        assert_eq!(
+            Some("big project".into()),
+            todo::parse_category("big project")
+        );
 
-        let response = send_command(TodoCommand::Finish("foo".into()), &mut state);
-        assert_eq!(r#"Marked "foo" as done"#, response);
+        // Quotes around the category are removed.
+        assert_eq!(
+            Some("big project".into()),
+            todo::parse_category("\"big project\"")
+        );
+        assert_eq!(
+            Some("big project".into()),
+            todo::parse_category("  \" big project \"  ")
+        );
 
-        // Verify that the items are displayed in the correct order.
-        let response = send_command(TodoCommand::Print { category: None }, &mut state);
+        // Quotes inside the category are kept.
         assert_eq!(
-            format!(
-                "TODO list for {USER_NAME}:\n\
-                ```\n\
-                (2) [X] foo\n\
-                (1) [ ] foo bar\n\
-                ```\n"
-            ),
-            response,
+            Some("the \"big\" project".into()),
+            todo::parse_category("the \"big\" project")
+        );
+        assert_eq!(
+            Some("\"big\" \"project\"".into()),
+            todo::parse_category("\"big\" \"project\"")
         );
+
+        // Empty categories are the same as leaving out the category.
+        assert_eq!(None, todo::parse_category(""));
+        assert_eq!(None, todo::parse_category("   "));
+        assert_eq!(None, todo::parse_category("\"\""));
     }
 
-    /// Verifies that a category can be set for each item and that categories are
-    /// correctly handled when displaying the TODO list.
+    /// Verifies that `count` counts pending and done items, leaving out removed
+    /// items.
     #[test]
-    fn categories() {
+    fn count() {
         let mut state = TodoList::default();
+        let response = send_command(TodoCommand::Count { category: None }, &mut state);
+        assert_eq!("You have 0 pending and 0 done items", response);
 
-        // Create 2 TODO items with different priority values so that they'll print
-        // in a deterministic order.
         add_with_category(&mut state, "foo", "Foo", 1);
-        add_with_category(&mut state, "foo", "Foo", 2);
-        add_item(&mut state, "foo bar", 1);
+        add_with_category(&mut state, "bar", "Foo", 1);
+        add_with_category(&mut state, "baz", "Baz", 1);
+        add_item(&mut state, "qux", 1);
+        add_item(&mut state, "removed", 1);
+        send_command(TodoCommand::Finish("bar".into()), &mut state);
+        send_command(TodoCommand::Finish("qux".into()), &mut state);
+        send_command(TodoCommand::Remove("removed".into()), &mut state);
 
-        // Verify that all items are displayed if no category is specified.
-        let response = send_command(TodoCommand::Print { category: None }, &mut state);
-        assert_eq!(
-            format!(
-                "TODO list for {USER_NAME}:\n\
-                ```\n\
-                (2) [ ] [Foo] foo\n\
-                (1) [ ] foo bar\n\
-                ```\n"
-            ),
-            response,
-        );
+        let response = send_command(TodoCommand::Count { category: None }, &mut state);
+        assert_eq!("You have 2 pending and 2 done items", response);
 
-        // Verify that a specific category can be displayed.
         let response = send_command(
-            TodoCommand::Print {
+            TodoCommand::Count {
                 category: Some("Foo".into()),
             },
             &mut state,
         );
+        assert_eq!("You have 1 pending and 1 done items in [Foo]", response);
+
+        let response = send_command(
+            TodoCommand::Count {
+                category: Some("Missing".into()),
+            },
+            &mut state,
+        );
+        assert_eq!("You have 0 pending and 0 done items in [Missing]", response);
+    }
+
+    #[test]
+    fn priority_color() {
+        assert_eq!(Colour::DARK_GREEN, todo::priority_color(0));
+        assert_eq!(Colour::DARK_GREEN, todo::priority_color(1));
+        assert_eq!(Colour::DARK_GREEN, todo::priority_color(4));
+        assert_eq!(Colour::GOLD, todo::priority_color(5));
+        assert_eq!(Colour::GOLD, todo::priority_color(9));
+        assert_eq!(Colour::RED, todo::priority_color(10));
+        assert_eq!(Colour::RED, todo::priority_color(1000));
+    }
+
+    #[test]
+    fn priority_width() {
+        assert_eq!(1, todo::priority_width(0));
+        assert_eq!(1, todo::priority_width(1));
+        assert_eq!(1, todo::priority_width(9));
+        assert_eq!(2, todo::priority_width(10));
+        assert_eq!(3, todo::priority_width(100));
+        assert_eq!(10, todo::priority_width(u32::MAX));
+    }
+
+    /// Verifies that priorities with different numbers of digits are padded to
+    /// line up.
+    #[test]
+    fn priority_alignment() {
+        let print = TodoCommand::Print {
+            category: None,
+            sort: SortMode::Priority,
+            all: false,
+        };
+
+        let mut state = TodoList::default();
+        add_item(&mut state, "one", 1);
+        let response = send_command(print.clone(), &mut state);
         assert_eq!(
             format!(
-                "TODO list for {USER_NAME} in category [Foo]:\n\
+                "TODO list for {USER_NAME}:\n\
                 ```\n\
-                (2) [ ] foo\n\
+                (1) [ ] one\n\
                 ```\n"
             ),
             response,
         );
 
-        // Verify that we can change the category of an existing item.
-        add_with_category(&mut state, "foo", "Bar", 3);
-        add_with_category(&mut state, "foo bar", "Foo", 2);
-        let response = send_command(TodoCommand::Print { category: None }, &mut state);
+        for (key, priority) in [("nine", 9), ("ten", 10), ("hundred", 100)] {
+            send_command(
+                TodoCommand::Add {
+                    key: key.into(),
+                    category: None,
+                    priority: Some(priority),
+                },
+                &mut state,
+            );
+        }
+        let response = send_command(print, &mut state);
         assert_eq!(
             format!(
                 "TODO list for {USER_NAME}:\n\
                 ```\n\
-                (3) [ ] [Bar] foo\n\
-                (2) [ ] [Foo] foo bar\n\
+                (100) [ ] hundred\n\
+                ( 10) [ ] ten\n\
+                (  9) [ ] nine\n\
+                (  1) [ ] one\n\
                 ```\n"
             ),
             response,
         );
     }
+
+    /// Verifies that lists are only loaded from the store when they aren't cached,
+    /// and that new lists are created for new users.
+    #[test]
+    fn cache_read_through() {
+        let store = FakeStore::default();
+        let cache = ListCache::new(CACHE_TTL);
+        let user_id = UserId(1);
+        let start = Instant::now();
+
+        // A new list is created and saved the first time.
+        let (_, first_use) = block_on(todo::load_cached(&store, &cache, user_id, start)).unwrap();
+        assert!(first_use);
+        assert_eq!((1, 1), (store.finds.get(), store.writes.get()));
+        assert!(store.lists.borrow().contains_key(&user_id));
+
+        // After that the cached copy is used until it expires.
+        let later = start + CACHE_TTL / 2;
+        let (_, first_use) = block_on(todo::load_cached(&store, &cache, user_id, later)).unwrap();
+        assert!(!first_use);
+        assert_eq!((1, 1), (store.finds.get(), store.writes.get()));
+
+        let expired = start + CACHE_TTL;
+        let (_, first_use) = block_on(todo::load_cached(&store, &cache, user_id, expired)).unwrap();
+        assert!(!first_use);
+        assert_eq!((2, 1), (store.finds.get(), store.writes.get()));
+
+        // Looking up a list without creating it doesn't cache missing lists.
+        let other = UserId(2);
+        assert!(block_on(todo::find_cached(&store, &cache, other, start))
+            .unwrap()
+            .is_none());
+        assert!(block_on(todo::find_cached(&store, &cache, other, start))
+            .unwrap()
+            .is_none());
+        assert_eq!((4, 1), (store.finds.get(), store.writes.get()));
+    }
+
+    /// Verifies that changes are written to both the store and the cache.
+    #[test]
+    fn cache_write_through() {
+        let store = FakeStore::default();
+        let cache = ListCache::new(CACHE_TTL);
+        let user_id = UserId(1);
+        let now = Instant::now();
+        let (mut state, _) = block_on(todo::load_cached(&store, &cache, user_id, now)).unwrap();
+        let writes = store.writes.get();
+
+        add_item(&mut state, "foo", 1);
+        block_on(todo::save_cached(&store, &cache, &state, now)).unwrap();
+        assert_eq!(writes + 1, store.writes.get());
+        assert!(store.lists.borrow()[&user_id].items.contains_key("foo"));
+
+        let (cached, _) = block_on(todo::load_cached(&store, &cache, user_id, now)).unwrap();
+        assert!(cached.items.contains_key("foo"));
+        assert_eq!(1, store.finds.get());
+
+        // If a write fails the cached copy is dropped, so the list is loaded from the
+        // store again next time.
+        store.fail_updates.set(true);
+        send_command(TodoCommand::Remove("foo".into()), &mut state);
+        assert!(block_on(todo::save_cached(&store, &cache, &state, now)).is_err());
+
+        let (loaded, _) = block_on(todo::load_cached(&store, &cache, user_id, now)).unwrap();
+        assert!(!loaded.items["foo"].archived);
+        assert_eq!(2, store.finds.get());
+    }
+
+    /// Verifies that only commands that actually change the list need to be saved.
+    #[test]
+    fn handle_command_tracked() {
+        let mut user = User::default();
+        user.name = USER_NAME.into();
+        let run = |command, state: &mut TodoList| {
+            let max_items = todo::DEFAULT_MAX_ITEMS;
+            todo::handle_command_tracked(command, state, &user, max_items, Utc::now()).1
+        };
+
+        let mut state = TodoList::default();
+        let print = TodoCommand::Print {
+            category: None,
+            sort: SortMode::Priority,
+            all: false,
+        };
+        assert!(!run(print.clone(), &mut state));
+
+        let add = TodoCommand::Add {
+            key: "foo".into(),
+            category: None,
+            priority: None,
+        };
+        assert!(run(add, &mut state));
+        assert!(!run(print, &mut state));
+        assert!(!run(TodoCommand::Stats, &mut state));
+
+        // Commands that fail or repeat the current state don't change the list.
+        assert!(!run(TodoCommand::Finish("missing".into()), &mut state));
+        assert!(!run(TodoCommand::SetPriorityDecay(false), &mut state));
+
+        assert!(run(TodoCommand::Finish("foo".into()), &mut state));
+        assert!(!run(TodoCommand::Finish("foo".into()), &mut state));
+        assert!(run(TodoCommand::SetPriorityDecay(true), &mut state));
+    }
 }